@@ -0,0 +1,309 @@
+// The MIT License (MIT)
+//
+// Copyright © 2022 <Brandon Lewis>
+//
+// Permission is hereby granted, free of charge, to any person
+// obtaining a copy of this software and associated documentation
+// files (the “Software”), to deal in the Software without
+// restriction, including without limitation the rights to use, copy,
+// modify, merge, publish, distribute, sublicense, and/or sell copies
+// of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS
+// BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN
+// ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+// Fork this project to create your own MIT license that you can
+// always link to.
+use core::fmt::Debug;
+use core::mem::discriminant;
+use crate::trs::{Types, SigmaRules};
+use crate::expr::{Expr, ReduceError};
+use crate::stepper::{Stepper, Strategy, Stop};
+use crate::parallel;
+use crate::names::Fresh;
+
+
+/*
+ * Differential testing of evaluators.
+ *
+ * Every evaluator in the crate should agree on the normal form of a
+ * term, whenever more than one of them finds it. `check` runs a term
+ * through a list of backends and reports the first pair that
+ * disagrees. New evaluators take part by implementing `Backend`.
+ *
+ * Each backend spends `fuel` in its own unit of work (single steps
+ * for a `Stepper`, parallel rounds for `parallel::normalize`), and
+ * strategies legitimately differ on whether a term terminates. So
+ * running out of fuel is inconclusive, and agrees with any outcome.
+ */
+
+
+/**
+ * What a backend observed when normalizing a term.
+ */
+#[derive(Debug)]
+pub enum Outcome<T: Types> {
+    Normal(Expr<T>),
+    OutOfFuel,
+    Failed(ReduceError<T>)
+}
+
+impl<T> Outcome<T> where T: Types, T::Val: PartialEq {
+    /**
+     * Normal forms agree up to alpha-equivalence, and failures agree
+     * if they are the same kind of `ReduceError`.
+     */
+    pub fn agrees_with(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Outcome::OutOfFuel, _) | (_, Outcome::OutOfFuel) => true,
+            (Outcome::Normal(a), Outcome::Normal(b)) => a.alpha_eq(b),
+            (Outcome::Failed(a), Outcome::Failed(b)) => discriminant(a) == discriminant(b),
+            _ => false
+        }
+    }
+}
+
+
+/**
+ * An evaluator that can take part in differential testing.
+ */
+pub trait Backend<T: Types> {
+    fn name(&self) -> String;
+    fn run(&mut self, expr: &Expr<T>, fuel: usize) -> Outcome<T>;
+}
+
+
+/**
+ * A `Stepper` driven by a given strategy, one redex per unit of fuel.
+ */
+#[derive(Clone, Debug)]
+pub struct Sequential<S>(pub S);
+
+impl<T, S> Backend<T> for Sequential<S>
+where T: Types + Clone, T::Sym: Fresh, S: Strategy<T> + Clone + Debug {
+    fn name(&self) -> String {
+        format!("{:?}", self.0)
+    }
+
+    fn run(&mut self, expr: &Expr<T>, fuel: usize) -> Outcome<T> {
+        let mut stepper = Stepper::new(Box::new(expr.clone()), self.0.clone());
+        match stepper.run(fuel) {
+            Ok(Stop::NormalForm) => Outcome::Normal(*stepper.into_term()),
+            Ok(_)                => Outcome::OutOfFuel,
+            Err(e)               => Outcome::Failed(e)
+        }
+    }
+}
+
+
+/**
+ * `parallel::normalize`, one round of parallel outermost reduction
+ * per unit of fuel.
+ */
+#[derive(Clone, Debug)]
+pub struct Parallel {
+    pub depth: usize
+}
+
+impl<T> Backend<T> for Parallel
+where T: Types + Clone,
+      T::Sym: Send + Fresh,
+      T::Val: Send,
+      <T::Val as SigmaRules>::Error: Send
+{
+    fn name(&self) -> String {
+        format!("{:?}", self)
+    }
+
+    fn run(&mut self, expr: &Expr<T>, fuel: usize) -> Outcome<T> {
+        let mut term = expr.clone();
+        match parallel::normalize(&mut term, self.depth, fuel) {
+            Ok(_) if term.redexes().is_empty() => Outcome::Normal(term),
+            Ok(_)  => Outcome::OutOfFuel,
+            Err(e) => Outcome::Failed(e)
+        }
+    }
+}
+
+
+/**
+ * Two backends that disagree on a term.
+ */
+#[derive(Debug)]
+pub struct Mismatch<T: Types> {
+    pub left: (String, Outcome<T>),
+    pub right: (String, Outcome<T>)
+}
+
+
+/**
+ * Run `expr` through every backend and check that the outcomes agree.
+ *
+ * On success, returns each backend's outcome, in order. On failure,
+ * returns the first backend paired with the first later one that
+ * disagrees with it.
+ */
+pub fn check<T>(
+    expr: &Expr<T>,
+    fuel: usize,
+    backends: &mut [&mut dyn Backend<T>]
+) -> Result<Vec<Outcome<T>>, Mismatch<T>>
+where T: Types, T::Val: PartialEq {
+    let mut outcomes: Vec<Outcome<T>> = backends.iter_mut()
+        .map(|b| b.run(expr, fuel))
+        .collect();
+
+    for i in 0..outcomes.len() {
+        for j in i + 1..outcomes.len() {
+            if !outcomes[i].agrees_with(&outcomes[j]) {
+                let right = outcomes.swap_remove(j);
+                let left = outcomes.swap_remove(i);
+                return Err(Mismatch {
+                    left: (backends[i].name(), left),
+                    right: (backends[j].name(), right)
+                });
+            }
+        }
+    }
+
+    Ok(outcomes)
+}
+
+
+/**
+ * Like `check`, but panics with both outcomes on a mismatch. Meant for
+ * use in tests.
+ */
+pub fn assert_conformance<T>(
+    expr: &Expr<T>,
+    fuel: usize,
+    backends: &mut [&mut dyn Backend<T>]
+) where T: Types + Debug, T::Val: PartialEq {
+    if let Err(mismatch) = check(expr, fuel, backends) {
+        panic!(
+            "backends disagree on {:?}:\n  {}: {:?}\n  {}: {:?}",
+            expr,
+            mismatch.left.0, mismatch.left.1,
+            mismatch.right.0, mismatch.right.1
+        );
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stepper::{NormalOrder, ApplicativeOrder};
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct ConfTypes;
+
+    #[derive(Clone, Debug, PartialEq)]
+    enum Val {
+        Int(i32),
+        Succ
+    }
+
+    impl SigmaRules for Val {
+        type Error = ();
+
+        fn apply(f: Self, x: Self) -> Result<Self, ()> {
+            match (f, x) {
+                (Val::Succ, Val::Int(n)) => Ok(Val::Int(n + 1)),
+                _ => Err(())
+            }
+        }
+    }
+
+    impl Types for ConfTypes {
+        type Val = Val;
+        type Sym = String;
+    }
+
+    type E = Expr<ConfTypes>;
+
+    /* A backend that gets sigma rules wrong. */
+    struct Broken;
+
+    impl Backend<ConfTypes> for Broken {
+        fn name(&self) -> String {
+            "Broken".into()
+        }
+
+        fn run(&mut self, _: &E, _: usize) -> Outcome<ConfTypes> {
+            Outcome::Normal(Expr::Val(Val::Int(0)))
+        }
+    }
+
+    fn succ_twice() -> Box<E> {
+        // (\f.\x.f (f x)) Succ 0
+        let twice = E::lambda("f", E::lambda("x", E::apply(
+            E::var("f"),
+            E::apply(E::var("f"), E::var("x"))
+        )));
+        E::apply(E::apply(twice, E::val(Val::Succ)), E::val(Val::Int(0)))
+    }
+
+    fn omega() -> Box<E> {
+        let dup = || E::lambda("x", E::apply(E::var("x"), E::var("x")));
+        E::apply(dup(), dup())
+    }
+
+    #[test]
+    fn test_builtin_backends_agree() {
+        let mut normal = Sequential(NormalOrder);
+        let mut applicative = Sequential(ApplicativeOrder);
+        let mut par = Parallel {depth: 2};
+
+        let outcomes = check(
+            &succ_twice(),
+            100,
+            &mut [&mut normal, &mut applicative, &mut par]
+        ).unwrap();
+        assert_eq!(outcomes.len(), 3);
+        for outcome in outcomes {
+            assert!(outcome.agrees_with(&Outcome::Normal(Expr::Val(Val::Int(2)))));
+            assert!(matches!(outcome, Outcome::Normal(_)));
+        }
+    }
+
+    #[test]
+    fn test_out_of_fuel_is_inconclusive() {
+        // (\y.0) Ω: normal order discards Ω, applicative order loops.
+        let term = E::apply(E::lambda("y", E::val(Val::Int(0))), omega());
+        let outcomes = check(
+            &term,
+            10,
+            &mut [&mut Sequential(NormalOrder), &mut Sequential(ApplicativeOrder)]
+        ).unwrap();
+        assert!(matches!(outcomes[0], Outcome::Normal(Expr::Val(Val::Int(0)))));
+        assert!(matches!(outcomes[1], Outcome::OutOfFuel));
+    }
+
+    #[test]
+    fn test_mismatch() {
+        let mismatch = check(
+            &succ_twice(),
+            100,
+            &mut [&mut Sequential(NormalOrder), &mut Parallel {depth: 0}, &mut Broken]
+        ).unwrap_err();
+        assert_eq!(mismatch.left.0, "NormalOrder");
+        assert_eq!(mismatch.right.0, "Broken");
+    }
+
+    #[test]
+    #[should_panic(expected = "backends disagree")]
+    fn test_assert_conformance() {
+        assert_conformance(&succ_twice(), 100, &mut [&mut Sequential(NormalOrder), &mut Broken]);
+    }
+}
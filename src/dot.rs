@@ -0,0 +1,106 @@
+// The MIT License (MIT)
+//
+// Copyright © 2022 <Brandon Lewis>
+//
+// Permission is hereby granted, free of charge, to any person
+// obtaining a copy of this software and associated documentation
+// files (the “Software”), to deal in the Software without
+// restriction, including without limitation the rights to use, copy,
+// modify, merge, publish, distribute, sublicense, and/or sell copies
+// of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS
+// BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN
+// ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+// Fork this project to create your own MIT license that you can
+// always link to.
+
+
+/**
+ * A minimal writer for Graphviz DOT output.
+ *
+ * This is only as much of the DOT language as we need to draw terms
+ * and data graphs: labeled nodes and (optionally labeled) edges. The
+ * caller chooses node names, which is how sharing gets drawn: a node
+ * that is reachable along several paths is emitted once, under one
+ * name, and every edge to it uses that name.
+ *
+ * Edges are drawn in the order they are added (`ordering=out`), so
+ * the function and argument of an application keep their position.
+ */
+pub struct Dot(String);
+
+impl Dot {
+    pub fn new() -> Self {
+        Dot(String::from("digraph {\n  ordering=out;\n"))
+    }
+
+    pub fn node(&mut self, name: &str, label: &str) {
+        self.0.push_str(&format!(
+            "  \"{}\" [label=\"{}\"];\n",
+            escape(name),
+            escape(label)
+        ));
+    }
+
+    pub fn edge(&mut self, src: &str, dst: &str, label: Option<&str>) {
+        self.0.push_str(&format!("  \"{}\" -> \"{}\"", escape(src), escape(dst)));
+        if let Some(label) = label {
+            self.0.push_str(&format!(" [label=\"{}\"]", escape(label)));
+        }
+        self.0.push_str(";\n");
+    }
+
+    pub fn finish(self) -> String {
+        let mut ret = self.0;
+        ret.push_str("}\n");
+        ret
+    }
+}
+
+impl Default for Dot {
+    fn default() -> Self { Self::new() }
+}
+
+
+// DOT strings are double-quoted, so quotes and backslashes in labels
+// (which `Debug` is fond of producing) have to be escaped.
+fn escape(s: &str) -> String {
+    let mut ret = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"'  => ret.push_str("\\\""),
+            '\\' => ret.push_str("\\\\"),
+            '\n' => ret.push_str("\\n"),
+            c    => ret.push(c),
+        }
+    }
+    ret
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape() {
+        let mut dot = Dot::new();
+        dot.node("n0", &format!("{:?}", "x"));
+        dot.edge("n0", "n0", Some("0"));
+        assert_eq!(
+            dot.finish(),
+            "digraph {\n  ordering=out;\n  \"n0\" [label=\"\\\"x\\\"\"];\n  \"n0\" -> \"n0\" [label=\"0\"];\n}\n"
+        );
+    }
+}
@@ -0,0 +1,292 @@
+// The MIT License (MIT)
+//
+// Copyright © 2022 <Brandon Lewis>
+//
+// Permission is hereby granted, free of charge, to any person
+// obtaining a copy of this software and associated documentation
+// files (the “Software”), to deal in the Software without
+// restriction, including without limitation the rights to use, copy,
+// modify, merge, publish, distribute, sublicense, and/or sell copies
+// of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS
+// BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN
+// ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+// Fork this project to create your own MIT license that you can
+// always link to.
+use core::fmt::Display;
+use crate::trs::Types;
+use crate::expr::{Expr, Path, Reduction};
+use crate::dot::Dot;
+use crate::names::Fresh;
+
+
+/**
+ * A fragment of the reduction graph of a term.
+ *
+ * Nodes are terms, deduplicated up to alpha-equivalence, and there is
+ * an edge for every single-step reduction between them. Node 0 is the
+ * starting term. Because terms are deduplicated, this is a graph
+ * rather than a tree, and may have cycles (e.g. for `Ω`).
+ */
+#[derive(Clone, Debug)]
+pub struct ReductionGraph<T: Types> {
+    pub terms: Vec<Expr<T>>,
+    pub edges: Vec<Edge>,
+    // Redexes whose contraction failed, as the term they are in and
+    // their path in it. They have no edge.
+    pub failed: Vec<(usize, Path)>,
+    // False if a bound cut the exploration short, or a contraction
+    // failed.
+    pub complete: bool
+}
+
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Edge {
+    pub from: usize,
+    pub to: usize,
+    // Where the contracted redex was in `from`
+    pub path: Path,
+    pub reduction: Reduction
+}
+
+
+/**
+ * Explore every reduction sequence from `expr` up to `max_depth`
+ * steps long, keeping at most `max_terms` distinct terms.
+ *
+ * The exploration is breadth-first, so when `max_terms` cuts it short,
+ * the terms closest to the start are the ones kept. Redexes whose
+ * contraction fails (e.g. a primitive rejecting its argument) are
+ * recorded in `failed`, and make the graph incomplete.
+ */
+pub fn explore<T>(expr: &Expr<T>, max_depth: usize, max_terms: usize) -> ReductionGraph<T>
+where T: Types + Clone, T::Sym: Fresh, T::Val: PartialEq {
+    let mut graph = ReductionGraph {
+        terms: vec![expr.clone()],
+        edges: Vec::new(),
+        failed: Vec::new(),
+        complete: true
+    };
+
+    let mut frontier = vec![0];
+    for depth in 0..=max_depth {
+        let mut next = Vec::new();
+
+        for from in frontier {
+            let redexes = graph.terms[from].redexes();
+            if depth == max_depth {
+                graph.complete &= redexes.is_empty();
+                continue;
+            }
+
+            for path in redexes {
+                let (reduction, contractum) = match graph.terms[from].contractum_at(&path) {
+                    Ok(result) => result,
+                    Err(_)     => {
+                        graph.failed.push((from, path));
+                        graph.complete = false;
+                        continue;
+                    }
+                };
+                let mut term = graph.terms[from].clone();
+                *term.at_mut(&path).expect("path came from redexes()") = *contractum;
+
+                let to = match graph.terms.iter().position(|t| t.alpha_eq(&term)) {
+                    Some(to) => to,
+                    None if graph.terms.len() < max_terms => {
+                        graph.terms.push(term);
+                        next.push(graph.terms.len() - 1);
+                        graph.terms.len() - 1
+                    },
+                    None => {
+                        graph.complete = false;
+                        continue;
+                    }
+                };
+
+                graph.edges.push(Edge {from, to, path, reduction});
+            }
+        }
+
+        frontier = next;
+    }
+
+    graph
+}
+
+
+impl<T> ReductionGraph<T> where T: Types + Clone {
+    /* Indices of the terms with no redexes. */
+    pub fn normal_forms(&self) -> Vec<usize> {
+        (0..self.terms.len())
+            .filter(|i| self.terms[*i].redexes().is_empty())
+            .collect()
+    }
+
+    /* Edges leaving the term with index `from`. */
+    pub fn successors(&self, from: usize) -> impl Iterator<Item = &Edge> {
+        self.edges.iter().filter(move |e| e.from == from)
+    }
+
+    /**
+     * Render the graph as Graphviz DOT, labeling terms with their
+     * `Display` form and edges with the kind of reduction.
+     */
+    pub fn to_dot(&self) -> String
+    where T::Sym: Display, T::Val: Display {
+        let mut dot = Dot::new();
+        for (i, term) in self.terms.iter().enumerate() {
+            dot.node(&format!("t{}", i), &term.to_string());
+        }
+        for edge in &self.edges {
+            let label = match edge.reduction {
+                Reduction::Beta  => "β",
+                Reduction::Sigma => "σ",
+                Reduction::Select => "if"
+            };
+            dot.edge(&format!("t{}", edge.from), &format!("t{}", edge.to), Some(label));
+        }
+        dot.finish()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expr::Dir::*;
+    use crate::trs::SigmaRules;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct ExploreTypes;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Atom;
+
+    impl SigmaRules for Atom {
+        type Error = ();
+    }
+
+    impl Display for Atom {
+        fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            write!(f, "a")
+        }
+    }
+
+    impl Types for ExploreTypes {
+        type Val = Atom;
+        type Sym = String;
+    }
+
+    type E = Expr<ExploreTypes>;
+
+    #[test]
+    fn test_diamond() {
+        // (\x.x) ((\y.y) z) has two redexes. Contracting either gives
+        // an alpha-variant of (\x.x) z, and then z.
+        let term = E::apply(
+            E::lambda("x", E::var("x")),
+            E::apply(E::lambda("y", E::var("y")), E::var("z"))
+        );
+        let graph = explore(&term, 10, 100);
+
+        assert!(graph.complete);
+        assert_eq!(graph.terms.len(), 3);
+        assert_eq!(graph.edges.len(), 3);
+        assert_eq!(graph.normal_forms(), vec![2]);
+        assert_eq!(graph.terms[2], *E::var("z"));
+        assert_eq!(
+            graph.successors(0).map(|e| e.path.clone()).collect::<Vec<_>>(),
+            vec![vec![], vec![Arg]]
+        );
+    }
+
+    #[test]
+    fn test_omega_is_a_cycle() {
+        // (\x.x x) (\y.y y) reduces to an alpha-variant of itself.
+        let term = E::apply(
+            E::lambda("x", E::apply(E::var("x"), E::var("x"))),
+            E::lambda("y", E::apply(E::var("y"), E::var("y")))
+        );
+        let graph = explore(&term, 10, 100);
+
+        assert!(graph.complete);
+        assert_eq!(graph.terms.len(), 1);
+        assert_eq!(graph.edges, vec![Edge {
+            from: 0,
+            to: 0,
+            path: vec![],
+            reduction: Reduction::Beta
+        }]);
+        assert!(graph.normal_forms().is_empty());
+    }
+
+    #[test]
+    fn test_bounds() {
+        // (\x.x x x) (\x.x x x) grows forever.
+        let triple = || E::lambda("x", E::apply(
+            E::apply(E::var("x"), E::var("x")),
+            E::var("x")
+        ));
+        let graph = explore(&E::apply(triple(), triple()), 3, 100);
+        assert!(!graph.complete);
+        assert!(graph.terms.len() >= 4);
+
+        let graph = explore(&E::apply(triple(), triple()), 100, 2);
+        assert!(!graph.complete);
+        assert_eq!(graph.terms.len(), 2);
+    }
+
+    #[test]
+    fn test_failed_contraction() {
+        // (\x.x) (a a): Atom doesn't apply to anything.
+        let term = E::apply(
+            E::lambda("x", E::var("x")),
+            E::apply(E::val(Atom), E::val(Atom))
+        );
+        let graph = explore(&term, 10, 100);
+
+        assert!(!graph.complete);
+        assert_eq!(graph.terms.len(), 2);
+        assert_eq!(graph.failed, vec![(0, vec![Arg]), (1, vec![])]);
+    }
+
+    #[test]
+    fn test_diamond_snapshot() {
+        // (\x.x x) ((\y.y) z)
+        let term = E::apply(
+            E::lambda("x", E::apply(E::var("x"), E::var("x"))),
+            E::apply(E::lambda("y", E::var("y")), E::var("z"))
+        );
+        crate::snapshot::Snapshots::in_package()
+            .assert("explore_diamond", &explore(&term, 10, 100).to_dot());
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let term = E::apply(E::lambda("x", E::var("x")), E::val(Atom));
+        assert_eq!(
+            explore(&term, 1, 10).to_dot(),
+            concat!(
+                "digraph {\n",
+                "  ordering=out;\n",
+                "  \"t0\" [label=\"(λx.x) a\"];\n",
+                "  \"t1\" [label=\"a\"];\n",
+                "  \"t0\" -> \"t1\" [label=\"β\"];\n",
+                "}\n"
+            )
+        );
+    }
+}
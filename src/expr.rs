@@ -26,8 +26,12 @@
 // always link to.
 
 use core::iter::Iterator;
-use core::fmt::Debug;
-use crate::{Token, Types, SigmaRules};
+use core::fmt::{Debug, Display};
+use crate::trs::{Types, SigmaRules};
+use crate::dot::Dot;
+use crate::latex;
+use crate::stack::SmallStack;
+use crate::names::{free_vars, substitute_avoiding, Fresh, NameSupply};
 
 
 /**
@@ -46,6 +50,11 @@ use crate::{Token, Types, SigmaRules};
  *
  * To go further than that, we'd need to abstract over memory
  * management as well. I'm still not sure how to do that.
+ *
+ * On threads: an `Expr` owns its whole tree through `Box`, so it is
+ * `Send` and `Sync` exactly when `T::Sym` and `T::Val` are. No `Arc`
+ * is needed to hand a term to a worker thread; an `Arc<Expr<T>>` can
+ * be shared read-only between threads as usual.
  */
 #[derive(Clone, Debug, PartialEq)]
 pub enum Expr<T: Types> {
@@ -55,6 +64,72 @@ pub enum Expr<T: Types> {
     App(Box<Expr<T>>, Box<Expr<T>>)
 }
 
+/**
+ * Tokens for the postfix encoding of lambda expressions.
+ *
+ * Operands are pushed onto a stack, `Lambda` pops a binder and a
+ * body, and `Apply` pops a function and an argument. So `x y Apply`
+ * is `(x y)`, and `x y Lambda` is `\x.y`.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub enum Token<T: Types> {
+    Val(T::Val),
+    Id(T::Sym),
+    Lambda,
+    Apply
+}
+
+impl<T: Types> Token<T> {
+    pub fn val<B>(v: B) -> Self
+    where B: Into<T::Val> {
+        Token::Val(v.into())
+    }
+
+    pub fn id<B>(name: B) -> Self
+    where B: Into<T::Sym> {
+        Token::Id(name.into())
+    }
+
+    /**
+     * Check that `stream` would parse, without building the tree.
+     *
+     * This makes the same checks as `Expr::parse`, in one pass: no
+     * operator underflows the stack, every `Lambda` binder is an `Id`,
+     * and exactly one term is left at the end. Only one flag per stack
+     * slot is kept, inline for shallow streams, so this is cheap enough
+     * to run on untrusted input before parsing it.
+     */
+    pub fn validate<'a>(
+        stream: impl IntoIterator<Item = &'a Token<T>>
+    ) -> Result<(), ParseError<T>> where T: 'a {
+        // Whether each operand on the stack is a bare identifier.
+        let mut stack: SmallStack<bool, PARSE_STACK_INLINE> = SmallStack::new();
+
+        for token in stream { match token {
+            Token::Val(_) => stack.push(false),
+            Token::Id(_)  => stack.push(true),
+            Token::Lambda => {
+                stack.pop().ok_or(ParseError::Underflow)?;
+                if !stack.pop().ok_or(ParseError::Underflow)? {
+                    return Err(ParseError::NotAVar);
+                }
+                stack.push(false);
+            },
+            Token::Apply  => {
+                stack.pop().ok_or(ParseError::Underflow)?;
+                stack.pop().ok_or(ParseError::Underflow)?;
+                stack.push(false);
+            }
+        } }
+
+        if stack.len() == 1 {
+            Ok(())
+        } else {
+            Err(ParseError::EOF)
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum ParseError<T: Types> {
     Unexpected(Token<T>),
@@ -66,13 +141,107 @@ pub enum ParseError<T: Types> {
 
 #[derive(Debug)]
 pub enum ReduceError<T: Types> {
-    NameCollision,
     NotApplicable,
     NotBetaReducible,
-    NotSigmaReducible(<T::Val as SigmaRules>::Error)
+    // A primitive rejected its argument. `func` and `arg` are the
+    // values of the sigma redex at `path`.
+    Delta {
+        func: T::Val,
+        arg: T::Val,
+        path: Path,
+        error: <T::Val as SigmaRules>::Error
+    },
+    InvalidPath,
+    // The term would grow past a configured size limit.
+    HeapLimit,
+    // A free variable, under `FreeVars::Error`.
+    FreeVariable(T::Sym)
+}
+
+impl<T: Types> ReduceError<T> {
+    /* Re-root the error path of a failure that happened in the subterm
+     * at `prefix`. */
+    pub fn under(mut self, prefix: &[Dir]) -> Self {
+        if let ReduceError::Delta {path, ..} = &mut self {
+            path.splice(0..0, prefix.iter().copied());
+        }
+        self
+    }
+}
+
+
+/**
+ * One step along a path from the root of an expression to a subterm.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Dir {
+    // Into the body of a lambda
+    Body,
+    // Into the function of an application
+    Func,
+    // Into the argument of an application
+    Arg
+}
+
+/* Paths compare in pre-order: a node sorts before its children, and
+ * the function of an application before its argument. */
+pub type Path = Vec<Dir>;
+
+
+/**
+ * The kinds of redex in an expression.
+ *
+ * A beta redex is a lambda applied to anything. A sigma redex is a
+ * value applied to a value, and is reduced by `SigmaRules`. A select
+ * redex is a value which selects (see `SigmaRules::select`) applied
+ * to two arguments, and reduces to one of them.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Reduction {
+    Beta,
+    Sigma,
+    Select
 }
 
 
+/**
+ * Summary statistics for a term, from `Expr::stats()`.
+ *
+ * The `Display` impl formats them as a short report.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Stats<S> {
+    // Nodes in the tree
+    pub size: usize,
+    // Nodes on the longest path from the root, so a leaf has depth 1
+    pub depth: usize,
+    pub binders: usize,
+    pub free_vars: Vec<S>,
+    pub beta_redexes: usize,
+    // Including select redexes, which are also down to the values.
+    pub sigma_redexes: usize,
+    // Subterms which differ up to alpha-equivalence. This is how many
+    // nodes a graph representation with full sharing would need.
+    pub distinct_subterms: usize
+}
+
+impl<S> Stats<S> {
+    /**
+     * How many tree nodes each node of a fully shared graph would
+     * stand for. 1.0 means nothing repeats.
+     */
+    pub fn sharing_factor(&self) -> f64 {
+        self.size as f64 / self.distinct_subterms as f64
+    }
+}
+
+
+/* Parse stack slots kept inline, before falling back to the heap. */
+const PARSE_STACK_INLINE: usize = 16;
+
+/* The most parse stack slots reserved from a size hint. */
+const PARSE_RESERVE_LIMIT: usize = 1024;
+
 type ParseResult<T> = core::result::Result<Box<Expr<T>>, ParseError<T>>;
 type ReduceResult<T> = core::result::Result<Box<Expr<T>>, ReduceError<T>>;
 
@@ -102,61 +271,152 @@ impl<'a, T: 'a> Expr<T> where T: Types + Clone {
         Box::new(Expr::App(func, arg))
     }
 
-    /* Reduce an expression tree
-     *
-     * This performs one reduction pass over the tree. The result
-     * itself might still be reducible (i.e., in the presence of
-     * recursion).
-     */
-    pub fn reduce(self) -> ReduceResult<T> {
+    /* Classify this node as a redex, if it is one. */
+    pub fn redex(&self) -> Option<Reduction> {
         match self {
-            // We distinguish between beta and sigma reduction by
-            // inspecting the function term. A lambda implies beta
-            // reduction, while a value implies sigma reduction.
-            Self::App(f, x) => match *f {
-                Self::Lambda(a, b) => Ok(b.beta_reduce(a, x)?),
-                Self::Val(v)       => Self::sigma_reduce(v, x),
-                _                  => Err(ReduceError::NotApplicable)
+            Self::App(f, x) => match (&**f, &**x) {
+                (Self::Lambda(_, _), _)      => Some(Reduction::Beta),
+                (Self::App(b, _), _) if Self::selects(b).is_some() =>
+                    Some(Reduction::Select),
+                // A boolean waits for both of its arguments.
+                (Self::Val(b), _) if b.select().is_some() => None,
+                (Self::Val(_), Self::Val(_)) => Some(Reduction::Sigma),
+                _                            => None
             },
-            _ => Err(ReduceError::NotBetaReducible)
+            _ => None
         }
     }
 
-    // Perform the substitution implied by the beta reduction.
-    fn beta_reduce(self, var: T::Sym, exp: Box<Self>) -> ReduceResult<T> {
+    /* The boolean this term is, if it is a value which selects. */
+    fn selects(&self) -> Option<bool> {
         match self {
-            Self::Var(v)       if v == var => Ok(exp.clone()),
-            Self::Lambda(a, _) if a == var => Err(ReduceError::NameCollision),
-            Self::Lambda(a, b)             => Ok(Self::lambda(a, b.beta_reduce(var, exp)?)),
-            Self::App(f, x)                => Ok(Self::apply(
-                f.beta_reduce(var.clone(), exp.clone())?,
-                x.beta_reduce(var, exp)?)),
-            x                              => Ok(Box::new(x))
+            Self::Val(v) => v.select(),
+            _            => None
         }
     }
 
-    // Sigma reduction is delegated to the external value type, T::Val
-    fn sigma_reduce(func: T::Val, arg: Box<Self>) -> ReduceResult<T> {
-        match *arg {
-            Self::Val(x) => T::Val::apply(func, x)
-                .map_or_else(
-                    |e| Err(ReduceError::NotSigmaReducible(e)),
-                    |v| Ok(Self::val(v))
-                ),
-            _ => {panic!("omg, multiple args! panic!");}
+    /* Return the subterm at `path`, if there is one. */
+    pub fn at(&self, path: &[Dir]) -> Option<&Self> {
+        path.iter().try_fold(self, |e, dir| match (e, dir) {
+            (Self::Lambda(_, b), Dir::Body) => Some(&**b),
+            (Self::App(f, _),    Dir::Func) => Some(&**f),
+            (Self::App(_, x),    Dir::Arg)  => Some(&**x),
+            _                               => None
+        })
+    }
+
+    pub fn at_mut(&mut self, path: &[Dir]) -> Option<&mut Self> {
+        path.iter().try_fold(self, |e, dir| match (e, dir) {
+            (Self::Lambda(_, b), Dir::Body) => Some(&mut **b),
+            (Self::App(f, _),    Dir::Func) => Some(&mut **f),
+            (Self::App(_, x),    Dir::Arg)  => Some(&mut **x),
+            _                               => None
+        })
+    }
+
+    /* Paths to every redex in the tree, leftmost-outermost first. */
+    pub fn redexes(&self) -> Vec<Path> {
+        let mut ret = Vec::new();
+        self.redexes_rec(&mut Vec::new(), &mut ret);
+        ret
+    }
+
+    fn redexes_rec(&self, here: &mut Path, ret: &mut Vec<Path>) {
+        if self.redex().is_some() {
+            ret.push(here.clone());
+        }
+
+        match self {
+            Self::Lambda(_, b) => {
+                here.push(Dir::Body);
+                b.redexes_rec(here, ret);
+                here.pop();
+            },
+            Self::App(f, x) => {
+                here.push(Dir::Func);
+                f.redexes_rec(here, ret);
+                here.pop();
+                here.push(Dir::Arg);
+                x.redexes_rec(here, ret);
+                here.pop();
+            },
+            _ => ()
+        }
+    }
+
+    /**
+     * Replace every free variable `v` for which `f(v)` returns a term
+     * with that term, leaving occurrences bound by a lambda alone.
+     *
+     * Nothing is renamed, so the replacements should be closed, or at
+     * least not mention names bound where they land.
+     */
+    pub fn replace_free(&self, mut f: impl FnMut(&T::Sym) -> Option<Self>) -> Self {
+        self.replace_free_rec(&mut f, &mut Vec::new())
+    }
+
+    fn replace_free_rec<F>(&self, f: &mut F, bound: &mut Vec<T::Sym>) -> Self
+    where F: FnMut(&T::Sym) -> Option<Self> {
+        match self {
+            Self::Var(v) if !bound.contains(v) => f(v).unwrap_or_else(|| self.clone()),
+            Self::Var(_) | Self::Val(_) => self.clone(),
+            Self::Lambda(a, b) => {
+                bound.push(a.clone());
+                let body = b.replace_free_rec(f, bound);
+                bound.pop();
+                Self::Lambda(a.clone(), Box::new(body))
+            },
+            Self::App(g, x) => Self::App(
+                Box::new(g.replace_free_rec(f, bound)),
+                Box::new(x.replace_free_rec(f, bound))
+            )
+        }
+    }
+
+    /* The number of nodes in the tree. */
+    pub fn size(&self) -> usize {
+        match self {
+            Self::Lambda(_, b) => 1 + b.size(),
+            Self::App(f, x)    => 1 + f.size() + x.size(),
+            _                  => 1
         }
     }
 
 
+    /**
+     * Parse a postfix token stream.
+     *
+     * Symbols and values are cloned out of the borrowed tokens. Use
+     * `parse_owned` to avoid the copies.
+     */
     pub fn parse(
         input: impl Iterator<Item = &'a Token<T>>
     ) -> ParseResult<T> {
-        let mut stack: Vec<Box<Self>> = Vec::new();
+        Self::parse_owned(input.cloned())
+    }
+
+    /**
+     * Parse a postfix token stream, moving symbols and values into the
+     * tree.
+     *
+     * Nothing is cloned, so when `T::Sym` is a `&str` or `Cow<str>`
+     * borrowed from an input buffer, the tree shares its identifiers
+     * with that buffer rather than duplicating them.
+     */
+    pub fn parse_owned(
+        input: impl Iterator<Item = Token<T>>
+    ) -> ParseResult<T> {
+        // A valid stream of n tokens never has more than (n + 1) / 2
+        // operands on the stack at once. The hint is only a guess, and
+        // may be huge for an unbounded iterator, so cap what we reserve
+        // up front; the stack grows past it if it has to.
+        let depth = input.size_hint().0.div_ceil(2).min(PARSE_RESERVE_LIMIT);
+        let mut stack: SmallStack<Box<Self>, PARSE_STACK_INLINE> =
+            SmallStack::with_capacity(depth);
 
         for token in input { match token {
-            // XXX: suspicious use of clone.
-            Token::Val(v) => stack.push(Self::val(v.clone())),
-            Token::Id(s)  => stack.push(Expr::var(s.clone())),
+            Token::Val(v) => stack.push(Self::val(v)),
+            Token::Id(s)  => stack.push(Expr::var(s)),
             Token::Lambda => {
                 let body = stack.pop().ok_or(ParseError::Underflow)?;
                 let arg = stack.pop().ok_or(ParseError::Underflow)?;
@@ -168,8 +428,8 @@ impl<'a, T: 'a> Expr<T> where T: Types + Clone {
                 }
             },
             Token::Apply  => {
-                let arg = stack.pop().unwrap();
-                let func = stack.pop().unwrap();
+                let arg = stack.pop().ok_or(ParseError::Underflow)?;
+                let func = stack.pop().ok_or(ParseError::Underflow)?;
                 stack.push(Expr::apply(func, arg));
             }
         } }
@@ -182,6 +442,391 @@ impl<'a, T: 'a> Expr<T> where T: Types + Clone {
             Err(ParseError::EOF)
         }
     }
+
+    /**
+     * Render this expression as a Graphviz DOT digraph.
+     *
+     * An `Expr` is a tree, so equal subterms are drawn once per
+     * occurrence. Compare `grs::to_dot`, which draws shared nodes of a
+     * datagraph exactly once.
+     */
+    pub fn to_dot(&self) -> String {
+        let mut dot = Dot::new();
+        self.to_dot_rec(&mut dot, &mut 0);
+        dot.finish()
+    }
+
+    // Emit `self` and its children, returning the name of the node
+    // for `self`.
+    fn to_dot_rec(&self, dot: &mut Dot, next: &mut usize) -> String {
+        let name = format!("n{}", next);
+        *next += 1;
+        match self {
+            Self::Lambda(a, b) => {
+                dot.node(&name, &format!("λ{:?}", a));
+                let b = b.to_dot_rec(dot, next);
+                dot.edge(&name, &b, None);
+            },
+            Self::Val(v) => dot.node(&name, &format!("{:?}", v)),
+            Self::Var(v) => dot.node(&name, &format!("{:?}", v)),
+            Self::App(f, x) => {
+                dot.node(&name, "@");
+                let f = f.to_dot_rec(dot, next);
+                let x = x.to_dot_rec(dot, next);
+                dot.edge(&name, &f, None);
+                dot.edge(&name, &x, None);
+            }
+        }
+        name
+    }
+
+    /**
+     * A tree dump, one node per line, for diffing in tests and bug
+     * reports.
+     *
+     * Each line is the node, indented two spaces per level, then its
+     * path. Applications print as `@`, and symbols and values with
+     * `Debug`, after `λ`, `var`, or `val` so the two can't be mixed
+     * up. Unlike the derived `Debug`, the format is stable:
+     *
+     * ```text
+     * @ []
+     *   λ "x" [Func]
+     *     var "x" [Func, Body]
+     *   val 0 [Arg]
+     * ```
+     */
+    pub fn dump(&self) -> String {
+        let mut ret = String::new();
+        self.dump_rec(&mut Vec::new(), &mut ret);
+        ret
+    }
+
+    fn dump_rec(&self, here: &mut Path, out: &mut String) {
+        use core::fmt::Write;
+
+        let indent = "  ".repeat(here.len());
+        match self {
+            Self::Lambda(a, _) => writeln!(out, "{}λ {:?} {:?}", indent, a, here),
+            Self::Var(v)       => writeln!(out, "{}var {:?} {:?}", indent, v, here),
+            Self::Val(v)       => writeln!(out, "{}val {:?} {:?}", indent, v, here),
+            Self::App(_, _)    => writeln!(out, "{}@ {:?}", indent, here)
+        }.unwrap();
+
+        match self {
+            Self::Lambda(_, b) => {
+                here.push(Dir::Body);
+                b.dump_rec(here, out);
+                here.pop();
+            },
+            Self::App(f, x) => {
+                here.push(Dir::Func);
+                f.dump_rec(here, out);
+                here.pop();
+                here.push(Dir::Arg);
+                x.dump_rec(here, out);
+                here.pop();
+            },
+            _ => ()
+        }
+    }
+
+    /**
+     * Typeset this expression for LaTeX math mode, e.g.
+     * `\lambda x.\,x\;y`.
+     *
+     * Application is left-associative and a lambda body extends as
+     * far right as possible, so parentheses are only emitted where
+     * those conventions require them.
+     */
+    pub fn to_latex(&self) -> String
+    where T::Sym: Display, T::Val: Display {
+        self.to_latex_rec(Position::Top)
+    }
+
+    fn to_latex_rec(&self, pos: Position) -> String
+    where T::Sym: Display, T::Val: Display {
+        match (self, pos) {
+            (Self::Var(v), _) => latex::ident(&v.to_string()),
+            (Self::Val(v), _) => latex::ident(&v.to_string()),
+            (Self::Lambda(a, b), Position::Top) => format!(
+                "\\lambda {}.\\,{}",
+                latex::ident(&a.to_string()),
+                b.to_latex_rec(Position::Top)
+            ),
+            (Self::App(f, x), _) => {
+                let app = format!(
+                    "{}\\;{}",
+                    f.to_latex_rec(Position::Func),
+                    x.to_latex_rec(Position::Arg)
+                );
+                if pos == Position::Arg { format!("({})", app) } else { app }
+            },
+            (lambda, _) => format!("({})", lambda.to_latex_rec(Position::Top)),
+        }
+    }
+}
+
+
+impl<T> Expr<T> where T: Types + Clone, T::Sym: Fresh {
+
+    /* Reduce an expression tree
+     *
+     * This performs one reduction pass over the tree. The result
+     * itself might still be reducible (i.e., in the presence of
+     * recursion).
+     *
+     * Substitution stops at a binder which shadows the variable, and
+     * renames a binder which would capture a free variable of the
+     * argument. New names avoid every name in `self`.
+     */
+    pub fn reduce(self) -> ReduceResult<T> {
+        let mut supply = NameSupply::new();
+        supply.avoid_in(&self);
+        self.reduce_with(&mut supply)
+    }
+
+    /* Like `reduce()`, drawing new names from `supply`, which must
+     * already avoid every name in `self`. */
+    pub fn reduce_with(self, supply: &mut NameSupply<T::Sym>) -> ReduceResult<T> {
+        match self {
+            // We distinguish between beta and sigma reduction by
+            // inspecting the function term. A lambda implies beta
+            // reduction, while a value implies sigma reduction. A
+            // boolean applied to two arguments selects one of them.
+            Self::App(f, x) => match *f {
+                Self::Lambda(a, b) => Ok(Box::new(substitute_avoiding(&b, &a, &x, supply))),
+                Self::Val(v)       => Self::sigma_reduce(v, *x),
+                Self::App(b, t)    => match b.selects() {
+                    Some(true)  => Ok(t),
+                    Some(false) => Ok(x),
+                    None        => Err(ReduceError::NotApplicable)
+                },
+                _                  => Err(ReduceError::NotApplicable)
+            },
+            _ => Err(ReduceError::NotBetaReducible)
+        }
+    }
+
+    /* Compute what the redex at `path` reduces to, without modifying
+     * the tree. */
+    pub fn contractum_at(
+        &self,
+        path: &[Dir]
+    ) -> Result<(Reduction, Box<Self>), ReduceError<T>> {
+        let mut supply = NameSupply::new();
+        supply.avoid_in(self);
+        self.contractum_at_with(path, &mut supply)
+    }
+
+    /* Like `contractum_at()`, drawing new names from `supply`, which
+     * must already avoid every name in `self`. */
+    pub fn contractum_at_with(
+        &self,
+        path: &[Dir],
+        supply: &mut NameSupply<T::Sym>
+    ) -> Result<(Reduction, Box<Self>), ReduceError<T>> {
+        let node = self.at(path).ok_or(ReduceError::InvalidPath)?;
+        let kind = node.redex().ok_or(ReduceError::NotApplicable)?;
+        let contractum = node.clone().reduce_with(supply).map_err(|e| e.under(path))?;
+        Ok((kind, contractum))
+    }
+
+    /* Reduce the redex at `path` in place.
+     *
+     * Unlike `reduce()`, this works anywhere in the tree, and leaves
+     * the expression untouched if reduction fails.
+     */
+    pub fn reduce_at(&mut self, path: &[Dir]) -> Result<Reduction, ReduceError<T>> {
+        let (kind, contractum) = self.contractum_at(path)?;
+        *self.at_mut(path).expect("path checked above") = *contractum;
+        Ok(kind)
+    }
+
+    // Sigma reduction is delegated to the external value type, T::Val
+    fn sigma_reduce(func: T::Val, arg: Self) -> ReduceResult<T> {
+        match arg {
+            Self::Val(x) => T::Val::apply(func.clone(), x.clone())
+                .map_or_else(
+                    |error| Err(ReduceError::Delta {
+                        func,
+                        arg: x,
+                        path: Path::new(),
+                        error
+                    }),
+                    |v| Ok(Self::val(v))
+                ),
+            // A value applied to a variable or a lambda is stuck, not
+            // a redex; see `redex()`.
+            _ => Err(ReduceError::NotApplicable)
+        }
+    }
+
+}
+
+
+impl<T> Expr<T> where T: Types, T::Val: PartialEq {
+    /**
+     * Equality up to renaming of bound variables.
+     *
+     * `λx.x` and `λy.y` are alpha-equivalent; `λx.y` and `λy.y` are
+     * not. Free variables must match by name.
+     */
+    pub fn alpha_eq(&self, other: &Self) -> bool {
+        self.alpha_eq_rec(other, &mut Vec::new())
+    }
+
+    // `scope` pairs up the binders passed on the way down, innermost
+    // last.
+    fn alpha_eq_rec(&self, other: &Self, scope: &mut Vec<(T::Sym, T::Sym)>) -> bool {
+        match (self, other) {
+            (Self::Var(x), Self::Var(y)) => {
+                match scope.iter().rev().find(|(l, r)| l == x || r == y) {
+                    Some((l, r)) => l == x && r == y,
+                    None         => x == y
+                }
+            },
+            (Self::Val(x), Self::Val(y)) => x == y,
+            (Self::Lambda(x, b1), Self::Lambda(y, b2)) => {
+                scope.push((x.clone(), y.clone()));
+                let ret = b1.alpha_eq_rec(b2, scope);
+                scope.pop();
+                ret
+            },
+            (Self::App(f1, x1), Self::App(f2, x2)) =>
+                f1.alpha_eq_rec(f2, scope) && x1.alpha_eq_rec(x2, scope),
+            _ => false
+        }
+    }
+}
+
+impl<T> Expr<T> where T: Types + Clone, T::Val: PartialEq {
+    /**
+     * Measure the term before evaluating it.
+     *
+     * Counting distinct subterms compares subterms of equal size
+     * pairwise, so this is quadratic in the worst case.
+     */
+    pub fn stats(&self) -> Stats<T::Sym> {
+        let mut subterms = Vec::new();
+        let depth = self.collect_subterms(&mut subterms);
+
+        let (mut beta_redexes, mut sigma_redexes) = (0, 0);
+        for path in self.redexes() {
+            match self.at(&path).and_then(Self::redex) {
+                Some(Reduction::Beta)  => beta_redexes += 1,
+                Some(Reduction::Sigma) | Some(Reduction::Select) => sigma_redexes += 1,
+                None                   => ()
+            }
+        }
+
+        // Only subterms of the same size can be alpha-equivalent.
+        subterms.sort_by_key(|(size, _)| *size);
+        let mut distinct_subterms = 0;
+        for (i, (size, term)) in subterms.iter().enumerate() {
+            let duplicate = subterms[..i].iter().rev()
+                .take_while(|(s, _)| s == size)
+                .any(|(_, t)| t.alpha_eq(term));
+            if !duplicate {
+                distinct_subterms += 1;
+            }
+        }
+
+        Stats {
+            size: subterms.len(),
+            depth,
+            binders: subterms.iter().filter(|(_, t)| matches!(t, Self::Lambda(..))).count(),
+            free_vars: free_vars(self),
+            beta_redexes,
+            sigma_redexes,
+            distinct_subterms
+        }
+    }
+
+    // Push every subterm with its size, returning the depth.
+    fn collect_subterms<'a>(&'a self, out: &mut Vec<(usize, &'a Self)>) -> usize {
+        let index = out.len();
+        out.push((0, self));
+        let depth = match self {
+            Self::Lambda(_, b) => 1 + b.collect_subterms(out),
+            Self::App(f, x)    => 1 + f.collect_subterms(out).max(x.collect_subterms(out)),
+            _                  => 1
+        };
+        out[index].0 = out.len() - index;
+        depth
+    }
+}
+
+impl<S: Display> Display for Stats<S> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        writeln!(f, "size:           {}", self.size)?;
+        writeln!(f, "depth:          {}", self.depth)?;
+        writeln!(f, "binders:        {}", self.binders)?;
+        write!(f, "free variables: {}", self.free_vars.len())?;
+        for (i, v) in self.free_vars.iter().enumerate() {
+            write!(f, "{}{}", if i == 0 {" ("} else {", "}, v)?;
+        }
+        if !self.free_vars.is_empty() {
+            write!(f, ")")?;
+        }
+        writeln!(f)?;
+        writeln!(f, "redexes:        {} beta, {} sigma", self.beta_redexes, self.sigma_redexes)?;
+        writeln!(
+            f,
+            "distinct:       {} subterms (sharing factor {:.2})",
+            self.distinct_subterms,
+            self.sharing_factor()
+        )
+    }
+}
+
+
+/* Where a subterm appears, for deciding where parentheses go. */
+#[derive(Clone, Copy, PartialEq)]
+enum Position {
+    Top,
+    Func,
+    Arg
+}
+
+
+/**
+ * Plain text rendering, e.g. `λx.f (g x)`.
+ *
+ * The conventions are the same as for `to_latex()`: application is
+ * left-associative, and a lambda body extends as far right as
+ * possible.
+ */
+impl<T> Display for Expr<T> where T: Types, T::Sym: Display, T::Val: Display {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        self.fmt_rec(f, Position::Top)
+    }
+}
+
+impl<T> Expr<T> where T: Types, T::Sym: Display, T::Val: Display {
+    fn fmt_rec(&self, f: &mut core::fmt::Formatter<'_>, pos: Position) -> core::fmt::Result {
+        match (self, pos) {
+            (Self::Var(v), _) => write!(f, "{}", v),
+            (Self::Val(v), _) => write!(f, "{}", v),
+            (Self::Lambda(a, b), Position::Top) => {
+                write!(f, "λ{}.", a)?;
+                b.fmt_rec(f, Position::Top)
+            },
+            (Self::App(g, x), _) => {
+                if pos == Position::Arg { write!(f, "(")?; }
+                g.fmt_rec(f, Position::Func)?;
+                write!(f, " ")?;
+                x.fmt_rec(f, Position::Arg)?;
+                if pos == Position::Arg { write!(f, ")")?; }
+                Ok(())
+            },
+            (lambda, _) => {
+                write!(f, "(")?;
+                lambda.fmt_rec(f, Position::Top)?;
+                write!(f, ")")
+            }
+        }
+    }
 }
 
 
@@ -253,6 +898,76 @@ mod tests {
         assert_eq!(got, expected);
     }
 
+    #[test]
+    fn test_validate() {
+        let ok = vec![
+            Tok::id("x"),
+            Tok::id("y"),
+            Tok::Lambda,
+            Tok::val(1),
+            Tok::Apply,
+        ];
+        assert!(Tok::validate(&ok).is_ok());
+        assert!(Expr::parse(ok.iter()).is_ok());
+
+        type Check = fn(&ParseError<MyTypes>) -> bool;
+        let bad: Vec<(Vec<Tok>, Check)> = vec![
+            (vec![Tok::id("x"), Tok::Apply],
+             |e| matches!(e, ParseError::Underflow)),
+            (vec![Tok::Lambda],
+             |e| matches!(e, ParseError::Underflow)),
+            (vec![Tok::val(1), Tok::id("y"), Tok::Lambda],
+             |e| matches!(e, ParseError::NotAVar)),
+            (vec![Tok::id("x"), Tok::id("y")],
+             |e| matches!(e, ParseError::EOF)),
+            (vec![],
+             |e| matches!(e, ParseError::EOF)),
+        ];
+
+        // validate() and parse() reject the same streams the same way.
+        for (tokens, expected) in bad {
+            assert!(expected(&Tok::validate(&tokens).unwrap_err()));
+            assert!(expected(&Expr::parse(tokens.iter()).unwrap_err()));
+        }
+
+        // An endless stream claims an enormous length, but underflows
+        // on its first token.
+        let endless = core::iter::repeat(Tok::Apply);
+        assert!(matches!(Exp::parse_owned(endless), Err(ParseError::Underflow)));
+    }
+
+    /* Symbols can borrow from the text they were read from. */
+    #[derive(Clone, Debug, PartialEq)]
+    struct BorrowedTypes<'s>(core::marker::PhantomData<&'s str>);
+
+    impl<'s> Types for BorrowedTypes<'s> {
+        type Val = i32;
+        type Sym = &'s str;
+    }
+
+    #[test]
+    fn test_parse_borrowed() {
+        let buffer = String::from("x y Lambda z Apply");
+        let tokens = buffer.split_whitespace().map(|word| match word {
+            "Lambda" => Token::Lambda,
+            "Apply"  => Token::Apply,
+            s        => Token::Id(s)
+        });
+
+        let got = Expr::<BorrowedTypes>::parse_owned(tokens).unwrap();
+        assert_eq!(got, Expr::apply(Expr::lambda("x", Expr::var("y")), Expr::var("z")));
+
+        // The identifiers in the tree point into `buffer`.
+        let z = match &*got {
+            Expr::App(_, z) => match &**z {
+                Expr::Var(z) => *z,
+                _ => panic!("not a var")
+            },
+            _ => panic!("not an application")
+        };
+        assert!(buffer.as_bytes().as_ptr_range().contains(&z.as_ptr()));
+    }
+
     #[test]
     fn test_beta_reduction() {
         type E = Exp;
@@ -286,6 +1001,277 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_to_dot() {
+        type E = Exp;
+
+        assert_eq!(
+            E::apply(E::lambda("x", E::var("x")), E::val(0)).to_dot(),
+            concat!(
+                "digraph {\n",
+                "  ordering=out;\n",
+                "  \"n0\" [label=\"@\"];\n",
+                "  \"n1\" [label=\"λ\\\"x\\\"\"];\n",
+                "  \"n2\" [label=\"\\\"x\\\"\"];\n",
+                "  \"n1\" -> \"n2\";\n",
+                "  \"n3\" [label=\"0\"];\n",
+                "  \"n0\" -> \"n1\";\n",
+                "  \"n0\" -> \"n3\";\n",
+                "}\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_dump() {
+        type E = Exp;
+
+        assert_eq!(
+            E::apply(E::lambda("x", E::var("x")), E::val(0)).dump(),
+            concat!(
+                "@ []\n",
+                "  λ \"x\" [Func]\n",
+                "    var \"x\" [Func, Body]\n",
+                "  val 0 [Arg]\n"
+            )
+        );
+
+        // \f.f (f 1): one line per node, indented by depth.
+        let term = E::lambda("f", E::apply(E::var("f"), E::apply(E::var("f"), E::val(1))));
+        let dump = term.dump();
+        assert_eq!(dump.lines().count(), term.size());
+        assert!(dump.ends_with("      val 1 [Body, Arg, Arg]\n"));
+        assert_eq!(term.at(&[Dir::Body, Dir::Arg, Dir::Arg]), Some(&*E::val(1)));
+    }
+
+    #[test]
+    fn test_printer_snapshots() {
+        type E = Exp;
+
+        // (\f.\x.f (f x)) (\y.y) 0
+        let twice = E::lambda("f", E::lambda("x", E::apply(
+            E::var("f"),
+            E::apply(E::var("f"), E::var("x"))
+        )));
+        let term = E::apply(E::apply(twice, E::lambda("y", E::var("y"))), E::val(0));
+
+        let snaps = crate::snapshot::Snapshots::in_package();
+        snaps.assert("expr_dot", &term.to_dot());
+        snaps.assert("expr_dump", &term.dump());
+    }
+
+    #[test]
+    fn test_to_latex() {
+        type E = Exp;
+
+        assert_eq!(
+            E::apply(E::var("x"), E::var("y")).to_latex(),
+            "x\\;y"
+        );
+
+        assert_eq!(
+            E::lambda("x", E::apply(E::var("x"), E::var("y"))).to_latex(),
+            "\\lambda x.\\,x\\;y"
+        );
+
+        // (\x.x) (f (g 0))
+        assert_eq!(
+            E::apply(
+                E::lambda("x", E::var("x")),
+                E::apply(E::var("f"), E::apply(E::var("g"), E::val(0)))
+            ).to_latex(),
+            "(\\lambda x.\\,x)\\;(f\\;(g\\;0))"
+        );
+
+        // f a b (\y.fib)
+        assert_eq!(
+            E::apply(
+                E::apply(E::apply(E::var("f"), E::var("a")), E::var("b")),
+                E::lambda("y", E::var("fib"))
+            ).to_latex(),
+            "f\\;a\\;b\\;(\\lambda y.\\,\\mathit{fib})"
+        );
+    }
+
+    #[test]
+    fn test_display() {
+        type E = Exp;
+
+        assert_eq!(
+            E::lambda("x", E::apply(E::var("f"), E::apply(E::var("g"), E::var("x"))))
+                .to_string(),
+            "λx.f (g x)"
+        );
+
+        assert_eq!(
+            E::apply(
+                E::apply(E::lambda("x", E::var("x")), E::val(0)),
+                E::lambda("y", E::var("y"))
+            ).to_string(),
+            "(λx.x) 0 (λy.y)"
+        );
+    }
+
+    #[test]
+    fn test_delta_error() {
+        type E = Exp;
+
+        // \x.f (1 2): the primitive failure is reported at its path.
+        let mut term = E::lambda("x", E::apply(
+            E::var("f"),
+            E::apply(E::val(1), E::val(2))
+        ));
+        let path = [Dir::Body, Dir::Arg];
+        match term.reduce_at(&path) {
+            Err(ReduceError::Delta {func: 1, arg: 2, path: at, error: ()}) =>
+                assert_eq!(at, path),
+            other => panic!("expected a delta error, got {:?}", other)
+        }
+        assert_eq!(term.at(&path), Some(&*E::apply(E::val(1), E::val(2))));
+    }
+
+    #[test]
+    fn test_open_terms() {
+        type E = Exp;
+
+        // 1 x is stuck, not a primitive failure.
+        assert!(matches!(
+            E::apply(E::val(1), E::var("x")).reduce(),
+            Err(ReduceError::NotApplicable)
+        ));
+        assert_eq!(E::apply(E::val(1), E::var("x")).redex(), None);
+
+        // (\x.\y.x) y renames the binder rather than capture y.
+        let k = || E::lambda("x", E::lambda("y", E::var("x")));
+        assert_eq!(E::apply(k(), E::var("y")).reduce().unwrap(), E::lambda("y_1", E::var("y")));
+        assert_eq!(E::apply(k(), E::var("z")).reduce().unwrap(), E::lambda("y", E::var("z")));
+
+        // (\x.\y.y) y is fine: x doesn't occur under the binder.
+        let ki = E::lambda("x", E::lambda("y", E::var("y")));
+        assert_eq!(E::apply(ki, E::var("y")).reduce().unwrap(), E::lambda("y", E::var("y")));
+
+        // \y.(\x.\y.x) y, under a binder.
+        let mut term = E::lambda("y", E::apply(k(), E::var("y")));
+        term.reduce_at(&[Dir::Body]).unwrap();
+        assert_eq!(term, E::lambda("y", E::lambda("y_1", E::var("y"))));
+    }
+
+    #[test]
+    fn test_replace_free() {
+        type E = Exp;
+
+        // x (\x.x) y, with x := 1 and y left alone.
+        let term = E::apply(
+            E::apply(E::var("x"), E::lambda("x", E::var("x"))),
+            E::var("y")
+        );
+        let replaced = term.replace_free(|v| match v.as_str() {
+            "x" => Some(Expr::Val(1)),
+            _   => None
+        });
+        assert_eq!(replaced, *E::apply(
+            E::apply(E::val(1), E::lambda("x", E::var("x"))),
+            E::var("y")
+        ));
+    }
+
+    #[test]
+    fn test_shadowing() {
+        type E = Exp;
+
+        // (\x.\x.x) z: the inner binder ends the substitution.
+        let term = E::apply(E::lambda("x", E::lambda("x", E::var("x"))), E::var("z"));
+        assert_eq!(term.reduce().unwrap(), E::lambda("x", E::var("x")));
+
+        // (\x.x (\x.x)) z
+        let term = E::apply(
+            E::lambda("x", E::apply(E::var("x"), E::lambda("x", E::var("x")))),
+            E::var("z")
+        );
+        assert_eq!(
+            term.reduce().unwrap(),
+            E::apply(E::var("z"), E::lambda("x", E::var("x")))
+        );
+    }
+
+    #[test]
+    fn test_church_two_two() {
+        type E = Exp;
+
+        let church = |n| E::lambda("f", E::lambda("x", (0..n).fold(
+            E::var("x"),
+            |body, _| E::apply(E::var("f"), body)
+        )));
+
+        // 2 2 duplicates a lambda into the scope of its own binders.
+        let mut term = E::apply(church(2), church(2));
+        let mut steps = 0;
+        while let Some(path) = term.redexes().first() {
+            term.reduce_at(path).unwrap();
+            steps += 1;
+            assert!(steps < 100);
+        }
+        assert!(term.alpha_eq(&church(4)));
+    }
+
+    #[test]
+    fn test_alpha_eq() {
+        type E = Exp;
+
+        let id = |x| E::lambda(x, E::var(x));
+        assert!(id("x").alpha_eq(&id("y")));
+        assert!(!E::lambda("x", E::var("y")).alpha_eq(&id("y")));
+        assert!(!E::var("x").alpha_eq(&E::var("y")));
+
+        // \x.\y.x vs \y.\x.y, and vs \y.\x.x
+        let k = |x, y| E::lambda(x, E::lambda(y, E::var(x)));
+        assert!(k("x", "y").alpha_eq(&k("y", "x")));
+        assert!(!k("x", "y").alpha_eq(&E::lambda("y", E::lambda("x", E::var("x")))));
+
+        assert!(E::apply(id("a"), E::val(1)).alpha_eq(&E::apply(id("b"), E::val(1))));
+        assert!(!E::apply(id("a"), E::val(1)).alpha_eq(&E::apply(id("b"), E::val(2))));
+    }
+
+    #[test]
+    fn test_stats() {
+        type E = Exp;
+
+        // (\x.x x) ((\y.y) z) (\y.y)
+        let id = || E::lambda("y", E::var("y"));
+        let term = E::apply(
+            E::apply(
+                E::lambda("x", E::apply(E::var("x"), E::var("x"))),
+                E::apply(id(), E::var("z"))
+            ),
+            id()
+        );
+        let stats = term.stats();
+
+        assert_eq!(stats, Stats {
+            size: 12,
+            depth: 5,
+            binders: 3,
+            free_vars: vec!["z".to_string()],
+            beta_redexes: 2,
+            sigma_redexes: 0,
+            // x, y, z, x x, \x.x x, \y.y, (\y.y) z, and the two
+            // applications on the spine
+            distinct_subterms: 9
+        });
+        assert_eq!(
+            stats.to_string(),
+            concat!(
+                "size:           12\n",
+                "depth:          5\n",
+                "binders:        3\n",
+                "free variables: 1 (z)\n",
+                "redexes:        2 beta, 0 sigma\n",
+                "distinct:       9 subterms (sharing factor 1.33)\n"
+            )
+        );
+        assert_eq!(E::val(0).stats().sharing_factor(), 1.0);
+    }
+
     /**
      * This section demonstrates extending the pure lambda calc with sigma rules.
      */
@@ -25,6 +25,7 @@
 // Fork this project to create your own MIT license that you can
 // always link to.
 use core::fmt::Debug;
+use crate::dot::Dot;
 
 /**
  * This module provides the core traits for FGRS, and algorithms
@@ -343,6 +344,37 @@ pub fn reduce<T, D, P, S, M>(
 }
 
 
+
+/**
+ * Render the part of a datagraph reachable from its root as a
+ * Graphviz DOT digraph.
+ *
+ * Each node is drawn once, however many nodes refer to it, so the
+ * picture shows exactly the sharing that graph rewriting exploits.
+ * Edges are labeled with argument positions. Cycles are fine.
+ */
+pub fn to_dot<T, D>(data: &D) -> String where T: Types, D: DataGraph<T> {
+    let mut dot = Dot::new();
+    let mut seen: Vec<T::Id> = Vec::new();
+    let mut todo = vec![data.root()];
+
+    while let Some(id) = todo.pop() {
+        if seen.contains(&id) {
+            continue;
+        }
+        seen.push(id);
+
+        let name = format!("{:?}", id);
+        dot.node(&name, &format!("{:?}", data.value(id)));
+        for (i, arg) in data.args(id).enumerate() {
+            dot.edge(&name, &format!("{:?}", arg), Some(&format!("{}", i)));
+            todo.push(arg);
+        }
+    }
+
+    dot.finish()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -408,6 +440,8 @@ mod tests {
         fn root(&'a self) -> u8 { 0 }
     }
 
+    impl DataGraph<TestTypes> for Vec<(Value, Vec<u8>)> {}
+
     impl Mapping<TestTypes> for HashMap<Symbol, u8> {
         fn new() -> Self { HashMap::new() }
         fn get(&self, var: Symbol) -> u8 { self[&var] }
@@ -439,4 +473,26 @@ mod tests {
     #[test]
     fn test_grs() {
     }
+
+    #[test]
+    fn test_to_dot_draws_shared_nodes_once() {
+        // Add(x, x), where both arguments are the same node.
+        let data: Vec<(Value, Vec<u8>)> = vec![
+            (Value::Add, vec![1, 1]),
+            (Value::Int(1), vec![]),
+        ];
+
+        assert_eq!(
+            to_dot::<TestTypes, _>(&data),
+            concat!(
+                "digraph {\n",
+                "  ordering=out;\n",
+                "  \"0\" [label=\"Add\"];\n",
+                "  \"0\" -> \"1\" [label=\"0\"];\n",
+                "  \"0\" -> \"1\" [label=\"1\"];\n",
+                "  \"1\" [label=\"Int(1)\"];\n",
+                "}\n"
+            )
+        );
+    }
 }
@@ -0,0 +1,284 @@
+// The MIT License (MIT)
+//
+// Copyright © 2022 <Brandon Lewis>
+//
+// Permission is hereby granted, free of charge, to any person
+// obtaining a copy of this software and associated documentation
+// files (the “Software”), to deal in the Software without
+// restriction, including without limitation the rights to use, copy,
+// modify, merge, publish, distribute, sublicense, and/or sell copies
+// of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS
+// BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN
+// ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+// Fork this project to create your own MIT license that you can
+// always link to.
+use core::fmt;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use crate::trs::{Types, SigmaRules};
+use crate::expr::Expr;
+
+
+/*
+ * Host-closure primitives.
+ *
+ * An embedder registers Rust closures under a name and an arity, and
+ * `Registry::resolve` turns free variables with those names into
+ * `Host` values. Applying one collects arguments until it has
+ * `arity` of them, then calls the closure. No `SigmaRules` impl on
+ * an application-specific enum is needed; `V` is any plain data type.
+ *
+ * Primitives are strict: a sigma redex only fires once its argument
+ * is a value, so a closure never sees an unevaluated term.
+ *
+ * Closures are `Send + Sync` behind an `Arc`, so a term holding host
+ * values can still be handed to another thread.
+ */
+
+
+type HostFn<V, E> = dyn Fn(&[V]) -> Result<V, E> + Send + Sync;
+
+
+/**
+ * A named closure, shared by every value that refers to it.
+ */
+pub struct Primitive<V, E> {
+    pub name: String,
+    pub arity: usize,
+    func: Box<HostFn<V, E>>
+}
+
+
+/**
+ * A host value: plain data, or a primitive waiting for arguments.
+ */
+pub enum Host<V, E> {
+    Val(V),
+    Partial(Arc<Primitive<V, E>>, Vec<V>)
+}
+
+impl<V: Clone, E> Clone for Host<V, E> {
+    fn clone(&self) -> Self {
+        match self {
+            Host::Val(v)           => Host::Val(v.clone()),
+            Host::Partial(p, args) => Host::Partial(p.clone(), args.clone())
+        }
+    }
+}
+
+// Two partial applications are equal when they are of the same
+// registered primitive, not merely one with the same name.
+impl<V: PartialEq, E> PartialEq for Host<V, E> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Host::Val(a), Host::Val(b)) => a == b,
+            (Host::Partial(p, a), Host::Partial(q, b)) =>
+                Arc::ptr_eq(p, q) && a == b,
+            _ => false
+        }
+    }
+}
+
+impl<V: fmt::Debug, E> fmt::Debug for Host<V, E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Host::Val(v) => f.debug_tuple("Val").field(v).finish(),
+            Host::Partial(p, args) => f.debug_tuple("Partial")
+                .field(&p.name)
+                .field(args)
+                .finish()
+        }
+    }
+}
+
+impl<V: fmt::Display, E> fmt::Display for Host<V, E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Host::Val(v) => write!(f, "{}", v),
+            Host::Partial(p, args) if args.is_empty() => write!(f, "{}", p.name),
+            Host::Partial(p, args) => {
+                write!(f, "({}", p.name)?;
+                for arg in args {
+                    write!(f, " {}", arg)?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+impl<V, E> From<V> for Host<V, E> {
+    fn from(v: V) -> Self {
+        Host::Val(v)
+    }
+}
+
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HostError<E> {
+    // Plain data applied to something.
+    #[default]
+    NotApplicable,
+    // A primitive applied to another primitive.
+    NotData,
+    // The closure itself failed.
+    Host(E)
+}
+
+
+impl<V, E> SigmaRules for Host<V, E>
+where V: Clone + fmt::Debug, E: fmt::Debug {
+    type Error = HostError<E>;
+
+    fn apply(f: Self, x: Self) -> Result<Self, Self::Error> {
+        let (prim, mut args) = match f {
+            Host::Partial(p, args) => (p, args),
+            Host::Val(_)           => return Err(HostError::NotApplicable)
+        };
+        match x {
+            Host::Val(v)        => args.push(v),
+            Host::Partial(_, _) => return Err(HostError::NotData)
+        }
+        if args.len() < prim.arity {
+            Ok(Host::Partial(prim, args))
+        } else {
+            (prim.func)(&args).map(Host::Val).map_err(HostError::Host)
+        }
+    }
+}
+
+
+/**
+ * The primitives an embedder exposes, by name.
+ */
+pub struct Registry<V, E> {
+    prims: BTreeMap<String, Arc<Primitive<V, E>>>
+}
+
+impl<V, E> Registry<V, E> {
+    pub fn new() -> Self {
+        Registry {prims: BTreeMap::new()}
+    }
+
+    /**
+     * Register `func` as `name`, taking `arity` arguments. The slice
+     * it is called with always has exactly that length. Registering a
+     * name again replaces the earlier primitive for later lookups.
+     *
+     * Panics if `arity` is zero: a nullary primitive is just a value.
+     */
+    pub fn register<F>(&mut self, name: &str, arity: usize, func: F) -> &mut Self
+    where F: Fn(&[V]) -> Result<V, E> + Send + Sync + 'static {
+        assert!(arity > 0, "primitive {} must take an argument", name);
+        let prim = Primitive {name: name.to_string(), arity, func: Box::new(func)};
+        self.prims.insert(name.to_string(), Arc::new(prim));
+        self
+    }
+
+    pub fn lookup(&self, name: &str) -> Option<Host<V, E>> {
+        self.prims.get(name).map(|p| Host::Partial(p.clone(), Vec::new()))
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.prims.keys().map(String::as_str)
+    }
+
+    /**
+     * Replace the free variables of `expr` which name primitives with
+     * the primitives themselves.
+     */
+    pub fn resolve<T>(&self, expr: &Expr<T>) -> Expr<T>
+    where T: Types<Val = Host<V, E>> + Clone, T::Sym: AsRef<str>, V: Clone {
+        expr.replace_free(|v| self.lookup(v.as_ref()).map(Expr::Val))
+    }
+}
+
+impl<V, E> Default for Registry<V, E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stepper::{Stepper, NormalOrder, Stop};
+
+    type Value = Host<String, String>;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct HostTypes;
+
+    impl Types for HostTypes {
+        type Val = Value;
+        type Sym = String;
+    }
+
+    type E = Expr<HostTypes>;
+
+    fn registry() -> Registry<String, String> {
+        let mut reg = Registry::new();
+        reg.register("upper", 1, |args: &[String]| Ok(args[0].to_uppercase()))
+            .register("concat", 2, |args: &[String]| Ok(args.concat()))
+            .register("fail", 1, |args: &[String]| Err(format!("no: {}", args[0])));
+        reg
+    }
+
+    fn s(text: &str) -> Value {
+        Host::Val(text.to_string())
+    }
+
+    #[test]
+    fn test_apply() {
+        let reg = registry();
+        let concat = reg.lookup("concat").unwrap();
+        let partial = Value::apply(concat, s("a")).unwrap();
+        assert_eq!(partial.to_string(), "(concat a)");
+        assert_eq!(Value::apply(partial, s("b")), Ok(s("ab")));
+
+        let fail = reg.lookup("fail").unwrap();
+        assert_eq!(Value::apply(fail, s("x")), Err(HostError::Host("no: x".to_string())));
+        assert_eq!(Value::apply(s("a"), s("b")), Err(HostError::NotApplicable));
+        let upper = reg.lookup("upper").unwrap();
+        assert_eq!(Value::apply(upper.clone(), upper), Err(HostError::NotData));
+        assert!(reg.lookup("missing").is_none());
+        assert_eq!(reg.names().collect::<Vec<_>>(), vec!["concat", "fail", "upper"]);
+    }
+
+    #[test]
+    fn test_resolve() {
+        let reg = registry();
+        // (\upper.concat (upper "a")) "b": the bound upper is left alone.
+        let term = E::apply(
+            E::lambda("upper", E::apply(
+                E::var("concat"),
+                E::apply(E::var("upper"), E::val(s("a")))
+            )),
+            E::val(s("b"))
+        );
+        let resolved = reg.resolve(&term);
+        assert_eq!(resolved.to_string(), "(λupper.concat (upper a)) b");
+
+        // concat (upper "a") "b"
+        let term = E::apply(
+            E::apply(E::var("concat"), E::apply(E::var("upper"), E::val(s("a")))),
+            E::val(s("b"))
+        );
+        let mut stepper = Stepper::new(Box::new(reg.resolve(&term)), NormalOrder);
+        assert_eq!(stepper.run(100).unwrap(), Stop::NormalForm);
+        assert_eq!(*stepper.term(), Expr::Val(s("Ab")));
+    }
+}
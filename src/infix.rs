@@ -0,0 +1,500 @@
+// The MIT License (MIT)
+//
+// Copyright © 2022 <Brandon Lewis>
+//
+// Permission is hereby granted, free of charge, to any person
+// obtaining a copy of this software and associated documentation
+// files (the “Software”), to deal in the Software without
+// restriction, including without limitation the rights to use, copy,
+// modify, merge, publish, distribute, sublicense, and/or sell copies
+// of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS
+// BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN
+// ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+// Fork this project to create your own MIT license that you can
+// always link to.
+use crate::trs::Types;
+use crate::expr::Token;
+
+
+/*
+ * Infix operator syntax.
+ *
+ * `convert` turns a surface expression like `f x + 2 * y` into a
+ * postfix `Token` stream, using the shunting-yard algorithm with an
+ * `OperatorTable`. A binary operator `a + b` becomes the curried
+ * application `(+ a) b` of the operator's symbol, so operators are
+ * just primitive symbols as far as reduction is concerned. Adjacent
+ * operands are applied to each other, binding tighter than any
+ * operator.
+ *
+ * Operators can be added to a table by an embedder with `declare`, or
+ * by the program itself with `Infix::Declare` tokens, which take
+ * effect for the rest of the stream. Either way, a declaration that
+ * conflicts with an existing one is rejected with a `Diagnostic`.
+ */
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Assoc {
+    Left,
+    Right,
+    // `a == b == c` is an error.
+    None
+}
+
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Operator<T: Types> {
+    pub symbol: T::Sym,
+    // Higher binds tighter.
+    pub precedence: u8,
+    pub assoc: Assoc
+}
+
+
+/**
+ * The binary operators `convert` recognizes.
+ */
+#[derive(Clone, Debug)]
+pub struct OperatorTable<T: Types> {
+    ops: Vec<Operator<T>>
+}
+
+impl<T: Types> OperatorTable<T> {
+    pub fn new() -> Self {
+        OperatorTable {ops: Vec::new()}
+    }
+
+    /* Add an operator, replacing any previous one with this symbol. */
+    pub fn with(mut self, symbol: T::Sym, precedence: u8, assoc: Assoc) -> Self {
+        self.ops.retain(|op| op.symbol != symbol);
+        self.ops.push(Operator {symbol, precedence, assoc});
+        self
+    }
+
+    pub fn lookup(&self, symbol: &T::Sym) -> Option<&Operator<T>> {
+        self.ops.iter().find(|op| op.symbol == *symbol)
+    }
+
+    /**
+     * Add an operator, unless it conflicts with one already declared.
+     *
+     * Unlike `with`, this never replaces an operator. Declaring the
+     * same operator twice is allowed.
+     */
+    pub fn declare(&mut self, op: Operator<T>) -> Result<(), Diagnostic<T>> {
+        check_declaration(self.ops.iter(), &op)?;
+        if self.lookup(&op.symbol).is_none() {
+            self.ops.push(op);
+        }
+        Ok(())
+    }
+
+    /**
+     * `+` and `-` at 6, `*` and `/` at 7, all left-associative, and
+     * right-associative `^` at 8.
+     */
+    pub fn arithmetic() -> Self where T::Sym: From<&'static str> {
+        Self::new()
+            .with("+".into(), 6, Assoc::Left)
+            .with("-".into(), 6, Assoc::Left)
+            .with("*".into(), 7, Assoc::Left)
+            .with("/".into(), 7, Assoc::Left)
+            .with("^".into(), 8, Assoc::Right)
+    }
+}
+
+impl<T: Types> Default for OperatorTable<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+/**
+ * Tokens of the infix surface syntax.
+ *
+ * An `Id` whose symbol is in the operator table is an operator;
+ * any other `Id` is a variable.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub enum Infix<T: Types> {
+    Val(T::Val),
+    Id(T::Sym),
+    Open,
+    Close,
+    // Declare an operator for the rest of the stream.
+    Declare(Operator<T>)
+}
+
+
+#[derive(Debug)]
+pub enum InfixError<T: Types> {
+    // An operator, `)`, or the end came where an operand was expected.
+    MissingOperand,
+    Unbalanced,
+    // Two operators of equal precedence which can't be chained, like
+    // `a == b == c`, or a left- and a right-associative operator.
+    Conflict(T::Sym, T::Sym),
+    Declaration(Diagnostic<T>)
+}
+
+
+/**
+ * Why an operator declaration was rejected.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub enum Diagnostic<T: Types> {
+    // The symbol is already an operator, with a different precedence
+    // or associativity.
+    Redeclared {
+        previous: Operator<T>,
+        new: Operator<T>
+    },
+    // Another operator has the same precedence but a different
+    // associativity, so the two could never be mixed without
+    // parentheses.
+    MixedAssoc {
+        existing: Operator<T>,
+        new: Operator<T>
+    }
+}
+
+fn check_declaration<'a, T: Types + 'a>(
+    existing: impl Iterator<Item = &'a Operator<T>>,
+    new: &Operator<T>
+) -> Result<(), Diagnostic<T>> {
+    for op in existing {
+        let same_level = op.precedence == new.precedence;
+        if op.symbol == new.symbol && (!same_level || op.assoc != new.assoc) {
+            return Err(Diagnostic::Redeclared {previous: copy(op), new: copy(new)});
+        }
+        if same_level && op.assoc != new.assoc {
+            return Err(Diagnostic::MixedAssoc {existing: copy(op), new: copy(new)});
+        }
+    }
+    Ok(())
+}
+
+// `Operator` is only `Clone` when `T` is.
+fn copy<T: Types>(op: &Operator<T>) -> Operator<T> {
+    Operator {symbol: op.symbol.clone(), precedence: op.precedence, assoc: op.assoc}
+}
+
+
+// An operator waiting on the stack.
+enum Pending<T: Types> {
+    Op(Operator<T>),
+    // Juxtaposition
+    Apply,
+    Open
+}
+
+impl<T: Types> Pending<T> {
+    // Juxtaposition binds tighter than any operator.
+    fn precedence(&self) -> u16 {
+        match self {
+            Pending::Op(op) => u16::from(op.precedence),
+            Pending::Apply  => u16::from(u8::MAX) + 1,
+            Pending::Open   => 0
+        }
+    }
+
+    fn assoc(&self) -> Assoc {
+        match self {
+            Pending::Op(op) => op.assoc,
+            _               => Assoc::Left
+        }
+    }
+}
+
+
+struct Converter<T: Types> {
+    out: Vec<Token<T>>,
+    // Where each operand in `out` starts.
+    starts: Vec<usize>,
+    pending: Vec<Pending<T>>
+}
+
+impl<T: Types> Converter<T> {
+    fn operand(&mut self, token: Token<T>) {
+        self.starts.push(self.out.len());
+        self.out.push(token);
+    }
+
+    // Apply `op` to the top two operands.
+    fn emit(&mut self, op: Pending<T>) {
+        let b = self.starts.pop().expect("operands checked by convert");
+        let a = self.starts.pop().expect("operands checked by convert");
+        if let Pending::Op(op) = op {
+            self.out.insert(a, Token::Id(op.symbol));
+            self.out.insert(b + 1, Token::Apply);
+        }
+        self.out.push(Token::Apply);
+        self.starts.push(a);
+    }
+
+    fn push(&mut self, op: Pending<T>) -> Result<(), InfixError<T>> {
+        while let Some(top) = self.pending.last() {
+            if let Pending::Open = top {
+                break;
+            }
+            if top.precedence() == op.precedence() {
+                match (top.assoc(), op.assoc()) {
+                    (Assoc::Left, Assoc::Left) => (),
+                    (Assoc::Right, Assoc::Right) => break,
+                    _ => return Err(conflict(top, &op))
+                }
+            } else if top.precedence() < op.precedence() {
+                break;
+            }
+            let top = self.pending.pop().expect("checked by last()");
+            self.emit(top);
+        }
+        self.pending.push(op);
+        Ok(())
+    }
+}
+
+fn conflict<T: Types>(a: &Pending<T>, b: &Pending<T>) -> InfixError<T> {
+    match (a, b) {
+        (Pending::Op(a), Pending::Op(b)) => InfixError::Conflict(a.symbol.clone(), b.symbol.clone()),
+        _ => unreachable!("only operators share a precedence")
+    }
+}
+
+
+/**
+ * Convert an infix token stream into a postfix one.
+ */
+pub fn convert<T: Types>(
+    input: impl IntoIterator<Item = Infix<T>>,
+    table: &OperatorTable<T>
+) -> Result<Vec<Token<T>>, InfixError<T>> {
+    let mut conv = Converter {out: Vec::new(), starts: Vec::new(), pending: Vec::new()};
+    let mut expect_operand = true;
+    // Operators declared by the stream itself.
+    let mut declared: Vec<Operator<T>> = Vec::new();
+
+    for token in input {
+        let operator = match &token {
+            Infix::Id(s) => declared.iter()
+                .find(|op| op.symbol == *s)
+                .or_else(|| table.lookup(s))
+                .map(copy),
+            _ => None
+        };
+
+        match (token, operator) {
+            (_, Some(op)) => {
+                if expect_operand {
+                    return Err(InfixError::MissingOperand);
+                }
+                conv.push(Pending::Op(op))?;
+                expect_operand = true;
+            },
+            (Infix::Declare(op), _) => {
+                check_declaration(declared.iter().chain(table.ops.iter()), &op)
+                    .map_err(InfixError::Declaration)?;
+                declared.push(op);
+            },
+            (Infix::Open, _) => {
+                if !expect_operand {
+                    conv.push(Pending::Apply)?;
+                }
+                conv.pending.push(Pending::Open);
+                expect_operand = true;
+            },
+            (Infix::Close, _) => {
+                if expect_operand {
+                    return Err(InfixError::MissingOperand);
+                }
+                loop {
+                    match conv.pending.pop() {
+                        Some(Pending::Open) => break,
+                        Some(op)            => conv.emit(op),
+                        None                => return Err(InfixError::Unbalanced)
+                    }
+                }
+            },
+            (operand, None) => {
+                if !expect_operand {
+                    conv.push(Pending::Apply)?;
+                }
+                conv.operand(match operand {
+                    Infix::Val(v) => Token::Val(v),
+                    Infix::Id(s)  => Token::Id(s),
+                    _             => unreachable!("handled above")
+                });
+                expect_operand = false;
+            }
+        }
+    }
+
+    if expect_operand {
+        return Err(InfixError::MissingOperand);
+    }
+    while let Some(op) = conv.pending.pop() {
+        match op {
+            Pending::Open => return Err(InfixError::Unbalanced),
+            op            => conv.emit(op)
+        }
+    }
+    Ok(conv.out)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expr::Expr;
+    use crate::trs::SigmaRules;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct InfixTypes;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Num(i32);
+
+    impl SigmaRules for Num {
+        type Error = ();
+    }
+
+    impl Types for InfixTypes {
+        type Val = Num;
+        type Sym = &'static str;
+    }
+
+    type E = Expr<InfixTypes>;
+
+    fn infix(s: &'static str) -> Vec<Infix<InfixTypes>> {
+        s.split_whitespace().map(|w| match w {
+            "(" => Infix::Open,
+            ")" => Infix::Close,
+            w   => match w.parse() {
+                Ok(n)  => Infix::Val(Num(n)),
+                Err(_) => Infix::Id(w)
+            }
+        }).collect()
+    }
+
+    fn parse(s: &'static str) -> Box<E> {
+        let table = OperatorTable::arithmetic();
+        E::parse_owned(convert(infix(s), &table).unwrap().into_iter()).unwrap()
+    }
+
+    // Apply a binary operator.
+    fn bin(op: &'static str, a: Box<E>, b: Box<E>) -> Box<E> {
+        E::apply(E::apply(E::var(op), a), b)
+    }
+
+    fn n(i: i32) -> Box<E> {
+        E::val(Num(i))
+    }
+
+    #[test]
+    fn test_precedence() {
+        assert_eq!(parse("1 + 2 * x"), bin("+", n(1), bin("*", n(2), E::var("x"))));
+        assert_eq!(parse("( 1 + 2 ) * x"), bin("*", bin("+", n(1), n(2)), E::var("x")));
+    }
+
+    #[test]
+    fn test_associativity() {
+        assert_eq!(parse("1 - 2 - 3"), bin("-", bin("-", n(1), n(2)), n(3)));
+        assert_eq!(parse("1 ^ 2 ^ 3"), bin("^", n(1), bin("^", n(2), n(3))));
+    }
+
+    #[test]
+    fn test_juxtaposition() {
+        // f x + g ( y ) * 2
+        assert_eq!(
+            parse("f x + g ( y ) * 2"),
+            bin(
+                "+",
+                E::apply(E::var("f"), E::var("x")),
+                bin("*", E::apply(E::var("g"), E::var("y")), n(2))
+            )
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let table = OperatorTable::arithmetic().with("==", 4, Assoc::None);
+        let table = table.with("++", 6, Assoc::Right);
+        let err = |s| convert(infix(s), &table).unwrap_err();
+
+        assert!(matches!(err("1 +"), InfixError::MissingOperand));
+        assert!(matches!(err("* 2"), InfixError::MissingOperand));
+        assert!(matches!(err("( )"), InfixError::MissingOperand));
+        assert!(matches!(err(""), InfixError::MissingOperand));
+        assert!(matches!(err("( 1"), InfixError::Unbalanced));
+        assert!(matches!(err("1 )"), InfixError::Unbalanced));
+        assert!(matches!(err("a == b == c"), InfixError::Conflict("==", "==")));
+        assert!(matches!(err("a + b ++ c"), InfixError::Conflict("+", "++")));
+        assert!(convert(infix("a == b + c"), &table).is_ok());
+    }
+
+    #[test]
+    fn test_declare() {
+        let mut table = OperatorTable::<InfixTypes>::arithmetic();
+        let op = |symbol, precedence, assoc| Operator {symbol, precedence, assoc};
+
+        assert_eq!(table.declare(op("<>", 5, Assoc::Right)), Ok(()));
+        assert_eq!(table.declare(op("<>", 5, Assoc::Right)), Ok(()));
+        assert_eq!(
+            table.declare(op("<>", 4, Assoc::Right)),
+            Err(Diagnostic::Redeclared {
+                previous: op("<>", 5, Assoc::Right),
+                new: op("<>", 4, Assoc::Right)
+            })
+        );
+        assert_eq!(
+            table.declare(op("++", 6, Assoc::Right)),
+            Err(Diagnostic::MixedAssoc {
+                existing: op("+", 6, Assoc::Left),
+                new: op("++", 6, Assoc::Right)
+            })
+        );
+
+        let got = convert(infix("a <> b <> c"), &table).unwrap();
+        let expected = bin("<>", E::var("a"), bin("<>", E::var("b"), E::var("c")));
+        assert_eq!(E::parse_owned(got.into_iter()).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_declare_in_stream() {
+        let table = OperatorTable::arithmetic();
+        let declare = |symbol, precedence, assoc| Infix::Declare(Operator {symbol, precedence, assoc});
+
+        // Before the declaration, `.` is an ordinary variable.
+        let mut input = infix("( f . g )");
+        input.push(declare(".", 9, Assoc::Right));
+        input.extend(infix("( f . g . h + 1 )"));
+        let got = convert(input, &table).unwrap();
+
+        let compose = bin(".", E::var("f"), bin(".", E::var("g"), E::var("h")));
+        let expected = E::apply(
+            E::apply(E::apply(E::var("f"), E::var(".")), E::var("g")),
+            bin("+", compose, n(1))
+        );
+        assert_eq!(E::parse_owned(got.into_iter()).unwrap(), expected);
+
+        let input = vec![declare("*", 2, Assoc::Left)];
+        assert!(matches!(
+            convert(input, &table),
+            Err(InfixError::Declaration(Diagnostic::Redeclared {..}))
+        ));
+    }
+}
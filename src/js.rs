@@ -0,0 +1,231 @@
+// The MIT License (MIT)
+//
+// Copyright © 2022 <Brandon Lewis>
+//
+// Permission is hereby granted, free of charge, to any person
+// obtaining a copy of this software and associated documentation
+// files (the “Software”), to deal in the Software without
+// restriction, including without limitation the rights to use, copy,
+// modify, merge, publish, distribute, sublicense, and/or sell copies
+// of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS
+// BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN
+// ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+// Fork this project to create your own MIT license that you can
+// always link to.
+use core::fmt::{Display, Write};
+use crate::trs::Types;
+use crate::expr::Expr;
+
+
+/*
+ * JavaScript code generation.
+ *
+ * A lambda becomes a one-argument arrow function and an application a
+ * call, so the output runs in any browser without shipping an
+ * interpreter. How values are written is up to the caller, as is
+ * providing them as curried JavaScript functions where they are
+ * primitives.
+ *
+ * Under `Lazy` evaluation every argument is passed as a memoizing
+ * thunk made by the `$lazy` helper, and every variable is forced where
+ * it is used, so free variables must be bound to thunks too, and
+ * primitives receive thunks and must force them.
+ *
+ * Identifiers which aren't valid in JavaScript are mangled, with each
+ * offending character written as `$` and its codepoint in hex.
+ */
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Evaluation {
+    // Call by value, as JavaScript itself.
+    Strict,
+    // Call by need.
+    Lazy
+}
+
+
+/* Defines `$lazy`, for the output of `Evaluation::Lazy`. */
+pub const LAZY_HELPER: &str =
+    "const $lazy = f => { let done = false, v; return () => done ? v : (done = true, v = f()); };\n";
+
+
+/**
+ * `expr` as a JavaScript expression. `val` writes a value as one.
+ */
+pub fn expression<T, F>(expr: &Expr<T>, eval: Evaluation, val: F) -> String
+where T: Types, T::Sym: Display, F: Fn(&T::Val) -> String {
+    let mut ret = String::new();
+    expression_rec(expr, eval, &val, &mut ret);
+    ret
+}
+
+fn expression_rec<T, F>(expr: &Expr<T>, eval: Evaluation, val: &F, out: &mut String)
+where T: Types, T::Sym: Display, F: Fn(&T::Val) -> String {
+    match expr {
+        Expr::Var(v) => {
+            out.push_str(&ident(v));
+            if eval == Evaluation::Lazy {
+                out.push_str("()");
+            }
+        },
+        Expr::Val(v) => out.push_str(&val(v)),
+        Expr::Lambda(a, b) => {
+            write!(out, "({}) => ", ident(a)).unwrap();
+            // An object literal body would need parentheses, but we
+            // never emit one.
+            expression_rec(b, eval, val, out);
+        },
+        Expr::App(f, x) => {
+            // Calling an arrow function needs it parenthesized.
+            let wrap = matches!(**f, Expr::Lambda(_, _));
+            if wrap {
+                out.push('(');
+            }
+            expression_rec(f, eval, val, out);
+            if wrap {
+                out.push(')');
+            }
+            out.push('(');
+            if eval == Evaluation::Lazy {
+                out.push_str("$lazy(() => ");
+            }
+            expression_rec(x, eval, val, out);
+            if eval == Evaluation::Lazy {
+                out.push(')');
+            }
+            out.push(')');
+        }
+    }
+}
+
+
+/**
+ * A module exporting `expr` as `name`, including any helper it needs.
+ * `name` must be a valid identifier.
+ */
+pub fn module<T, F>(name: &str, expr: &Expr<T>, eval: Evaluation, val: F) -> String
+where T: Types, T::Sym: Display, F: Fn(&T::Val) -> String {
+    let mut ret = String::new();
+    if eval == Evaluation::Lazy {
+        ret.push_str(LAZY_HELPER);
+    }
+    writeln!(ret, "export const {} = {};", name, expression(expr, eval, val)).unwrap();
+    ret
+}
+
+
+const RESERVED: &[&str] = &[
+    "await", "break", "case", "catch", "class", "const", "continue",
+    "debugger", "default", "delete", "do", "else", "enum", "export",
+    "extends", "false", "finally", "for", "function", "if", "implements",
+    "import", "in", "instanceof", "interface", "let", "new", "null",
+    "package", "private", "protected", "public", "return", "static",
+    "super", "switch", "this", "throw", "true", "try", "typeof", "var",
+    "void", "while", "with", "yield"
+];
+
+
+/* A JavaScript identifier for `sym`, the same for equal names. */
+fn ident<S: Display>(sym: &S) -> String {
+    let name = sym.to_string();
+    let mut ret = String::with_capacity(name.len());
+    for (i, c) in name.chars().enumerate() {
+        let ok = c.is_ascii_alphabetic() || c == '_' || (i > 0 && c.is_ascii_digit());
+        if ok {
+            ret.push(c);
+        } else {
+            write!(ret, "${:x}", u32::from(c)).unwrap();
+        }
+    }
+    // Reserved words, and the empty name, get a `$` in front.
+    if ret.is_empty() || RESERVED.contains(&ret.as_str()) {
+        ret.insert(0, '$');
+    }
+    ret
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trs::SigmaRules;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct JsTypes;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Int(i64);
+
+    impl SigmaRules for Int {
+        type Error = ();
+    }
+
+    impl Types for JsTypes {
+        type Val = Int;
+        type Sym = &'static str;
+    }
+
+    type E = Expr<JsTypes>;
+
+    fn int(v: &Int) -> String {
+        v.0.to_string()
+    }
+
+    // (\f.\x.f (f x)) (\y.succ y) 0
+    fn example() -> Box<E> {
+        E::apply(
+            E::apply(
+                E::lambda("f", E::lambda("x", E::apply(
+                    E::var("f"),
+                    E::apply(E::var("f"), E::var("x"))
+                ))),
+                E::lambda("y", E::apply(E::var("succ"), E::var("y")))
+            ),
+            E::val(Int(0))
+        )
+    }
+
+    #[test]
+    fn test_strict() {
+        assert_eq!(
+            expression(&example(), Evaluation::Strict, int),
+            "((f) => (x) => f(f(x)))((y) => succ(y))(0)"
+        );
+    }
+
+    #[test]
+    fn test_lazy() {
+        let id = E::apply(E::lambda("x", E::var("x")), E::val(Int(1)));
+        assert_eq!(
+            expression(&id, Evaluation::Lazy, int),
+            "((x) => x())($lazy(() => 1))"
+        );
+        let module = module("one", &id, Evaluation::Lazy, int);
+        assert!(module.starts_with(LAZY_HELPER));
+        assert!(module.ends_with("export const one = ((x) => x())($lazy(() => 1));\n"));
+        assert!(!super::module("one", &id, Evaluation::Strict, int).contains("$lazy"));
+    }
+
+    #[test]
+    fn test_ident() {
+        assert_eq!(ident(&"x1"), "x1");
+        assert_eq!(ident(&"1x"), "$31x");
+        assert_eq!(ident(&"x'"), "x$27");
+        assert_eq!(ident(&"new"), "$new");
+        assert_eq!(ident(&""), "$");
+        assert_eq!(ident(&"λ"), "$3bb");
+    }
+}
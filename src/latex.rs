@@ -0,0 +1,75 @@
+// The MIT License (MIT)
+//
+// Copyright © 2022 <Brandon Lewis>
+//
+// Permission is hereby granted, free of charge, to any person
+// obtaining a copy of this software and associated documentation
+// files (the “Software”), to deal in the Software without
+// restriction, including without limitation the rights to use, copy,
+// modify, merge, publish, distribute, sublicense, and/or sell copies
+// of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS
+// BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN
+// ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+// Fork this project to create your own MIT license that you can
+// always link to.
+
+
+/**
+ * Helpers for typesetting terms in LaTeX math mode.
+ *
+ * Identifiers longer than one character are set in `\mathit`, so
+ * that `fib` reads as a name rather than as the product `f i b`, and
+ * LaTeX's special characters are escaped.
+ */
+pub fn ident(s: &str) -> String {
+    let escaped = escape(s);
+    if s.chars().count() > 1 {
+        format!("\\mathit{{{}}}", escaped)
+    } else {
+        escaped
+    }
+}
+
+
+fn escape(s: &str) -> String {
+    let mut ret = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '#' | '$' | '%' | '&' | '_' | '{' | '}' => {
+                ret.push('\\');
+                ret.push(c);
+            },
+            '\\' => ret.push_str("\\backslash{}"),
+            '^'  => ret.push_str("\\hat{}"),
+            '~'  => ret.push_str("\\sim{}"),
+            c    => ret.push(c),
+        }
+    }
+    ret
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ident() {
+        assert_eq!(ident("x"), "x");
+        assert_eq!(ident("fib"), "\\mathit{fib}");
+        assert_eq!(ident("x_1"), "\\mathit{x\\_1}");
+        assert_eq!(ident("_"), "\\_");
+    }
+}
@@ -47,7 +47,38 @@ pub fn debug<T: Debug>(prefix: &str, value: T) -> T {
  * Just to get oriented, we start with a simple lambda expression
  * parser and evaluator.
  */
+pub mod expr;
+pub mod stepper;
+pub mod query;
+pub mod snapshot;
+pub mod explore;
+pub mod shrink;
+pub mod parallel;
+pub mod conformance;
+pub mod ski;
+pub mod usage;
+pub mod occurrence;
+pub mod names;
+pub mod num;
+pub mod text;
+pub mod host;
+pub mod provenance;
+#[cfg(feature = "rational")]
+pub mod rational;
+pub mod store;
 pub mod trs;
 pub mod grs;
+pub mod slab;
+mod json;
 pub mod ast;
 pub mod parser;
+pub mod scheme;
+pub mod dot;
+pub mod stack;
+pub mod latex;
+pub mod js;
+pub mod rust;
+pub mod prover;
+pub mod pretty;
+pub mod peephole;
+pub mod infix;
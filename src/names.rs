@@ -0,0 +1,460 @@
+// The MIT License (MIT)
+//
+// Copyright © 2022 <Brandon Lewis>
+//
+// Permission is hereby granted, free of charge, to any person
+// obtaining a copy of this software and associated documentation
+// files (the “Software”), to deal in the Software without
+// restriction, including without limitation the rights to use, copy,
+// modify, merge, publish, distribute, sublicense, and/or sell copies
+// of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS
+// BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN
+// ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+// Fork this project to create your own MIT license that you can
+// always link to.
+use crate::trs::Types;
+use crate::expr::Expr;
+use std::borrow::Cow;
+
+
+/*
+ * Fresh names and capture-avoiding substitution.
+ *
+ * Substitution stops at a binder of the same name, and renames a
+ * binder which would capture a free variable of the value being
+ * substituted, drawing the new name from a `NameSupply`. `Expr::reduce`
+ * and the `Stepper` contract beta redexes this way. Names are
+ * numbered from a counter, so the same input always gets the same
+ * names, and the supply remembers every name it has seen or made, so
+ * generated names never collide with the user's.
+ */
+
+
+/**
+ * How to make new symbols from old ones.
+ *
+ * `variant(n)` should give distinct symbols for distinct `n`. They may
+ * collide with existing names; `NameSupply` skips those.
+ *
+ * There is no impl for `&'static str`: a new name would have to be
+ * leaked to live that long. Terms over borrowed names should use
+ * `Cow<'_, str>`, whose variants are owned.
+ */
+pub trait Fresh: Sized {
+    fn variant(&self, n: usize) -> Self;
+}
+
+impl Fresh for String {
+    /* `x` becomes `x_1`, `x_2`, … */
+    fn variant(&self, n: usize) -> Self {
+        format!("{}_{}", self, n)
+    }
+}
+
+impl Fresh for Cow<'_, str> {
+    /* As for `String`; borrowed names stay borrowed. */
+    fn variant(&self, n: usize) -> Self {
+        Cow::Owned(format!("{}_{}", self, n))
+    }
+}
+
+
+/**
+ * A deterministic source of fresh names.
+ */
+#[derive(Clone, Debug)]
+pub struct NameSupply<S> {
+    // Every name seen or handed out. Symbols need only be comparable,
+    // so this is a list.
+    used: Vec<S>,
+    counter: usize
+}
+
+impl<S: Fresh + Clone + PartialEq> NameSupply<S> {
+    pub fn new() -> Self {
+        NameSupply {used: Vec::new(), counter: 0}
+    }
+
+    /* Never hand out `name`. */
+    pub fn avoid(&mut self, name: &S) {
+        if !self.used.contains(name) {
+            self.used.push(name.clone());
+        }
+    }
+
+    /* Never hand out any name, bound or free, occurring in `expr`. */
+    pub fn avoid_in<T: Types<Sym = S>>(&mut self, expr: &Expr<T>) {
+        match expr {
+            Expr::Var(v) => self.avoid(v),
+            Expr::Val(_) => (),
+            Expr::Lambda(a, b) => {
+                self.avoid(a);
+                self.avoid_in(b);
+            },
+            Expr::App(f, x) => {
+                self.avoid_in(f);
+                self.avoid_in(x);
+            }
+        }
+    }
+
+    /* A name based on `base` which hasn't been seen or handed out. */
+    pub fn fresh(&mut self, base: &S) -> S {
+        loop {
+            self.counter += 1;
+            let candidate = base.variant(self.counter);
+            if !self.used.contains(&candidate) {
+                self.used.push(candidate.clone());
+                return candidate;
+            }
+        }
+    }
+}
+
+impl<S: Fresh + Clone + PartialEq> Default for NameSupply<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+/**
+ * The free variables of `expr`, in order of first occurrence.
+ */
+pub fn free_vars<T: Types>(expr: &Expr<T>) -> Vec<T::Sym> {
+    let mut free = Vec::new();
+    free_vars_rec(expr, &mut Vec::new(), &mut free);
+    free
+}
+
+fn free_vars_rec<T: Types>(expr: &Expr<T>, bound: &mut Vec<T::Sym>, free: &mut Vec<T::Sym>) {
+    match expr {
+        Expr::Var(v) => if !bound.contains(v) && !free.contains(v) {
+            free.push(v.clone());
+        },
+        Expr::Val(_) => (),
+        Expr::Lambda(a, b) => {
+            bound.push(a.clone());
+            free_vars_rec(b, bound, free);
+            bound.pop();
+        },
+        Expr::App(f, x) => {
+            free_vars_rec(f, bound, free);
+            free_vars_rec(x, bound, free);
+        }
+    }
+}
+
+
+/**
+ * Replace the free occurrences of `var` in `expr` with `value`.
+ *
+ * A binder in `expr` that would capture a free variable of `value` is
+ * renamed to a fresh name first. Every name in `expr` and `value` is
+ * added to `supply`'s avoid list.
+ */
+pub fn substitute<T>(
+    expr: &Expr<T>,
+    var: &T::Sym,
+    value: &Expr<T>,
+    supply: &mut NameSupply<T::Sym>
+) -> Expr<T> where T: Types + Clone, T::Sym: Fresh {
+    supply.avoid_in(expr);
+    supply.avoid_in(value);
+    substitute_avoiding(expr, var, value, supply)
+}
+
+/* `substitute`, for a `supply` which already avoids every name in
+ * `expr` and `value`, so they needn't be walked again. */
+pub(crate) fn substitute_avoiding<T>(
+    expr: &Expr<T>,
+    var: &T::Sym,
+    value: &Expr<T>,
+    supply: &mut NameSupply<T::Sym>
+) -> Expr<T> where T: Types + Clone, T::Sym: Fresh {
+    subst_rec(expr, var, value, &free_vars(value), supply)
+}
+
+fn subst_rec<T>(
+    expr: &Expr<T>,
+    var: &T::Sym,
+    value: &Expr<T>,
+    value_free: &[T::Sym],
+    supply: &mut NameSupply<T::Sym>
+) -> Expr<T> where T: Types + Clone, T::Sym: Fresh {
+    match expr {
+        Expr::Var(v) if v == var => value.clone(),
+        Expr::Var(_) | Expr::Val(_) => expr.clone(),
+        Expr::App(f, x) => Expr::App(
+            Box::new(subst_rec(f, var, value, value_free, supply)),
+            Box::new(subst_rec(x, var, value, value_free, supply))
+        ),
+        // `var` is shadowed here.
+        Expr::Lambda(a, _) if a == var => expr.clone(),
+        Expr::Lambda(a, b) if value_free.contains(a) && free_vars(b).contains(var) => {
+            let fresh = supply.fresh(a);
+            let renaming = Expr::Var(fresh.clone());
+            let renamed = subst_rec(b, a, &renaming, core::slice::from_ref(&fresh), supply);
+            Expr::Lambda(fresh, Box::new(subst_rec(&renamed, var, value, value_free, supply)))
+        },
+        Expr::Lambda(a, b) => Expr::Lambda(
+            a.clone(),
+            Box::new(subst_rec(b, var, value, value_free, supply))
+        )
+    }
+}
+
+
+/**
+ * Contract the beta redex `(λx.b) a` to `b[x := a]`, avoiding capture.
+ *
+ * Returns `None` if `expr` is not a beta redex. Unlike
+ * `Expr::reduce_with`, this adds the names in `expr` to `supply`
+ * first, so any supply will do.
+ */
+pub fn beta<T>(expr: &Expr<T>, supply: &mut NameSupply<T::Sym>) -> Option<Expr<T>>
+where T: Types + Clone, T::Sym: Fresh {
+    match expr {
+        Expr::App(f, a) => match &**f {
+            Expr::Lambda(x, b) => Some(substitute(b, x, a, supply)),
+            _ => None
+        },
+        _ => None
+    }
+}
+
+
+/**
+ * Rename every binder in `expr` to a fresh name.
+ *
+ * Afterwards no two lambdas bind the same name, and no binder has the
+ * same name as a free variable, so passes can substitute and move
+ * subterms around without checking for capture. The result is
+ * alpha-equivalent to `expr`; free variables are left alone.
+ */
+pub fn uniquify<T>(expr: &Expr<T>, supply: &mut NameSupply<T::Sym>) -> Expr<T>
+where T: Types + Clone, T::Sym: Fresh {
+    supply.avoid_in(expr);
+    uniquify_rec(expr, &mut Vec::new(), supply)
+}
+
+fn uniquify_rec<T>(
+    expr: &Expr<T>,
+    scope: &mut Vec<(T::Sym, T::Sym)>,
+    supply: &mut NameSupply<T::Sym>
+) -> Expr<T> where T: Types + Clone, T::Sym: Fresh {
+    match expr {
+        Expr::Var(v) => match scope.iter().rev().find(|(old, _)| old == v) {
+            Some((_, new)) => Expr::Var(new.clone()),
+            None           => expr.clone()
+        },
+        Expr::Val(_) => expr.clone(),
+        Expr::Lambda(a, b) => {
+            let fresh = supply.fresh(a);
+            scope.push((a.clone(), fresh.clone()));
+            let body = uniquify_rec(b, scope, supply);
+            scope.pop();
+            Expr::Lambda(fresh, Box::new(body))
+        },
+        Expr::App(f, x) => Expr::App(
+            Box::new(uniquify_rec(f, scope, supply)),
+            Box::new(uniquify_rec(x, scope, supply))
+        )
+    }
+}
+
+/**
+ * Whether every binder in `expr` is distinct from every other binder
+ * and from every free variable, as after `uniquify`.
+ */
+pub fn has_unique_binders<T: Types>(expr: &Expr<T>) -> bool {
+    fn binders<T: Types>(expr: &Expr<T>, out: &mut Vec<T::Sym>) {
+        match expr {
+            Expr::Lambda(a, b) => {
+                out.push(a.clone());
+                binders(b, out);
+            },
+            Expr::App(f, x) => {
+                binders(f, out);
+                binders(x, out);
+            },
+            Expr::Var(_) | Expr::Val(_) => ()
+        }
+    }
+
+    let mut seen = free_vars(expr);
+    let mut all = Vec::new();
+    binders(expr, &mut all);
+    for a in all {
+        if seen.contains(&a) {
+            return false;
+        }
+        seen.push(a);
+    }
+    true
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trs::SigmaRules;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct NameTypes;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Unit;
+
+    impl SigmaRules for Unit {
+        type Error = ();
+    }
+
+    impl Types for NameTypes {
+        type Val = Unit;
+        type Sym = String;
+    }
+
+    type E = Expr<NameTypes>;
+
+    #[test]
+    fn test_fresh_is_deterministic() {
+        let mut a = NameSupply::<String>::new();
+        a.avoid(&"x_2".to_string());
+        let names: Vec<String> = (0..3).map(|_| a.fresh(&"x".into())).collect();
+        assert_eq!(names, vec!["x_1", "x_3", "x_4"]);
+
+        let mut b = NameSupply::<String>::new();
+        b.avoid(&"x_2".to_string());
+        assert_eq!(b.fresh(&"x".into()), "x_1");
+    }
+
+    #[test]
+    fn test_free_vars() {
+        // \x.f x (\f.f y) x
+        let term = E::lambda("x", E::apply(
+            E::apply(
+                E::apply(E::var("f"), E::var("x")),
+                E::lambda("f", E::apply(E::var("f"), E::var("y")))
+            ),
+            E::var("x")
+        ));
+        assert_eq!(free_vars(&term), vec!["f", "y"]);
+    }
+
+    #[test]
+    fn test_substitute_avoids_capture() {
+        let mut supply = NameSupply::new();
+
+        // (\y.x y)[x := y] is \y_1.y y_1, not \y.y y.
+        let term = E::lambda("y", E::apply(E::var("x"), E::var("y")));
+        assert_eq!(
+            substitute(&term, &"x".into(), &E::var("y"), &mut supply),
+            *E::lambda("y_1", E::apply(E::var("y"), E::var("y_1")))
+        );
+
+        // No renaming when there is nothing to capture, or when the
+        // variable is shadowed.
+        let term = E::lambda("y", E::var("y"));
+        assert_eq!(substitute(&term, &"x".into(), &E::var("y"), &mut supply), *term);
+        let term = E::lambda("x", E::var("x"));
+        assert_eq!(substitute(&term, &"x".into(), &E::var("y"), &mut supply), *term);
+    }
+
+    #[test]
+    fn test_beta_agrees_with_reduce() {
+        // (\x.\x.x) z
+        let term = E::apply(E::lambda("x", E::lambda("x", E::var("x"))), E::var("z"));
+        assert_eq!(beta(&term, &mut NameSupply::new()), Some(*term.clone().reduce().unwrap()));
+        assert_eq!(
+            beta(&term, &mut NameSupply::new()),
+            Some(*E::lambda("x", E::var("x")))
+        );
+        assert_eq!(beta(&E::var("z"), &mut NameSupply::new()), None);
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct CowTypes;
+
+    impl Types for CowTypes {
+        type Val = Unit;
+        type Sym = Cow<'static, str>;
+    }
+
+    #[test]
+    fn test_cow_symbols() {
+        type C = Expr<CowTypes>;
+
+        // (\x.\y.x y) y reduces to \y_1.y y_1.
+        let term = C::apply(
+            C::lambda("x", C::lambda("y", C::apply(C::var("x"), C::var("y")))),
+            C::var("y")
+        );
+        assert_eq!(
+            *term.reduce().unwrap(),
+            *C::lambda("y_1", C::apply(C::var("y"), C::var("y_1")))
+        );
+    }
+
+    /* A symbol type that isn't a string. */
+    #[derive(Clone, Debug, PartialEq)]
+    struct Indexed(&'static str, usize);
+
+    impl Fresh for Indexed {
+        fn variant(&self, n: usize) -> Self {
+            Indexed(self.0, n)
+        }
+    }
+
+    #[test]
+    fn test_custom_scheme() {
+        let mut supply = NameSupply::new();
+        supply.avoid(&Indexed("x", 1));
+        assert_eq!(supply.fresh(&Indexed("x", 0)), Indexed("x", 2));
+        assert_eq!(supply.fresh(&Indexed("y", 0)), Indexed("y", 3));
+    }
+
+    #[test]
+    fn test_uniquify() {
+        // \x.(\x.x) (\y.x y) y
+        let term = E::lambda("x", E::apply(
+            E::apply(
+                E::lambda("x", E::var("x")),
+                E::lambda("y", E::apply(E::var("x"), E::var("y")))
+            ),
+            E::var("y")
+        ));
+        assert!(!has_unique_binders(&term));
+
+        let unique = uniquify(&term, &mut NameSupply::new());
+        assert_eq!(unique, *E::lambda("x_1", E::apply(
+            E::apply(
+                E::lambda("x_2", E::var("x_2")),
+                E::lambda("y_3", E::apply(E::var("x_1"), E::var("y_3")))
+            ),
+            E::var("y")
+        )));
+        assert!(has_unique_binders(&unique));
+        assert!(unique.alpha_eq(&term));
+    }
+
+    #[test]
+    fn test_uniquify_skips_used_names() {
+        // (\x.\x.x) z
+        let term = E::apply(E::lambda("x", E::lambda("x", E::var("x"))), E::var("z"));
+        let unique = uniquify(&term, &mut NameSupply::new());
+        assert_eq!(*unique.reduce().unwrap(), *E::lambda("x_2", E::var("x_2")));
+    }
+}
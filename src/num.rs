@@ -0,0 +1,510 @@
+// The MIT License (MIT)
+//
+// Copyright © 2022 <Brandon Lewis>
+//
+// Permission is hereby granted, free of charge, to any person
+// obtaining a copy of this software and associated documentation
+// files (the “Software”), to deal in the Software without
+// restriction, including without limitation the rights to use, copy,
+// modify, merge, publish, distribute, sublicense, and/or sell copies
+// of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS
+// BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN
+// ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+// Fork this project to create your own MIT license that you can
+// always link to.
+use core::fmt;
+use core::convert::TryFrom;
+use crate::trs::{Types, SigmaRules};
+use crate::expr::Expr;
+
+
+/*
+ * A standard numeric value type.
+ *
+ * Integers are 64 bits on every platform, and what happens on overflow
+ * is spelled out by each primitive rather than left to the build
+ * profile, so a term normalizes to the same value, and prints the
+ * same, everywhere. Floating point is deliberately left out.
+ *
+ * Characters are Unicode scalar values, converted to and from their
+ * codepoints with `ord` and `chr`. See `text` for strings.
+ */
+
+
+/**
+ * What an arithmetic primitive does when the result doesn't fit.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Overflow {
+    // Overflow is a sigma error.
+    Checked,
+    // Two's complement wraparound.
+    Wrapping,
+    // Clamp to `i64::MIN` or `i64::MAX`.
+    Saturating
+}
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Op {
+    Add,
+    Sub,
+    Mul,
+    // Division rounds toward zero, and `Rem` takes the sign of the
+    // dividend, as in Rust.
+    Div,
+    Rem,
+    Eq,
+    Lt
+}
+
+
+/**
+ * A binary primitive, with its overflow behavior.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Prim {
+    pub op: Op,
+    pub overflow: Overflow
+}
+
+impl Prim {
+    pub fn eval(self, a: i64, b: i64) -> Result<Num, NumError> {
+        let checked = match self.op {
+            Op::Add => a.checked_add(b),
+            Op::Sub => a.checked_sub(b),
+            Op::Mul => a.checked_mul(b),
+            Op::Div | Op::Rem if b == 0 => return Err(NumError::DivideByZero),
+            Op::Div => a.checked_div(b),
+            // Only i64::MIN % -1 fails, and its exact result is 0.
+            Op::Rem => Some(a.checked_rem(b).unwrap_or(0)),
+            Op::Eq  => return Ok(Num::Bool(a == b)),
+            Op::Lt  => return Ok(Num::Bool(a < b))
+        };
+
+        match (checked, self.overflow) {
+            (Some(n), _) => Ok(Num::Int(n)),
+            (None, Overflow::Checked) => Err(NumError::Overflow),
+            (None, Overflow::Wrapping) => Ok(Num::Int(match self.op {
+                Op::Add => a.wrapping_add(b),
+                Op::Sub => a.wrapping_sub(b),
+                Op::Mul => a.wrapping_mul(b),
+                Op::Div => a.wrapping_div(b),
+                _       => unreachable!("can't overflow")
+            })),
+            (None, Overflow::Saturating) => Ok(Num::Int(match self.op {
+                Op::Add => a.saturating_add(b),
+                Op::Sub => a.saturating_sub(b),
+                Op::Mul => a.saturating_mul(b),
+                Op::Div => a.saturating_div(b),
+                _       => unreachable!("can't overflow")
+            }))
+        }
+    }
+}
+
+
+/**
+ * A primitive of one argument.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Unary {
+    // The codepoint of a `Char`.
+    Ord,
+    // The `Char` with a codepoint, failing on surrogates and values
+    // past U+10FFFF.
+    Chr,
+    // The state after `Int` in a xorshift64 generator; see `random`.
+    Random
+}
+
+impl Unary {
+    pub fn eval(self, x: Num) -> Result<Num, NumError> {
+        match (self, x) {
+            (Unary::Ord, Num::Char(c)) => Ok(Num::Int(i64::from(u32::from(c)))),
+            (Unary::Chr, Num::Int(n)) => u32::try_from(n).ok()
+                .and_then(char::from_u32)
+                .map(Num::Char)
+                .ok_or(NumError::InvalidChar),
+            (Unary::Random, Num::Int(n)) => Ok(Num::Int(random(n))),
+            _ => Err(NumError::NotApplicable)
+        }
+    }
+}
+
+
+/* What `Primitives` resolves `seed` to unless told otherwise. */
+pub const DEFAULT_SEED: i64 = 0x2545_f491_4f6c_dd1d;
+
+
+/**
+ * One step of a xorshift64 generator, on the state's bits.
+ *
+ * Terms have no mutable state, so a program threads the state
+ * through itself: `random s` is both the next number and the state to
+ * draw the one after from, and the same seed always gives the same
+ * numbers, whatever the strategy. Results can be negative; take `%`
+ * for a range. Zero would stay zero, so it steps as `DEFAULT_SEED`.
+ */
+pub fn random(state: i64) -> i64 {
+    let mut x = if state == 0 { DEFAULT_SEED as u64 } else { state as u64 };
+    x ^= x << 13;
+    x ^= x >> 7;
+    x ^= x << 17;
+    x as i64
+}
+
+
+/**
+ * Numbers, booleans, characters, and the primitives over them.
+ *
+ * Binary primitives are curried: `Prim` applied to an `Int` gives a
+ * `Partial`, which applied to another `Int` gives the result.
+ *
+ * A `Bool` selects between its next two arguments, like a Church
+ * boolean, so a comparison can be the test of a conditional.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Num {
+    Int(i64),
+    Bool(bool),
+    Char(char),
+    Prim(Prim),
+    Partial(Prim, i64),
+    Unary(Unary)
+}
+
+impl Num {
+    pub fn prim(op: Op, overflow: Overflow) -> Self {
+        Num::Prim(Prim {op, overflow})
+    }
+}
+
+impl From<i64> for Num {
+    fn from(n: i64) -> Self {
+        Num::Int(n)
+    }
+}
+
+impl From<char> for Num {
+    fn from(c: char) -> Self {
+        Num::Char(c)
+    }
+}
+
+
+/**
+ * The primitives by name, all with one overflow mode.
+ *
+ * Names are the operator symbols `Op` displays as, so a term read with
+ * `infix::OperatorTable::arithmetic()` can be passed to `resolve`.
+ *
+ * `seed` names the seed, so a program draws reproducible numbers with
+ * `random seed`, `random (random seed)`, and so on.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Primitives {
+    pub overflow: Overflow,
+    pub seed: i64
+}
+
+impl Primitives {
+    pub fn new(overflow: Overflow) -> Self {
+        Primitives {overflow, seed: DEFAULT_SEED}
+    }
+
+    pub fn with_seed(self, seed: i64) -> Self {
+        Primitives {seed, ..self}
+    }
+
+    pub fn lookup(&self, name: &str) -> Option<Num> {
+        let op = match name {
+            "+"  => Op::Add,
+            "-"  => Op::Sub,
+            "*"  => Op::Mul,
+            "/"  => Op::Div,
+            "%"  => Op::Rem,
+            "==" => Op::Eq,
+            "<"  => Op::Lt,
+            "ord" => return Some(Num::Unary(Unary::Ord)),
+            "chr" => return Some(Num::Unary(Unary::Chr)),
+            "random" => return Some(Num::Unary(Unary::Random)),
+            "seed" => return Some(Num::Int(self.seed)),
+            _    => return None
+        };
+        Some(Num::prim(op, self.overflow))
+    }
+
+    /**
+     * Replace the free variables of `expr` which name primitives with
+     * the primitives themselves.
+     */
+    pub fn resolve<T>(&self, expr: &Expr<T>) -> Expr<T>
+    where T: Types + Clone, T::Sym: AsRef<str>, T::Val: From<Num> {
+        expr.replace_free(|v| self.lookup(v.as_ref()).map(|prim| Expr::Val(prim.into())))
+    }
+}
+
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NumError {
+    // Something other than a primitive applied to an integer.
+    #[default]
+    NotApplicable,
+    Overflow,
+    DivideByZero,
+    // `chr` of something that isn't a Unicode scalar value.
+    InvalidChar
+}
+
+impl SigmaRules for Num {
+    type Error = NumError;
+
+    fn apply(f: Self, x: Self) -> Result<Self, NumError> {
+        match (f, x) {
+            (Num::Prim(p), Num::Int(a))       => Ok(Num::Partial(p, a)),
+            (Num::Partial(p, a), Num::Int(b)) => p.eval(a, b),
+            (Num::Unary(u), x)                => u.eval(x),
+            _                                 => Err(NumError::NotApplicable)
+        }
+    }
+
+    fn select(&self) -> Option<bool> {
+        match self {
+            Num::Bool(b) => Some(*b),
+            _            => None
+        }
+    }
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Op::Add => "+",
+            Op::Sub => "-",
+            Op::Mul => "*",
+            Op::Div => "/",
+            Op::Rem => "%",
+            Op::Eq  => "==",
+            Op::Lt  => "<"
+        })
+    }
+}
+
+impl fmt::Display for Num {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Num::Int(n)        => write!(f, "{}", n),
+            Num::Bool(b)       => write!(f, "{}", b),
+            Num::Char(c)       => write!(f, "{:?}", c),
+            Num::Prim(p)       => write!(f, "({})", p.op),
+            Num::Partial(p, a) => write!(f, "({} {})", p.op, a),
+            Num::Unary(Unary::Ord) => write!(f, "ord"),
+            Num::Unary(Unary::Chr) => write!(f, "chr"),
+            Num::Unary(Unary::Random) => write!(f, "random")
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expr::Expr;
+    use crate::stepper::{Stepper, NormalOrder, Stop};
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct NumTypes;
+
+    impl Types for NumTypes {
+        type Val = Num;
+        type Sym = String;
+    }
+
+    type E = Expr<NumTypes>;
+
+    fn apply2(f: Num, a: i64, b: i64) -> Result<Num, NumError> {
+        Num::apply(Num::apply(f, Num::Int(a))?, Num::Int(b))
+    }
+
+    #[test]
+    fn test_checked() {
+        let add = Num::prim(Op::Add, Overflow::Checked);
+        assert_eq!(apply2(add, 2, 3), Ok(Num::Int(5)));
+        assert_eq!(apply2(add, i64::MAX, 1), Err(NumError::Overflow));
+
+        let div = Num::prim(Op::Div, Overflow::Checked);
+        assert_eq!(apply2(div, -7, 2), Ok(Num::Int(-3)));
+        assert_eq!(apply2(div, 1, 0), Err(NumError::DivideByZero));
+        assert_eq!(apply2(div, i64::MIN, -1), Err(NumError::Overflow));
+
+        let rem = Num::prim(Op::Rem, Overflow::Checked);
+        assert_eq!(apply2(rem, -7, 2), Ok(Num::Int(-1)));
+        assert_eq!(apply2(rem, i64::MIN, -1), Ok(Num::Int(0)));
+    }
+
+    #[test]
+    fn test_wrapping() {
+        let mul = Num::prim(Op::Mul, Overflow::Wrapping);
+        assert_eq!(apply2(mul, i64::MAX, 2), Ok(Num::Int(-2)));
+        let div = Num::prim(Op::Div, Overflow::Wrapping);
+        assert_eq!(apply2(div, i64::MIN, -1), Ok(Num::Int(i64::MIN)));
+        assert_eq!(apply2(div, 1, 0), Err(NumError::DivideByZero));
+    }
+
+    #[test]
+    fn test_comparisons() {
+        let lt = Num::prim(Op::Lt, Overflow::Checked);
+        assert_eq!(apply2(lt, 1, 2), Ok(Num::Bool(true)));
+        assert_eq!(Num::apply(Num::Bool(true), Num::Int(1)), Err(NumError::NotApplicable));
+    }
+
+    #[test]
+    fn test_select() {
+        // (< a b) 10 20
+        let lt = |a, b| E::apply(
+            E::apply(E::val(Num::prim(Op::Lt, Overflow::Checked)), E::val(a)),
+            E::val(b)
+        );
+        let pick = |test| E::apply(E::apply(test, E::val(10)), E::val(20));
+
+        let redex = pick(E::val(Num::Bool(false)));
+        assert_eq!(redex.redex(), Some(crate::expr::Reduction::Select));
+        let explanation = crate::stepper::explain(&redex, &E::val(20)).unwrap();
+        assert_eq!(explanation.english, "select: `false`, so take `20` over `10`");
+
+        for (a, b, expected) in [(1, 2, 10), (2, 1, 20)] {
+            let mut stepper = Stepper::new(pick(lt(a, b)), NormalOrder);
+            assert_eq!(stepper.run(100).unwrap(), Stop::NormalForm);
+            assert_eq!(*stepper.term(), Expr::Val(Num::Int(expected)));
+        }
+
+        // A boolean given one argument is waiting, not stuck.
+        let partial = E::apply(E::val(Num::Bool(true)), E::val(10));
+        assert_eq!(partial.redex(), None);
+        assert_eq!(partial.redexes(), Vec::<crate::expr::Path>::new());
+    }
+
+    #[test]
+    fn test_normalize() {
+        // (\x.x * x) (1 + 2)
+        let op = |op| E::val(Num::prim(op, Overflow::Checked));
+        let bin = |o, a, b| E::apply(E::apply(op(o), a), b);
+        let term = E::apply(
+            E::lambda("x", bin(Op::Mul, E::var("x"), E::var("x"))),
+            bin(Op::Add, E::val(1), E::val(2))
+        );
+        assert_eq!(term.to_string(), "(λx.(*) x x) ((+) 1 2)");
+
+        let mut stepper = Stepper::new(term, NormalOrder);
+        assert_eq!(stepper.run(100).unwrap(), Stop::NormalForm);
+        assert_eq!(*stepper.term(), Expr::Val(Num::Int(9)));
+    }
+
+    #[test]
+    fn test_saturating() {
+        let sub = Num::prim(Op::Sub, Overflow::Saturating);
+        assert_eq!(apply2(sub, i64::MIN, 1), Ok(Num::Int(i64::MIN)));
+        let mul = Num::prim(Op::Mul, Overflow::Saturating);
+        assert_eq!(apply2(mul, i64::MAX, -2), Ok(Num::Int(i64::MIN)));
+        let div = Num::prim(Op::Div, Overflow::Saturating);
+        assert_eq!(apply2(div, i64::MIN, -1), Ok(Num::Int(i64::MAX)));
+    }
+
+    #[test]
+    fn test_primitives() {
+        use crate::infix::{convert, Infix, OperatorTable};
+
+        // (\max.max - 1 + 2) i64::MAX overflows on the +.
+        let input = "max - 1 + 2".split_whitespace().map(|w| match w.parse() {
+            Ok(n)  => Infix::Val(Num::Int(n)),
+            Err(_) => Infix::Id(w.into())
+        });
+        let tokens = convert(input, &OperatorTable::arithmetic()).unwrap();
+        let term = E::parse_owned(tokens.into_iter()).unwrap();
+        let term = E::apply(E::lambda("max", term), E::val(i64::MAX));
+
+        for (overflow, expected) in [
+            (Overflow::Wrapping, Expr::Val(Num::Int(i64::MIN))),
+            (Overflow::Saturating, Expr::Val(Num::Int(i64::MAX)))
+        ] {
+            let resolved = Primitives::new(overflow).resolve(&term);
+            let mut stepper = Stepper::new(Box::new(resolved), NormalOrder);
+            assert_eq!(stepper.run(100).unwrap(), Stop::NormalForm);
+            assert_eq!(*stepper.term(), expected);
+        }
+
+        let resolved = Primitives::new(Overflow::Checked).resolve(&term);
+        let mut stepper = Stepper::new(Box::new(resolved), NormalOrder);
+        match stepper.run(100) {
+            Err(crate::expr::ReduceError::Delta {func, arg, path, error}) => {
+                assert_eq!(error, NumError::Overflow);
+                assert_eq!(func.to_string(), "(+ 9223372036854775806)");
+                assert_eq!(arg, Num::Int(2));
+                assert!(path.is_empty());
+            },
+            other => panic!("expected a delta error, got {:?}", other)
+        }
+
+        // A bound + is left alone.
+        let shadowed = E::lambda("+", E::var("+"));
+        assert_eq!(Primitives::new(Overflow::Checked).resolve(&shadowed), *shadowed);
+    }
+
+    #[test]
+    fn test_chars() {
+        let prims = Primitives::new(Overflow::Checked);
+        let ord = prims.lookup("ord").unwrap();
+        let chr = prims.lookup("chr").unwrap();
+
+        assert_eq!(Num::apply(ord, Num::Char('λ')), Ok(Num::Int(0x3bb)));
+        assert_eq!(Num::apply(chr, Num::Int(0x41)), Ok(Num::Char('A')));
+        assert_eq!(Num::apply(chr, Num::Int(0xd800)), Err(NumError::InvalidChar));
+        assert_eq!(Num::apply(chr, Num::Int(-1)), Err(NumError::InvalidChar));
+        assert_eq!(Num::apply(ord, Num::Int(1)), Err(NumError::NotApplicable));
+        assert_eq!(Num::Char('\n').to_string(), "'\\n'");
+    }
+
+    #[test]
+    fn test_random() {
+        // Published xorshift64 (13, 7, 17) output from state 1.
+        assert_eq!(random(1), 1082269761);
+        assert_eq!(random(0), random(DEFAULT_SEED));
+        assert_ne!(random(DEFAULT_SEED), DEFAULT_SEED);
+
+        // (\r.(+) (r seed) (r (r seed))) random: the same seed gives
+        // the same sum, and another seed a different one.
+        let term = E::apply(
+            E::lambda("r", E::apply(
+                E::apply(
+                    E::var("+"),
+                    E::apply(E::var("r"), E::var("seed"))
+                ),
+                E::apply(E::var("r"), E::apply(E::var("r"), E::var("seed")))
+            )),
+            E::var("random")
+        );
+        let run = |seed| {
+            let prims = Primitives::new(Overflow::Wrapping).with_seed(seed);
+            let mut stepper = Stepper::new(Box::new(prims.resolve(&term)), NormalOrder);
+            assert_eq!(stepper.run(100).unwrap(), Stop::NormalForm);
+            stepper.into_term()
+        };
+        let expected = random(7).wrapping_add(random(random(7)));
+        assert_eq!(*run(7), Expr::Val(Num::Int(expected)));
+        assert_eq!(run(7), run(7));
+        assert_ne!(run(7), run(8));
+    }
+}
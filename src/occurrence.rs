@@ -0,0 +1,211 @@
+// The MIT License (MIT)
+//
+// Copyright © 2022 <Brandon Lewis>
+//
+// Permission is hereby granted, free of charge, to any person
+// obtaining a copy of this software and associated documentation
+// files (the “Software”), to deal in the Software without
+// restriction, including without limitation the rights to use, copy,
+// modify, merge, publish, distribute, sublicense, and/or sell copies
+// of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS
+// BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN
+// ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+// Fork this project to create your own MIT license that you can
+// always link to.
+use std::collections::BTreeMap;
+use crate::trs::Types;
+use crate::expr::{Expr, Dir, Path};
+
+
+/*
+ * Occurrence analysis.
+ *
+ * For each lambda, record how often its variable occurs, and whether
+ * any occurrence is under a further lambda. This is what an inliner
+ * needs to know: a variable used once, not under a lambda, can have
+ * its argument substituted without duplicating work, and an unused one
+ * can have its argument dropped. Compare `usage::check`, which only
+ * reports violations of a fixed discipline.
+ */
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Count {
+    Zero,
+    One,
+    Many
+}
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Occurrence {
+    pub count: Count,
+    // Some occurrence is inside a lambda nested in the binder's body,
+    // so it may be evaluated more than once.
+    pub under_lambda: bool
+}
+
+impl Occurrence {
+    /* The argument is never used, and can be dropped. */
+    pub fn is_dead(&self) -> bool {
+        self.count == Count::Zero
+    }
+
+    /* The argument can be substituted without duplicating work. */
+    pub fn inline_is_safe(&self) -> bool {
+        match self.count {
+            Count::Zero => true,
+            Count::One  => !self.under_lambda,
+            Count::Many => false
+        }
+    }
+}
+
+
+/**
+ * Occurrence information for every lambda in `expr`, keyed by the
+ * path to the lambda.
+ *
+ * Occurrences are counted syntactically, with inner binders of the
+ * same name shadowing outer ones.
+ */
+pub fn analyze<T: Types>(expr: &Expr<T>) -> BTreeMap<Path, Occurrence> {
+    let mut table = BTreeMap::new();
+    analyze_rec(expr, &mut Vec::new(), &mut Vec::new(), &mut table);
+    table
+}
+
+struct Binder<S> {
+    name: S,
+    // Lambdas enclosing the binder's body, including its own.
+    depth: usize,
+    info: Occurrence
+}
+
+fn analyze_rec<T: Types>(
+    expr: &Expr<T>,
+    here: &mut Path,
+    scope: &mut Vec<Binder<T::Sym>>,
+    table: &mut BTreeMap<Path, Occurrence>
+) {
+    match expr {
+        Expr::Var(v) => {
+            let depth = lambda_depth(here);
+            if let Some(binder) = scope.iter_mut().rev().find(|b| b.name == *v) {
+                binder.info.count = match binder.info.count {
+                    Count::Zero => Count::One,
+                    _           => Count::Many
+                };
+                binder.info.under_lambda |= depth > binder.depth;
+            }
+        },
+        Expr::Val(_) => (),
+        Expr::Lambda(a, b) => {
+            here.push(Dir::Body);
+            scope.push(Binder {
+                name: a.clone(),
+                depth: lambda_depth(here),
+                info: Occurrence {count: Count::Zero, under_lambda: false}
+            });
+            analyze_rec(b, here, scope, table);
+            here.pop();
+            let binder = scope.pop().expect("scope pushed above");
+            table.insert(here.clone(), binder.info);
+        },
+        Expr::App(f, x) => {
+            here.push(Dir::Func);
+            analyze_rec(f, here, scope, table);
+            here.pop();
+            here.push(Dir::Arg);
+            analyze_rec(x, here, scope, table);
+            here.pop();
+        }
+    }
+}
+
+fn lambda_depth(path: &[Dir]) -> usize {
+    path.iter().filter(|d| **d == Dir::Body).count()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trs::SigmaRules;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct OccTypes;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Unit;
+
+    impl SigmaRules for Unit {
+        type Error = ();
+    }
+
+    impl Types for OccTypes {
+        type Val = Unit;
+        type Sym = &'static str;
+    }
+
+    type E = Expr<OccTypes>;
+    use Dir::*;
+
+    fn occ(count: Count, under_lambda: bool) -> Occurrence {
+        Occurrence {count, under_lambda}
+    }
+
+    #[test]
+    fn test_counts() {
+        // \x.\y.\z.x (x z)
+        let term = E::lambda("x", E::lambda("y", E::lambda("z", E::apply(
+            E::var("x"),
+            E::apply(E::var("x"), E::var("z"))
+        ))));
+        let table = analyze(&term);
+
+        assert_eq!(table[&vec![]], occ(Count::Many, true));
+        assert_eq!(table[&vec![Body]], occ(Count::Zero, false));
+        assert_eq!(table[&vec![Body, Body]], occ(Count::One, false));
+        assert!(table[&vec![Body]].is_dead());
+        assert!(table[&vec![Body, Body]].inline_is_safe());
+        assert!(!table[&vec![]].inline_is_safe());
+    }
+
+    #[test]
+    fn test_under_lambda() {
+        // (\x.\y.x) (\x.x): the first x is used once, but under \y.
+        let term = E::apply(
+            E::lambda("x", E::lambda("y", E::var("x"))),
+            E::lambda("x", E::var("x"))
+        );
+        let table = analyze(&term);
+
+        assert_eq!(table.len(), 3);
+        assert_eq!(table[&vec![Func]], occ(Count::One, true));
+        assert!(!table[&vec![Func]].inline_is_safe());
+        assert_eq!(table[&vec![Func, Body]], occ(Count::Zero, false));
+        assert_eq!(table[&vec![Arg]], occ(Count::One, false));
+    }
+
+    #[test]
+    fn test_shadowing() {
+        // \x.\x.x: the inner x shadows the outer.
+        let term = E::lambda("x", E::lambda("x", E::var("x")));
+        let table = analyze(&term);
+        assert_eq!(table[&vec![]], occ(Count::Zero, false));
+        assert_eq!(table[&vec![Body]], occ(Count::One, false));
+    }
+}
@@ -0,0 +1,203 @@
+// The MIT License (MIT)
+//
+// Copyright © 2022 <Brandon Lewis>
+//
+// Permission is hereby granted, free of charge, to any person
+// obtaining a copy of this software and associated documentation
+// files (the “Software”), to deal in the Software without
+// restriction, including without limitation the rights to use, copy,
+// modify, merge, publish, distribute, sublicense, and/or sell copies
+// of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS
+// BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN
+// ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+// Fork this project to create your own MIT license that you can
+// always link to.
+use crate::trs::{Types, SigmaRules};
+use crate::expr::{Expr, Dir, ReduceError};
+use crate::names::Fresh;
+
+
+/*
+ * Experimental: parallel reduction of lambda expressions.
+ *
+ * In a tree, the outermost redexes are disjoint by construction: no
+ * outermost redex contains another. So all of them can be contracted
+ * at once, and the contractions don't interfere. This is "parallel
+ * outermost" reduction. Here, the two sides of an application are
+ * handed to separate scoped threads, down to a fixed depth of
+ * splitting, and the results are joined in left-to-right order, so
+ * the outcome doesn't depend on scheduling.
+ *
+ * Thread spawning is far more expensive than a contraction, so this
+ * only pays off for terms with large, independent, arithmetic-heavy
+ * subterms. Keep `depth` small.
+ */
+
+
+/**
+ * Contract every outermost redex, returning how many were contracted.
+ *
+ * `depth` bounds how many levels of applications are split across
+ * threads. With `depth` zero, everything runs on the calling thread.
+ *
+ * If a contraction fails, the first error in left-to-right order is
+ * returned, but disjoint redexes elsewhere may already have been
+ * contracted.
+ */
+pub fn step_outermost<T>(expr: &mut Expr<T>, depth: usize) -> Result<usize, ReduceError<T>>
+where T: Types + Clone,
+      T::Sym: Send + Fresh,
+      T::Val: Send,
+      <T::Val as SigmaRules>::Error: Send
+{
+    if expr.redex().is_some() {
+        expr.reduce_at(&[])?;
+        return Ok(1);
+    }
+
+    match expr {
+        Expr::Lambda(_, b) => step_outermost(b, depth).map_err(|e| e.under(&[Dir::Body])),
+        Expr::App(f, x) if depth > 0 => std::thread::scope(|scope| {
+            let func = scope.spawn(|| step_outermost(f, depth - 1));
+            let arg = step_outermost(x, depth - 1);
+            let func = func.join().expect("reduction thread panicked");
+            Ok(func.map_err(|e| e.under(&[Dir::Func]))?
+               + arg.map_err(|e| e.under(&[Dir::Arg]))?)
+        }),
+        Expr::App(f, x) => Ok(
+            step_outermost(f, 0).map_err(|e| e.under(&[Dir::Func]))?
+            + step_outermost(x, 0).map_err(|e| e.under(&[Dir::Arg]))?
+        ),
+        _ => Ok(0)
+    }
+}
+
+
+/**
+ * Apply `step_outermost` until no redexes remain, or `fuel` parallel
+ * steps have been taken. Returns the total number of contractions.
+ */
+pub fn normalize<T>(
+    expr: &mut Expr<T>,
+    depth: usize,
+    fuel: usize
+) -> Result<usize, ReduceError<T>>
+where T: Types + Clone,
+      T::Sym: Send + Fresh,
+      T::Val: Send,
+      <T::Val as SigmaRules>::Error: Send
+{
+    let mut total = 0;
+    for _ in 0..fuel {
+        match step_outermost(expr, depth)? {
+            0 => break,
+            n => total += n
+        }
+    }
+    Ok(total)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stepper::{Stepper, NormalOrder};
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct ParTypes;
+
+    #[derive(Clone, Debug, PartialEq)]
+    enum Val {
+        Int(i64),
+        Add,
+        AddTo(i64)
+    }
+
+    impl SigmaRules for Val {
+        type Error = ();
+
+        fn apply(f: Self, x: Self) -> Result<Self, ()> {
+            match (f, x) {
+                (Val::Add,      Val::Int(x)) => Ok(Val::AddTo(x)),
+                (Val::AddTo(x), Val::Int(y)) => Ok(Val::Int(x + y)),
+                _                            => Err(())
+            }
+        }
+    }
+
+    impl Types for ParTypes {
+        type Val = Val;
+        type Sym = String;
+    }
+
+    type E = Expr<ParTypes>;
+
+    // A balanced tree of additions summing 1 .. 2^height.
+    fn sum(lo: i64, height: u32) -> Box<E> {
+        if height == 0 {
+            E::val(Val::Int(lo))
+        } else {
+            let half = 1 << (height - 1);
+            let double = E::lambda("x", E::apply(
+                E::apply(E::val(Val::Add), E::var("x")),
+                E::val(Val::Int(0))
+            ));
+            E::apply(double, E::apply(
+                E::apply(E::val(Val::Add), sum(lo, height - 1)),
+                sum(lo + half, height - 1)
+            ))
+        }
+    }
+
+    #[test]
+    fn test_agrees_with_normal_order() {
+        let mut par = sum(1, 6);
+        let contractions = normalize(&mut par, 3, 100).unwrap();
+        assert_eq!(*par, Expr::Val(Val::Int(64 * 65 / 2)));
+
+        let mut seq = Stepper::new(sum(1, 6), NormalOrder);
+        seq.run(1000).unwrap();
+        assert_eq!(seq.term(), &*par);
+        assert_eq!(seq.steps(), contractions);
+    }
+
+    #[test]
+    fn test_step_outermost() {
+        // Both sides are redexes, so both are contracted in one step.
+        let mut expr = E::apply(
+            E::apply(E::val(Val::Add), E::val(Val::Int(1))),
+            E::apply(E::lambda("x", E::var("x")), E::val(Val::Int(2)))
+        );
+        assert_eq!(step_outermost(&mut expr, 1).unwrap(), 2);
+        assert_eq!(expr, E::apply(E::val(Val::AddTo(1)), E::val(Val::Int(2))));
+        assert_eq!(step_outermost(&mut expr, 1).unwrap(), 1);
+        assert_eq!(step_outermost(&mut expr, 1).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_first_error_wins() {
+        let mut expr = E::apply(
+            E::apply(E::val(Val::Int(1)), E::val(Val::Int(1))),
+            E::apply(E::val(Val::Add), E::val(Val::Add))
+        );
+        match step_outermost(&mut expr, 1) {
+            Err(ReduceError::Delta {func, arg, path, ..}) => {
+                assert_eq!((func, arg), (Val::Int(1), Val::Int(1)));
+                assert_eq!(path, vec![Dir::Func]);
+            },
+            other => panic!("expected a delta error, got {:?}", other)
+        }
+    }
+}
@@ -174,6 +174,11 @@ mod lexer {
      * As the main purpose of the parser is to suppor the unit tests,
      * and the whole grammar is very simple, I decided to write it by
      * hand.
+     *
+     * `#` starts a comment, which runs to the end of the line. `#|`
+     * starts a block comment, which runs to the matching `|#`; block
+     * comments nest. Input that ends inside a block comment panics,
+     * like any other malformed input.
      */
     use super::Token;
     use core::marker::PhantomData;
@@ -185,6 +190,11 @@ mod lexer {
         Start,
         Symbol(String),
         NodeId(String),
+        // Just after a `#`, which may open a block comment.
+        Hash,
+        Comment,
+        // Nesting depth, and the previous character.
+        Block(usize, char),
     }
 
     enum Action<Id, Val> {
@@ -199,6 +209,7 @@ mod lexer {
     enum CharType<Id, Val> {
         Whitespace,
         Operator(Token<Id, Val>),
+        CommentStart,
         SymbolStart,
         SymbolChar
     }
@@ -254,6 +265,7 @@ mod lexer {
             ':'                     => CharType::Operator(Token::Colon),
             '_'                     => CharType::Operator(Token::Empty),
             '='                     => CharType::Operator(Token::Redirect),
+            '#'                     => CharType::CommentStart,
              _  if c.is_uppercase() => CharType::SymbolStart,
              _                      => CharType::SymbolChar
         } }
@@ -266,21 +278,42 @@ mod lexer {
             //println!("{:?} {:?} {:?}", c, self.1, Self::classify(c));
             
             // use Self::*;
+            match replace(&mut self.1, State::Start) {
+                Hash => return Next(match c {
+                    '|'  => Block(1, ' '),
+                    '\n' => Start,
+                    _    => Comment
+                }),
+                Comment => return Next(if c == '\n' {Start} else {Comment}),
+                Block(depth, prev) => return Next(match (prev, c) {
+                    ('|', '#') if depth == 1 => Start,
+                    ('|', '#') => Block(depth - 1, ' '),
+                    ('#', '|') => Block(depth + 1, ' '),
+                    _          => Block(depth, c)
+                }),
+                state => self.1 = state
+            }
+
             match (replace(&mut self.1, State::Start), Self::classify(c)) {
                 (Start,     Whitespace)    => Next(                      Start),
                 (Start,     Operator(tok)) => EmitOne(tok,               Start),
+                (Start,     CommentStart)  => Next(                       Hash),
                 (Start,     SymbolStart)   => Next(    Symbol(String::from(c))),
                 (Start,     SymbolChar)    => Next(    NodeId(String::from(c))),
 
                 (Symbol(k), Whitespace)    => EmitOne(Self::sym(k),      Start),
                 (Symbol(k), Operator(tok)) => EmitTwo(Self::sym(k), tok),
+                (Symbol(k), CommentStart)  => EmitOne(Self::sym(k),       Hash),
                 (Symbol(k), SymbolStart)   => Next(   Symbol(Self::push(k, c))),
                 (Symbol(k), SymbolChar)    => Next(   Symbol(Self::push(k, c))),
 
                 (NodeId(k), Whitespace)    => EmitOne(Self::id(k),       Start),
                 (NodeId(k), Operator(tok)) => EmitTwo(Self::id(k), tok),
+                (NodeId(k), CommentStart)  => EmitOne(Self::id(k),        Hash),
                 (NodeId(k), SymbolStart)   => Next(   NodeId(Self::push(k, c))),
                 (NodeId(k), SymbolChar)    => Next(   NodeId(Self::push(k, c))),
+
+                (Hash, _) | (Comment, _) | (Block(..), _) => unreachable!("handled above"),
             }
         }
     }
@@ -309,6 +342,9 @@ mod lexer {
                     Action::Unexpected(c)   => {panic!("unexpected input {:?}");}
                 }
             }
+            if let State::Block(..) = self.1 {
+                panic!("unterminated block comment");
+            }
             return None
         }
     }
@@ -345,4 +381,34 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn test_lexer_comments() {
+        assert_eq!(
+            lex("# a whole line -> of (comment)\nFoo # trailing\n12# tight\nBar\n"),
+            vec![
+                Token::Symbol("Foo".into()),
+                Token::NodeId(12),
+                Token::Symbol("Bar".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lexer_block_comments() {
+        assert_eq!(
+            lex("Foo #| spans\nlines -> ( |# 12 #|outer #| inner |# still |#Bar\n# not #| a block\n"),
+            vec![
+                Token::Symbol("Foo".into()),
+                Token::NodeId(12),
+                Token::Symbol("Bar".into()),
+            ]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "unterminated block comment")]
+    fn test_lexer_unterminated_block_comment() {
+        lex("Foo #| never closed |\n");
+    }
 }
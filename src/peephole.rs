@@ -0,0 +1,198 @@
+// The MIT License (MIT)
+//
+// Copyright © 2022 <Brandon Lewis>
+//
+// Permission is hereby granted, free of charge, to any person
+// obtaining a copy of this software and associated documentation
+// files (the “Software”), to deal in the Software without
+// restriction, including without limitation the rights to use, copy,
+// modify, merge, publish, distribute, sublicense, and/or sell copies
+// of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS
+// BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN
+// ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+// Fork this project to create your own MIT license that you can
+// always link to.
+use crate::trs::Types;
+use crate::expr::{Token, ParseError};
+
+
+/*
+ * Peephole optimization of postfix token streams.
+ *
+ * Pipelines which pass terms around as `Token` streams can clean them
+ * up here without building an `Expr`. The stream is rewritten in one
+ * pass, keeping only the output tokens and the offset where each
+ * operand on the stack starts. Operands are simplified before the
+ * operators that consume them, so rewrites compose bottom-up.
+ *
+ * The rewrites are:
+ *
+ * - `(λx.x) a` to `a`
+ * - `(λx.b) a` to `b`, when `x` doesn't occur in `b` and `a` is a
+ *   single `Id` or `Val`, so no work is thrown away
+ * - `λx.f x` to `f`, when `x` doesn't occur in `f` (eta)
+ *
+ * "Occurs" is checked by looking for any `Id` token with the same
+ * name, bound or not, which is conservative.
+ */
+
+
+/**
+ * Optimize a postfix stream, returning the rewritten stream.
+ *
+ * Malformed input gets the same errors `Expr::parse` would give.
+ */
+pub fn optimize<T: Types>(
+    input: impl IntoIterator<Item = Token<T>>
+) -> Result<Vec<Token<T>>, ParseError<T>> {
+    let mut out: Vec<Token<T>> = Vec::new();
+    // Where each operand on the stack starts in `out`.
+    let mut starts: Vec<usize> = Vec::new();
+
+    for token in input { match token {
+        Token::Val(_) | Token::Id(_) => {
+            starts.push(out.len());
+            out.push(token);
+        },
+        Token::Lambda => {
+            let body = starts.pop().ok_or(ParseError::Underflow)?;
+            let binder = starts.pop().ok_or(ParseError::Underflow)?;
+            let x = match &out[binder..body] {
+                [Token::Id(x)] => x.clone(),
+                _ => return Err(ParseError::NotAVar)
+            };
+
+            if is_eta_redex(&x, &out[body..]) {
+                out.truncate(out.len() - 2);
+                out.remove(binder);
+            } else {
+                out.push(Token::Lambda);
+            }
+            starts.push(binder);
+        },
+        Token::Apply => {
+            let arg = starts.pop().ok_or(ParseError::Underflow)?;
+            let func = starts.pop().ok_or(ParseError::Underflow)?;
+
+            match &out[func..] {
+                [Token::Id(x), Token::Id(y), Token::Lambda, ..] if arg == func + 3 && x == y => {
+                    out.drain(func..arg);
+                },
+                [Token::Id(x), body @ .., Token::Lambda, Token::Val(_) | Token::Id(_)]
+                    if !occurs(x, body) =>
+                {
+                    out.truncate(arg - 1);
+                    out.remove(func);
+                },
+                _ => out.push(Token::Apply)
+            }
+            starts.push(func);
+        }
+    } }
+
+    if starts.len() == 1 {
+        Ok(out)
+    } else {
+        Err(ParseError::EOF)
+    }
+}
+
+// Is `body` of the form `f x Apply`, with `x` not occurring in `f`?
+fn is_eta_redex<T: Types>(x: &T::Sym, body: &[Token<T>]) -> bool {
+    match body {
+        [f @ .., Token::Id(y), Token::Apply] => y == x && !occurs(x, f),
+        _ => false
+    }
+}
+
+fn occurs<T: Types>(x: &T::Sym, tokens: &[Token<T>]) -> bool {
+    tokens.iter().any(|t| matches!(t, Token::Id(y) if y == x))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expr::Expr;
+    use crate::trs::SigmaRules;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct PeepholeTypes;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Unit;
+
+    impl SigmaRules for Unit {
+        type Error = ();
+    }
+
+    impl Types for PeepholeTypes {
+        type Val = Unit;
+        type Sym = &'static str;
+    }
+
+    type Tok = Token<PeepholeTypes>;
+    type E = Expr<PeepholeTypes>;
+
+    // Read a stream from words, with `\` for Lambda, `@` for Apply and
+    // `()` for the unit value.
+    fn toks(s: &'static str) -> Vec<Tok> {
+        s.split_whitespace().map(|w| match w {
+            "\\" => Tok::Lambda,
+            "@"  => Tok::Apply,
+            "()" => Tok::Val(Unit),
+            w    => Tok::Id(w)
+        }).collect()
+    }
+
+    fn check(input: &'static str, expected: &'static str) {
+        assert_eq!(optimize(toks(input)).unwrap(), toks(expected), "{}", input);
+        E::parse_owned(toks(expected).into_iter()).unwrap();
+    }
+
+    #[test]
+    fn test_identity() {
+        // (\x.x) (f y)
+        check("x x \\ f y @ @", "f y @");
+        // (\x.x) ((\y.y) z): the inner redex goes first.
+        check("x x \\ y y \\ z @ @", "z");
+    }
+
+    #[test]
+    fn test_vacuous() {
+        // (\x.f) y, (\x.\z.z) ()
+        check("x f \\ y @", "f");
+        check("x z z \\ \\ () @", "z z \\");
+        // (\x.f) (g y) would discard work; (\x.x y) z uses x.
+        check("x f \\ g y @ @", "x f \\ g y @ @");
+        check("x x y @ \\ z @", "x x y @ \\ z @");
+    }
+
+    #[test]
+    fn test_eta() {
+        // \x.f x, \x.(\y.g y) x
+        check("x f x @ \\", "f");
+        check("x y g y @ \\ x @ \\", "g");
+        // \x.x x can't be contracted.
+        check("x x x @ \\", "x x x @ \\");
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(matches!(optimize(toks("x @")), Err(ParseError::Underflow)));
+        assert!(matches!(optimize(toks("() y \\")), Err(ParseError::NotAVar)));
+        assert!(matches!(optimize(toks("x y")), Err(ParseError::EOF)));
+    }
+}
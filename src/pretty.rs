@@ -0,0 +1,353 @@
+// The MIT License (MIT)
+//
+// Copyright © 2022 <Brandon Lewis>
+//
+// Permission is hereby granted, free of charge, to any person
+// obtaining a copy of this software and associated documentation
+// files (the “Software”), to deal in the Software without
+// restriction, including without limitation the rights to use, copy,
+// modify, merge, publish, distribute, sublicense, and/or sell copies
+// of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS
+// BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN
+// ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+// Fork this project to create your own MIT license that you can
+// always link to.
+use core::fmt::Display;
+use crate::trs::Types;
+use crate::expr::Expr;
+
+
+/*
+ * Width-aware pretty printing.
+ *
+ * `Display` for `Expr` puts everything on one line. `pretty` lays a
+ * term out to fit a given width instead, in the style of Wadler's "A
+ * prettier printer": the term is first turned into a `Doc` of text,
+ * optional line breaks and groups, and each group is then printed flat
+ * if the rest of it fits on the current line, and broken otherwise.
+ */
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LambdaStyle {
+    // λx.x
+    Unicode,
+    // \x.x
+    Ascii
+}
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Naming {
+    // Variables by name, as written.
+    Named,
+    // Bound variables as de Bruijn indices counting from 0, and
+    // binders omitted: `λx.λy.x` prints as `λ.λ.1`. Free variables
+    // keep their names.
+    DeBruijn
+}
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Config {
+    // Columns to fit output into, where possible. Atoms longer than
+    // this still overflow.
+    pub width: usize,
+    pub lambda: LambdaStyle,
+    pub naming: Naming,
+    // Extra columns for each level of a broken group.
+    pub indent: usize
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            width: 80,
+            lambda: LambdaStyle::Unicode,
+            naming: Naming::Named,
+            indent: 2
+        }
+    }
+}
+
+
+/**
+ * A document to be laid out.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub enum Doc {
+    Text(String),
+    // A line break, or this text when its group is flat.
+    Break(&'static str),
+    Nest(usize, Box<Doc>),
+    Concat(Vec<Doc>),
+    // Printed flat if it fits, otherwise with its breaks broken.
+    Group(Box<Doc>)
+}
+
+impl Doc {
+    /**
+     * Lay the document out in `width` columns.
+     */
+    pub fn render(&self, width: usize) -> String {
+        let mut out = String::new();
+        let mut col = 0;
+        // (indent, flat, doc), next to print on top.
+        let mut stack: Vec<(usize, bool, &Doc)> = vec![(0, false, self)];
+
+        while let Some((indent, flat, doc)) = stack.pop() { match doc {
+            Doc::Text(s) => {
+                out.push_str(s);
+                col += s.chars().count();
+            },
+            Doc::Break(s) if flat => {
+                out.push_str(s);
+                col += s.chars().count();
+            },
+            Doc::Break(_) => {
+                out.push('\n');
+                out.extend(core::iter::repeat_n(' ', indent));
+                col = indent;
+            },
+            Doc::Nest(n, d) => stack.push((indent + n, flat, d)),
+            Doc::Concat(ds) => stack.extend(ds.iter().rev().map(|d| (indent, flat, d))),
+            Doc::Group(d) => {
+                let flat = flat || fits(width.saturating_sub(col), d, &stack);
+                stack.push((indent, flat, d));
+            }
+        } }
+
+        out
+    }
+}
+
+// Does `doc`, printed flat, and whatever follows it up to the next
+// line break, fit in `room` columns?
+fn fits(room: usize, doc: &Doc, rest: &[(usize, bool, &Doc)]) -> bool {
+    let mut room = room;
+    let mut stack: Vec<(bool, &Doc)> = vec![(true, doc)];
+    let mut rest = rest.iter().rev();
+
+    loop {
+        let (flat, doc) = match stack.pop() {
+            Some(next) => next,
+            None => match rest.next() {
+                Some((_, flat, doc)) => (*flat, *doc),
+                None => return true
+            }
+        };
+        let text = match doc {
+            Doc::Text(s) => s.as_str(),
+            Doc::Break(s) if flat => s,
+            Doc::Break(_) => return true,
+            Doc::Nest(_, d) | Doc::Group(d) => {
+                stack.push((flat, d));
+                continue;
+            },
+            Doc::Concat(ds) => {
+                stack.extend(ds.iter().rev().map(|d| (flat, d)));
+                continue;
+            }
+        };
+        match room.checked_sub(text.chars().count()) {
+            Some(left) => room = left,
+            None       => return false
+        }
+    }
+}
+
+
+/**
+ * Pretty-print `expr` according to `config`.
+ */
+pub fn pretty<T>(expr: &Expr<T>, config: &Config) -> String
+where T: Types, T::Sym: Display, T::Val: Display {
+    doc(expr, config).render(config.width)
+}
+
+/**
+ * Build the document `pretty` lays out.
+ *
+ * Lambdas break after their run of binders (`λf.λx.`), putting the
+ * body on the next line, indented. Applications break between the
+ * function and each argument, with the arguments indented.
+ */
+pub fn doc<T>(expr: &Expr<T>, config: &Config) -> Doc
+where T: Types, T::Sym: Display, T::Val: Display {
+    to_doc(expr, config, &mut Vec::new(), false)
+}
+
+// `scope` holds the enclosing binders, innermost last. `arg` is true
+// when `expr` is an argument, so an application must be parenthesized.
+fn to_doc<'a, T>(expr: &'a Expr<T>, config: &Config, scope: &mut Vec<&'a T::Sym>, arg: bool) -> Doc
+where T: Types, T::Sym: Display, T::Val: Display {
+    match expr {
+        Expr::Var(v) => Doc::Text(match config.naming {
+            Naming::DeBruijn => match scope.iter().rev().position(|b| *b == v) {
+                Some(index) => index.to_string(),
+                None        => v.to_string()
+            },
+            Naming::Named => v.to_string()
+        }),
+        Expr::Val(v) => Doc::Text(v.to_string()),
+        Expr::Lambda(..) => {
+            // Collect the binders of λx.λy.… together.
+            let mut head = String::new();
+            let mut body = expr;
+            let depth = scope.len();
+            while let Expr::Lambda(a, b) = body {
+                head.push_str(match config.lambda {
+                    LambdaStyle::Unicode => "λ",
+                    LambdaStyle::Ascii   => "\\"
+                });
+                if config.naming == Naming::Named {
+                    head.push_str(&a.to_string());
+                }
+                head.push('.');
+                scope.push(a);
+                body = b;
+            }
+            let body = to_doc(body, config, scope, false);
+            scope.truncate(depth);
+
+            let lambda = Doc::Group(Box::new(Doc::Concat(vec![
+                Doc::Text(head),
+                Doc::Nest(config.indent, Box::new(Doc::Concat(vec![Doc::Break(""), body])))
+            ])));
+            // Runs of binders are collected above, so a lambda is
+            // either at the top or part of an application. In the
+            // latter case it is parenthesized, like `Display` does.
+            if arg { parens(lambda) } else { lambda }
+        },
+        Expr::App(..) => {
+            let mut spine = Vec::new();
+            let mut func = expr;
+            while let Expr::App(f, x) = func {
+                spine.push(x);
+                func = f;
+            }
+
+            let mut args = Vec::new();
+            for x in spine.into_iter().rev() {
+                args.push(Doc::Break(" "));
+                args.push(to_doc(x, config, scope, true));
+            }
+            let app = Doc::Group(Box::new(Doc::Concat(vec![
+                to_doc(func, config, scope, true),
+                Doc::Nest(config.indent, Box::new(Doc::Concat(args)))
+            ])));
+            if arg { parens(app) } else { app }
+        }
+    }
+}
+
+fn parens(doc: Doc) -> Doc {
+    Doc::Concat(vec![Doc::Text("(".into()), doc, Doc::Text(")".into())])
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trs::SigmaRules;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct PrettyTypes;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Num(i32);
+
+    impl SigmaRules for Num {
+        type Error = ();
+    }
+
+    impl Display for Num {
+        fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            write!(f, "{}", self.0)
+        }
+    }
+
+    impl Types for PrettyTypes {
+        type Val = Num;
+        type Sym = &'static str;
+    }
+
+    type E = Expr<PrettyTypes>;
+
+    // λf.λx.f (f x)
+    fn twice() -> Box<E> {
+        E::lambda("f", E::lambda("x", E::apply(
+            E::var("f"),
+            E::apply(E::var("f"), E::var("x"))
+        )))
+    }
+
+    #[test]
+    fn test_flat_matches_display() {
+        let term = E::apply(E::apply(twice(), E::var("g")), E::val(Num(0)));
+        assert_eq!(pretty(&term, &Config::default()), term.to_string());
+        assert_eq!(pretty(&twice(), &Config::default()), "λf.λx.f (f x)");
+    }
+
+    #[test]
+    fn test_wrapping() {
+        let config = Config {width: 10, ..Config::default()};
+        assert_eq!(pretty(&twice(), &config), "λf.λx.\n  f (f x)");
+
+        let long = E::apply(
+            E::apply(E::var("function"), E::var("argument")),
+            E::apply(E::var("another"), E::var("one"))
+        );
+        let config = Config {width: 20, indent: 4, ..Config::default()};
+        assert_eq!(
+            pretty(&long, &config),
+            "function\n    argument\n    (another one)"
+        );
+    }
+
+    #[test]
+    fn test_widths_snapshot() {
+        // twice (\n.plus n n) (twice (\n.times n n) 3)
+        let square = |op| E::lambda("n", E::apply(E::apply(E::var(op), E::var("n")), E::var("n")));
+        let term = E::apply(
+            E::apply(twice(), square("plus")),
+            E::apply(E::apply(twice(), square("times")), E::val(Num(3)))
+        );
+
+        let mut out = String::new();
+        for width in [80, 40, 20, 10] {
+            let config = Config {width, ..Config::default()};
+            out.push_str(&format!("width {}:\n{}\n\n", width, pretty(&term, &config)));
+        }
+        crate::snapshot::Snapshots::in_package().assert("pretty_widths", &out);
+    }
+
+    #[test]
+    fn test_ascii_and_de_bruijn() {
+        let config = Config {
+            lambda: LambdaStyle::Ascii,
+            naming: Naming::DeBruijn,
+            ..Config::default()
+        };
+        assert_eq!(pretty(&twice(), &config), "\\.\\.1 (1 0)");
+
+        // Shadowing, and a free variable.
+        let term = E::lambda("x", E::apply(
+            E::lambda("x", E::var("x")),
+            E::apply(E::var("x"), E::var("y"))
+        ));
+        assert_eq!(pretty(&term, &config), "\\.(\\.0) (0 y)");
+    }
+}
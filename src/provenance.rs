@@ -0,0 +1,291 @@
+// The MIT License (MIT)
+//
+// Copyright © 2022 <Brandon Lewis>
+//
+// Permission is hereby granted, free of charge, to any person
+// obtaining a copy of this software and associated documentation
+// files (the “Software”), to deal in the Software without
+// restriction, including without limitation the rights to use, copy,
+// modify, merge, publish, distribute, sublicense, and/or sell copies
+// of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS
+// BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN
+// ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+// Fork this project to create your own MIT license that you can
+// always link to.
+use core::fmt;
+use core::marker::PhantomData;
+use std::collections::BTreeSet;
+use crate::trs::{Types, SigmaRules};
+use crate::expr::{Expr, Dir, Path};
+
+
+/*
+ * Labels and provenance tracking through reduction.
+ *
+ * `label` attaches a user-chosen label to every value in a term. The
+ * result is an ordinary `Expr`, which any evaluator can reduce: beta
+ * steps copy values along with their labels, and a sigma step labels
+ * its result with the join of the labels of the function and the
+ * argument. A taint bit joined with `||` gives explicit information
+ * flow; a set of source paths joined by union gives provenance.
+ *
+ * `trace` is the provenance instance: every value is labelled with
+ * its own path, and `origins` then answers "where did this value in
+ * the normal form come from?" with paths into the original term.
+ *
+ * Only values are labelled. Lambdas and variables are rebuilt by
+ * substitution and carry no identity of their own, so flows through
+ * control (which branch a Church boolean selects) are not seen.
+ */
+
+
+/**
+ * A label that can be combined when values are combined.
+ *
+ * `join` should be associative and commutative, as the order
+ * in which a result's inputs are joined depends on how the
+ * primitive is curried.
+ */
+pub trait Label: Clone + fmt::Debug {
+    fn join(self, other: Self) -> Self;
+}
+
+impl Label for () {
+    fn join(self, _: Self) -> Self {}
+}
+
+// Tainted if either input is.
+impl Label for bool {
+    fn join(self, other: Self) -> Self {
+        self || other
+    }
+}
+
+impl<X: Ord + Clone + fmt::Debug> Label for BTreeSet<X> {
+    fn join(mut self, mut other: Self) -> Self {
+        self.append(&mut other);
+        self
+    }
+}
+
+
+/**
+ * A value with a label.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Labeled<V, L> {
+    pub val: V,
+    pub label: L
+}
+
+impl<V: SigmaRules, L: Label> SigmaRules for Labeled<V, L> {
+    type Error = V::Error;
+
+    fn apply(f: Self, x: Self) -> Result<Self, Self::Error> {
+        let label = f.label.join(x.label);
+        V::apply(f.val, x.val).map(|val| Labeled {val, label})
+    }
+
+    fn select(&self) -> Option<bool> {
+        self.val.select()
+    }
+}
+
+impl<V: fmt::Display, L> fmt::Display for Labeled<V, L> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.val.fmt(f)
+    }
+}
+
+
+/**
+ * `T`, with values labelled by `L`.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Labels<T, L>(PhantomData<(T, L)>);
+
+impl<T: Types, L: Label> Types for Labels<T, L> {
+    type Val = Labeled<T::Val, L>;
+    type Sym = T::Sym;
+}
+
+
+/* A value with the paths of the source values it was computed from. */
+pub type Traced<V> = Labeled<V, BTreeSet<Path>>;
+
+/* `T`, with values wrapped in `Traced`. */
+pub type Provenance<T> = Labels<T, BTreeSet<Path>>;
+
+
+/* Label every value in `expr` with `f` of its path and itself. */
+pub fn label<T, L, F>(expr: &Expr<T>, mut f: F) -> Expr<Labels<T, L>>
+where T: Types, L: Label, F: FnMut(&[Dir], &T::Val) -> L {
+    label_rec(expr, &mut Vec::new(), &mut f)
+}
+
+fn label_rec<T, L, F>(expr: &Expr<T>, here: &mut Path, f: &mut F) -> Expr<Labels<T, L>>
+where T: Types, L: Label, F: FnMut(&[Dir], &T::Val) -> L {
+    match expr {
+        Expr::Val(v) => Expr::Val(Labeled {val: v.clone(), label: f(here, v)}),
+        Expr::Var(v) => Expr::Var(v.clone()),
+        Expr::Lambda(a, b) => {
+            here.push(Dir::Body);
+            let body = label_rec(b, here, f);
+            here.pop();
+            Expr::Lambda(a.clone(), Box::new(body))
+        },
+        Expr::App(g, x) => {
+            here.push(Dir::Func);
+            let func = label_rec(g, here, f);
+            here.pop();
+            here.push(Dir::Arg);
+            let arg = label_rec(x, here, f);
+            here.pop();
+            Expr::App(Box::new(func), Box::new(arg))
+        }
+    }
+}
+
+
+/* Drop the labels, recovering a plain term. */
+pub fn erase<T: Types, L: Label>(expr: &Expr<Labels<T, L>>) -> Expr<T> {
+    match expr {
+        Expr::Val(v)       => Expr::Val(v.val.clone()),
+        Expr::Var(v)       => Expr::Var(v.clone()),
+        Expr::Lambda(a, b) => Expr::Lambda(a.clone(), Box::new(erase(b))),
+        Expr::App(f, x)    => Expr::App(Box::new(erase(f)), Box::new(erase(x)))
+    }
+}
+
+
+/* The label of the value at `path`, if there is a value there. */
+pub fn label_at<'a, T, L>(expr: &'a Expr<Labels<T, L>>, path: &[Dir]) -> Option<&'a L>
+where T: Types + Clone, L: Label {
+    match expr.at(path)? {
+        Expr::Val(v) => Some(&v.label),
+        _            => None
+    }
+}
+
+
+/* Tag every value in `expr` with its own path. */
+pub fn trace<T: Types>(expr: &Expr<T>) -> Expr<Provenance<T>> {
+    label(expr, |path, _| core::iter::once(path.to_vec()).collect())
+}
+
+
+/**
+ * The source paths of the value at `path`, if there is a value there.
+ */
+pub fn origins<'a, T: Types + Clone>(
+    expr: &'a Expr<Provenance<T>>,
+    path: &[Dir]
+) -> Option<&'a BTreeSet<Path>> {
+    label_at(expr, path)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::num::{Num, Op, Overflow};
+    use crate::stepper::{Stepper, NormalOrder, Stop};
+    use Dir::*;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct NumTypes;
+
+    impl Types for NumTypes {
+        type Val = Num;
+        type Sym = String;
+    }
+
+    type E = Expr<NumTypes>;
+
+    fn normalize(expr: &E) -> Expr<Provenance<NumTypes>> {
+        let mut stepper = Stepper::new(Box::new(trace(expr)), NormalOrder);
+        assert_eq!(stepper.run(100).unwrap(), Stop::NormalForm);
+        *stepper.into_term()
+    }
+
+    #[test]
+    fn test_delta() {
+        // (\x.\y.(+) x 1) 10 20: the 20 is discarded.
+        let add = E::val(Num::prim(Op::Add, Overflow::Checked));
+        let term = E::apply(
+            E::apply(
+                E::lambda("x", E::lambda("y", E::apply(
+                    E::apply(add, E::var("x")),
+                    E::val(1)
+                ))),
+                E::val(10)
+            ),
+            E::val(20)
+        );
+        let normal = normalize(&term);
+        assert_eq!(erase(&normal), Expr::Val(Num::Int(11)));
+
+        let expected: BTreeSet<Path> = vec![
+            vec![Func, Arg],
+            vec![Func, Func, Body, Body, Arg],
+            vec![Func, Func, Body, Body, Func, Func]
+        ].into_iter().collect();
+        assert_eq!(origins(&normal, &[]), Some(&expected));
+        for path in &expected {
+            assert!(matches!(term.at(path), Some(Expr::Val(_))));
+        }
+    }
+
+    #[test]
+    fn test_copies() {
+        // (\x.f x x) 0: both copies come from the one 0.
+        let term = E::apply(
+            E::lambda("x", E::apply(E::apply(E::var("f"), E::var("x")), E::var("x"))),
+            E::val(0)
+        );
+        let normal = normalize(&term);
+        assert_eq!(normal.to_string(), "f 0 0");
+
+        let arg: BTreeSet<Path> = core::iter::once(vec![Arg]).collect();
+        assert_eq!(origins(&normal, &[Func, Arg]), Some(&arg));
+        assert_eq!(origins(&normal, &[Arg]), Some(&arg));
+        assert_eq!(origins(&normal, &[Func]), None);
+    }
+
+    #[test]
+    fn test_taint() {
+        // (\secret.f ((+) secret 1) ((*) 2 3)) 41: only the sum
+        // depends on the tainted input.
+        let prim = |op| E::val(Num::prim(op, Overflow::Checked));
+        let term = E::apply(
+            E::lambda("secret", E::apply(
+                E::apply(
+                    E::var("f"),
+                    E::apply(E::apply(prim(Op::Add), E::var("secret")), E::val(1))
+                ),
+                E::apply(E::apply(prim(Op::Mul), E::val(2)), E::val(3))
+            )),
+            E::val(41)
+        );
+        let tainted = label(&term, |path, _| path == [Arg]);
+
+        let mut stepper = Stepper::new(Box::new(tainted), NormalOrder);
+        assert_eq!(stepper.run(100).unwrap(), Stop::NormalForm);
+        let normal = stepper.into_term();
+        assert_eq!(normal.to_string(), "f 42 6");
+        assert_eq!(label_at(&normal, &[Func, Arg]), Some(&true));
+        assert_eq!(label_at(&normal, &[Arg]), Some(&false));
+    }
+}
@@ -0,0 +1,253 @@
+// The MIT License (MIT)
+//
+// Copyright © 2022 <Brandon Lewis>
+//
+// Permission is hereby granted, free of charge, to any person
+// obtaining a copy of this software and associated documentation
+// files (the “Software”), to deal in the Software without
+// restriction, including without limitation the rights to use, copy,
+// modify, merge, publish, distribute, sublicense, and/or sell copies
+// of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS
+// BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN
+// ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+// Fork this project to create your own MIT license that you can
+// always link to.
+use core::fmt::{Display, Write};
+use crate::trs::Types;
+use crate::expr::Expr;
+
+
+/*
+ * Export to proof assistants.
+ *
+ * Terms are printed as values of a small inductive type, deeply
+ * embedded with de Bruijn indices, so that alpha-equivalent terms
+ * export identically:
+ *
+ *   var n      the variable bound by the n-th enclosing lambda
+ *   free s     a free variable, by name
+ *   lam b      a lambda with body b
+ *   app f x    an application
+ *   const s    a primitive value, by its printed form
+ *
+ * `preamble` declares the type. A reduction sequence exports as one
+ * definition per term plus a list of them, for statements about the
+ * steps to be proved on the proof assistant's side.
+ */
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Prover {
+    Coq,
+    // Lean 4.
+    Lean
+}
+
+
+/* The declaration of the term type, to go before any definitions. */
+pub fn preamble(prover: Prover) -> &'static str {
+    match prover {
+        Prover::Coq => concat!(
+            "Require Import Coq.Strings.String.\n",
+            "Open Scope string_scope.\n",
+            "\n",
+            "Inductive term : Type :=\n",
+            "  | var : nat -> term\n",
+            "  | free : string -> term\n",
+            "  | lam : term -> term\n",
+            "  | app : term -> term -> term\n",
+            "  | const : string -> term.\n"
+        ),
+        Prover::Lean => concat!(
+            "inductive Term where\n",
+            "  | var : Nat → Term\n",
+            "  | free : String → Term\n",
+            "  | lam : Term → Term\n",
+            "  | app : Term → Term → Term\n",
+            "  | const : String → Term\n",
+            "  deriving Repr, DecidableEq\n"
+        )
+    }
+}
+
+
+/* `expr` as a value of the term type. */
+pub fn term<T>(expr: &Expr<T>, prover: Prover) -> String
+where T: Types, T::Sym: Display, T::Val: Display {
+    let mut ret = String::new();
+    term_rec(expr, prover, &mut Vec::new(), &mut ret);
+    ret
+}
+
+fn term_rec<T>(expr: &Expr<T>, prover: Prover, scope: &mut Vec<T::Sym>, out: &mut String)
+where T: Types, T::Sym: Display, T::Val: Display {
+    let con = |name| match prover {
+        Prover::Coq  => String::from(name),
+        Prover::Lean => format!("Term.{}", name)
+    };
+    match expr {
+        Expr::Var(v) => match scope.iter().rev().position(|b| b == v) {
+            Some(i) => write!(out, "{} {}", con("var"), i).unwrap(),
+            None    => write!(out, "{} {}", con("free"), string(&v.to_string(), prover)).unwrap()
+        },
+        Expr::Val(v) => write!(out, "{} {}", con("const"), string(&v.to_string(), prover)).unwrap(),
+        Expr::Lambda(a, b) => {
+            write!(out, "{} (", con("lam")).unwrap();
+            scope.push(a.clone());
+            term_rec(b, prover, scope, out);
+            scope.pop();
+            out.push(')');
+        },
+        Expr::App(f, x) => {
+            write!(out, "{} (", con("app")).unwrap();
+            term_rec(f, prover, scope, out);
+            out.push_str(") (");
+            term_rec(x, prover, scope, out);
+            out.push(')');
+        }
+    }
+}
+
+
+/* A string literal. Coq doubles quotes; Lean escapes them. */
+fn string(s: &str, prover: Prover) -> String {
+    let mut ret = String::from("\"");
+    for c in s.chars() {
+        match (prover, c) {
+            (Prover::Coq, '"')   => ret.push_str("\"\""),
+            (Prover::Lean, '"')  => ret.push_str("\\\""),
+            (Prover::Lean, '\\') => ret.push_str("\\\\"),
+            (Prover::Lean, '\n') => ret.push_str("\\n"),
+            (_, c)               => ret.push(c)
+        }
+    }
+    ret.push('"');
+    ret
+}
+
+
+/* Define `name` as `expr`. `name` must be a valid identifier. */
+pub fn definition<T>(name: &str, expr: &Expr<T>, prover: Prover) -> String
+where T: Types, T::Sym: Display, T::Val: Display {
+    match prover {
+        Prover::Coq  => format!("Definition {} : term := {}.\n", name, term(expr, prover)),
+        Prover::Lean => format!("def {} : Term := {}\n", name, term(expr, prover))
+    }
+}
+
+
+/**
+ * Define `name_0` to `name_n` as the terms of a reduction sequence,
+ * and `name` as the list of them, first to last.
+ */
+pub fn derivation<T>(name: &str, steps: &[Expr<T>], prover: Prover) -> String
+where T: Types, T::Sym: Display, T::Val: Display {
+    let mut ret = String::new();
+    let names: Vec<String> = (0..steps.len()).map(|i| format!("{}_{}", name, i)).collect();
+    for (n, expr) in names.iter().zip(steps) {
+        ret.push_str(&definition(n, expr, prover));
+    }
+    match prover {
+        Prover::Coq => {
+            let mut list = names.join(" :: ");
+            if !list.is_empty() {
+                list.push_str(" :: ");
+            }
+            writeln!(ret, "Definition {} : list term := {}nil.", name, list).unwrap();
+        },
+        Prover::Lean => {
+            writeln!(ret, "def {} : List Term := [{}]", name, names.join(", ")).unwrap();
+        }
+    }
+    ret
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trs::SigmaRules;
+    use crate::stepper::{Stepper, NormalOrder};
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct ExportTypes;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Lit(&'static str);
+
+    impl SigmaRules for Lit {
+        type Error = ();
+    }
+
+    impl Display for Lit {
+        fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            write!(f, "{}", self.0)
+        }
+    }
+
+    impl Types for ExportTypes {
+        type Val = Lit;
+        type Sym = String;
+    }
+
+    type E = Expr<ExportTypes>;
+
+    // (\x.\y.x) z "a"
+    fn example() -> Box<E> {
+        E::apply(
+            E::apply(E::lambda("x", E::lambda("y", E::var("x"))), E::var("z")),
+            E::val(Lit("\"a\""))
+        )
+    }
+
+    #[test]
+    fn test_term() {
+        assert_eq!(
+            term(&example(), Prover::Lean),
+            "Term.app (Term.app (Term.lam (Term.lam (Term.var 1))) (Term.free \"z\")) \
+             (Term.const \"\\\"a\\\"\")"
+        );
+        assert_eq!(
+            term(&example(), Prover::Coq),
+            "app (app (lam (lam (var 1))) (free \"z\")) (const \"\"\"a\"\"\")"
+        );
+    }
+
+    #[test]
+    fn test_derivation() {
+        let mut stepper = Stepper::new(example(), NormalOrder);
+        let mut steps = vec![stepper.term().clone()];
+        while stepper.step_forward().unwrap().is_some() {
+            steps.push(stepper.term().clone());
+        }
+        assert_eq!(steps.len(), 3);
+
+        assert_eq!(derivation("ex", &steps, Prover::Lean), concat!(
+            "def ex_0 : Term := Term.app (Term.app (Term.lam (Term.lam (Term.var 1))) ",
+            "(Term.free \"z\")) (Term.const \"\\\"a\\\"\")\n",
+            "def ex_1 : Term := Term.app (Term.lam (Term.free \"z\")) ",
+            "(Term.const \"\\\"a\\\"\")\n",
+            "def ex_2 : Term := Term.free \"z\"\n",
+            "def ex : List Term := [ex_0, ex_1, ex_2]\n"
+        ));
+        assert!(derivation("ex", &steps, Prover::Coq)
+            .ends_with("Definition ex : list term := ex_0 :: ex_1 :: ex_2 :: nil.\n"));
+        assert_eq!(
+            derivation::<ExportTypes>("none", &[], Prover::Coq),
+            "Definition none : list term := nil.\n"
+        );
+        assert!(preamble(Prover::Coq).contains("Inductive term"));
+    }
+}
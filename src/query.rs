@@ -0,0 +1,338 @@
+// The MIT License (MIT)
+//
+// Copyright © 2022 <Brandon Lewis>
+//
+// Permission is hereby granted, free of charge, to any person
+// obtaining a copy of this software and associated documentation
+// files (the “Software”), to deal in the Software without
+// restriction, including without limitation the rights to use, copy,
+// modify, merge, publish, distribute, sublicense, and/or sell copies
+// of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS
+// BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN
+// ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+// Fork this project to create your own MIT license that you can
+// always link to.
+use core::fmt::{Debug, Display};
+use crate::trs::Types;
+use crate::expr::{Expr, Reduction, ReduceError};
+use crate::stepper::{Stepper, Strategy, Step};
+use crate::names::Fresh;
+use crate::json::json_string;
+
+
+/*
+ * Queries over recorded reductions.
+ *
+ * A `Trace` keeps every state a `Stepper` passed through, alongside
+ * the steps between them, so that tools can ask questions of a run
+ * after the fact instead of re-deriving them from the raw steps:
+ * which steps were sigma steps, when a name first turned up, how many
+ * times a definition was unfolded.
+ *
+ * State 0 is the starting term, and state `i` is the term after step
+ * `i`, whose redex is in state `i - 1`.
+ *
+ * `Trace::to_json` exports a trace for front-ends which don't link
+ * this crate, e.g. to animate a reduction in a notebook.
+ */
+
+
+pub struct Trace<T: Types> {
+    states: Vec<Expr<T>>,
+    steps: Vec<Step>,
+    // Why recording stopped early, if a step failed.
+    failed: Option<ReduceError<T>>
+}
+
+
+impl<T: Types + Clone> Trace<T> {
+    /**
+     * Run `stepper` for at most `fuel` steps, recording each state.
+     *
+     * Breakpoints are ignored. If a step fails, recording stops there:
+     * the trace keeps the steps up to the failure, and `failed()`
+     * says what went wrong.
+     */
+    pub fn record<S: Strategy<T>>(
+        stepper: &mut Stepper<T, S>,
+        fuel: usize
+    ) -> Self where T::Sym: Fresh {
+        let mut trace = Trace {
+            states: vec![stepper.term().clone()],
+            steps: Vec::new(),
+            failed: None
+        };
+        for _ in 0..fuel {
+            match stepper.step_forward() {
+                Ok(Some(step)) => {
+                    trace.states.push(stepper.term().clone());
+                    trace.steps.push(step);
+                },
+                Ok(None) => break,
+                Err(error) => {
+                    trace.failed = Some(error);
+                    break;
+                }
+            }
+        }
+        trace
+    }
+
+    /* The error that ended the recording, if a step failed. */
+    pub fn failed(&self) -> Option<&ReduceError<T>> {
+        self.failed.as_ref()
+    }
+
+    pub fn steps(&self) -> &[Step] {
+        &self.steps
+    }
+
+    /* The state after step `i`, or the starting term for 0. */
+    pub fn state(&self, i: usize) -> Option<&Expr<T>> {
+        self.states.get(i)
+    }
+
+    /* The last state recorded. */
+    pub fn last(&self) -> &Expr<T> {
+        self.states.last().expect("a trace has a starting state")
+    }
+
+    /* The redex contracted by step `i`, counting from 1. */
+    pub fn redex(&self, i: usize) -> Option<&Expr<T>> {
+        let step = self.steps.get(i.checked_sub(1)?)?;
+        self.states[i - 1].at(&step.path)
+    }
+
+    /* The steps of kind `reduction`. */
+    pub fn filter(&self, reduction: Reduction) -> impl Iterator<Item = &Step> {
+        self.steps.iter().filter(move |s| s.reduction == reduction)
+    }
+
+    /* The index of the first state satisfying `pred`. */
+    pub fn find<P>(&self, pred: P) -> Option<usize>
+    where P: FnMut(&Expr<T>) -> bool {
+        self.states.iter().position(pred)
+    }
+
+    /* The index of the first state with a variable named `sym`,
+     * whether free or bound. */
+    pub fn first_with(&self, sym: &T::Sym) -> Option<usize> {
+        self.find(|e| mentions(e, sym))
+    }
+}
+
+
+impl<T: Types + Clone> Trace<T> where T::Val: PartialEq {
+    /**
+     * The number of beta steps which applied `def`, up to renaming.
+     *
+     * A definition bound by `let` or held in a `CodeStore` is copied
+     * into place by substitution and loses its name, so it is
+     * recognised by its body instead.
+     */
+    pub fn unfoldings(&self, def: &Expr<T>) -> usize {
+        (1..=self.steps.len())
+            .filter(|&i| self.steps[i - 1].reduction == Reduction::Beta)
+            .filter(|&i| match self.redex(i) {
+                Some(Expr::App(f, _)) => f.alpha_eq(def),
+                _                     => false
+            })
+            .count()
+    }
+}
+
+
+impl<T: Types + Clone> Trace<T>
+where T::Sym: Display, T::Val: Display, ReduceError<T>: Debug {
+    /**
+     * The trace as JSON, in this schema:
+     *
+     *   {
+     *     "states": ["(λx.x) 0", "0"],
+     *     "steps": [
+     *       {"number": 1, "rule": "beta", "path": [], "explanation": null}
+     *     ],
+     *     "failed": null
+     *   }
+     *
+     * `states` are the terms as `Display` prints them, starting term
+     * first. Each step has its number, counting from 1, its `rule`,
+     * `"beta"`, `"sigma"` or `"select"`, and the `path` to its redex
+     * in the state before it, from the root, as `"Body"`, `"Func"` and
+     * `"Arg"`. `explanation` is the English explanation, when the
+     * stepper made one. `failed` is `null`, or the `Debug` form of the error which
+     * stopped recording. Output is compact, with no whitespace.
+     */
+    pub fn to_json(&self) -> String {
+        let states: Vec<String> = self.states.iter()
+            .map(|state| json_string(&state.to_string()))
+            .collect();
+        let steps: Vec<String> = self.steps.iter().map(|step| {
+            let path: Vec<String> = step.path.iter()
+                .map(|dir| json_string(&format!("{:?}", dir)))
+                .collect();
+            format!(
+                "{{\"number\":{},\"rule\":{},\"path\":[{}],\"explanation\":{}}}",
+                step.number,
+                json_string(match step.reduction {
+                    Reduction::Beta  => "beta",
+                    Reduction::Sigma => "sigma",
+                    Reduction::Select => "select"
+                }),
+                path.join(","),
+                step.explanation.as_ref()
+                    .map_or("null".to_string(), |e| json_string(&e.english))
+            )
+        }).collect();
+        format!(
+            "{{\"states\":[{}],\"steps\":[{}],\"failed\":{}}}",
+            states.join(","),
+            steps.join(","),
+            self.failed.as_ref()
+                .map_or("null".to_string(), |e| json_string(&format!("{:?}", e)))
+        )
+    }
+}
+
+
+fn mentions<T: Types>(expr: &Expr<T>, sym: &T::Sym) -> bool {
+    match expr {
+        Expr::Var(v)       => v == sym,
+        Expr::Val(_)       => false,
+        Expr::Lambda(a, b) => a == sym || mentions(b, sym),
+        Expr::App(f, x)    => mentions(f, sym) || mentions(x, sym)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trs::SigmaRules;
+    use crate::stepper::NormalOrder;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct QueryTypes;
+
+    #[derive(Clone, Debug, PartialEq)]
+    enum Val {
+        Int(i32),
+        Succ
+    }
+
+    impl SigmaRules for Val {
+        type Error = ();
+
+        fn apply(f: Self, x: Self) -> Result<Self, ()> {
+            match (f, x) {
+                (Val::Succ, Val::Int(i)) => Ok(Val::Int(i + 1)),
+                _                        => Err(())
+            }
+        }
+    }
+
+    impl core::fmt::Display for Val {
+        fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            match self {
+                Val::Int(i) => write!(f, "{}", i),
+                Val::Succ   => write!(f, "Succ")
+            }
+        }
+    }
+
+    impl Types for QueryTypes {
+        type Val = Val;
+        type Sym = String;
+    }
+
+    type E = Expr<QueryTypes>;
+
+    // \y.Succ y
+    fn inc() -> Box<E> {
+        E::lambda("y", E::apply(E::val(Val::Succ), E::var("y")))
+    }
+
+    // (\f.f (f 0)) inc
+    fn example() -> Box<E> {
+        E::apply(
+            E::lambda("f", E::apply(E::var("f"), E::apply(E::var("f"), E::val(Val::Int(0))))),
+            inc()
+        )
+    }
+
+    #[test]
+    fn test_record() {
+        let mut stepper = Stepper::new(example(), NormalOrder);
+        let trace = Trace::record(&mut stepper, 100);
+
+        assert_eq!(trace.steps().len(), 5);
+        assert_eq!(trace.last(), &Expr::Val(Val::Int(2)));
+        assert_eq!(trace.state(0), Some(&*example()));
+        assert_eq!(trace.redex(1), Some(&*example()));
+        assert_eq!(trace.redex(0), None);
+        assert_eq!(trace.redex(6), None);
+        assert!(trace.failed().is_none());
+
+        let fewer = Trace::record(&mut Stepper::new(example(), NormalOrder), 2);
+        assert_eq!(fewer.steps().len(), 2);
+    }
+
+    #[test]
+    fn test_record_failure() {
+        // (\f.f (f 0)) 1: the beta step goes through, then 1 0 fails.
+        let term = E::apply(
+            E::lambda("f", E::apply(E::var("f"), E::apply(E::var("f"), E::val(Val::Int(0))))),
+            E::val(Val::Int(1))
+        );
+        let trace = Trace::record(&mut Stepper::new(term, NormalOrder), 100);
+        assert_eq!(trace.steps().len(), 1);
+        assert_eq!(trace.last(), &*E::apply(
+            E::val(Val::Int(1)),
+            E::apply(E::val(Val::Int(1)), E::val(Val::Int(0)))
+        ));
+        assert!(matches!(trace.failed(), Some(ReduceError::Delta {..})));
+        assert!(trace.to_json().ends_with(concat!(
+            "\"steps\":[{\"number\":1,\"rule\":\"beta\",\"path\":[],\"explanation\":null}],",
+            "\"failed\":\"Delta { func: Int(1), arg: Int(0), path: [Arg], error: () }\"}"
+        )));
+    }
+
+    #[test]
+    fn test_to_json() {
+        let mut stepper = Stepper::new(example(), NormalOrder).with_explanations();
+        let json = Trace::record(&mut stepper, 100).to_json();
+        assert!(json.starts_with("{\"states\":[\"(λf.f (f 0)) (λy.Succ y)\","));
+        crate::snapshot::Snapshots::in_package().assert("query_trace", &json);
+    }
+
+    #[test]
+    fn test_queries() {
+        let trace = Trace::record(&mut Stepper::new(example(), NormalOrder), 100);
+
+        let sigma: Vec<usize> = trace.filter(Reduction::Sigma).map(|s| s.number).collect();
+        assert_eq!(sigma, vec![4, 5]);
+        assert_eq!(trace.filter(Reduction::Beta).count(), 3);
+
+        assert_eq!(trace.first_with(&"f".into()), Some(0));
+        assert_eq!(trace.first_with(&"y".into()), Some(0));
+        assert_eq!(trace.first_with(&"z".into()), None);
+        assert_eq!(trace.find(|e| matches!(e, Expr::Val(_))), Some(5));
+
+        // inc is applied twice, under another name for its binder.
+        let renamed = E::lambda("n", E::apply(E::val(Val::Succ), E::var("n")));
+        assert_eq!(trace.unfoldings(&renamed), 2);
+        assert_eq!(trace.unfoldings(&E::lambda("y", E::var("y"))), 0);
+    }
+}
@@ -0,0 +1,207 @@
+// The MIT License (MIT)
+//
+// Copyright © 2022 <Brandon Lewis>
+//
+// Permission is hereby granted, free of charge, to any person
+// obtaining a copy of this software and associated documentation
+// files (the “Software”), to deal in the Software without
+// restriction, including without limitation the rights to use, copy,
+// modify, merge, publish, distribute, sublicense, and/or sell copies
+// of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS
+// BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN
+// ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+// Fork this project to create your own MIT license that you can
+// always link to.
+use core::fmt;
+use core::convert::TryFrom;
+use crate::trs::SigmaRules;
+use crate::num::{Op, NumError};
+
+
+/*
+ * Exact rational arithmetic, for programs where rounding error is
+ * unacceptable.
+ *
+ * Requires the `rational` feature. Numerators and denominators are
+ * 64-bit and kept in lowest terms; a result which doesn't fit is an
+ * overflow error, never rounded. The primitives are those of
+ * `num::Op`, except `Rem`, which rationals don't need.
+ */
+
+
+/**
+ * A fraction in lowest terms, with a positive denominator.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Rational {
+    num: i64,
+    den: i64
+}
+
+impl Rational {
+    /* `num / den`, or `None` if `den` is zero or the result overflows. */
+    pub fn new(num: i64, den: i64) -> Option<Self> {
+        Self::reduce(i128::from(num), i128::from(den))
+    }
+
+    pub fn integer(n: i64) -> Self {
+        Rational {num: n, den: 1}
+    }
+
+    pub fn numerator(&self) -> i64 {
+        self.num
+    }
+
+    pub fn denominator(&self) -> i64 {
+        self.den
+    }
+
+    // Bring to lowest terms and check the result fits.
+    fn reduce(num: i128, den: i128) -> Option<Self> {
+        if den == 0 {
+            return None;
+        }
+        let g = gcd(num, den);
+        let sign = if den < 0 {-1} else {1};
+        Some(Rational {
+            num: i64::try_from(sign * num / g).ok()?,
+            den: i64::try_from(sign * den / g).ok()?
+        })
+    }
+
+    pub fn eval(op: Op, a: Self, b: Self) -> Result<Exact, NumError> {
+        let (an, ad) = (i128::from(a.num), i128::from(a.den));
+        let (bn, bd) = (i128::from(b.num), i128::from(b.den));
+        // Products of two i64s always fit in an i128, and so do sums
+        // of two such products.
+        let (num, den) = match op {
+            Op::Add => (an * bd + bn * ad, ad * bd),
+            Op::Sub => (an * bd - bn * ad, ad * bd),
+            Op::Mul => (an * bn, ad * bd),
+            Op::Div if bn == 0 => return Err(NumError::DivideByZero),
+            Op::Div => (an * bd, ad * bn),
+            Op::Eq  => return Ok(Exact::Bool(a == b)),
+            Op::Lt  => return Ok(Exact::Bool(an * bd < bn * ad)),
+            Op::Rem => return Err(NumError::NotApplicable)
+        };
+        Self::reduce(num, den).map(Exact::Rat).ok_or(NumError::Overflow)
+    }
+}
+
+fn gcd(a: i128, b: i128) -> i128 {
+    let (mut a, mut b) = (a.abs(), b.abs());
+    while b != 0 {
+        let r = a % b;
+        a = b;
+        b = r;
+    }
+    // gcd(0, 0) only arises for 0/0, which `reduce` rejects first.
+    a.max(1)
+}
+
+impl fmt::Display for Rational {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.den == 1 {
+            write!(f, "{}", self.num)
+        } else {
+            write!(f, "{}/{}", self.num, self.den)
+        }
+    }
+}
+
+
+/**
+ * Rationals, booleans, and curried primitives over them, like
+ * `num::Num`.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Exact {
+    Rat(Rational),
+    Bool(bool),
+    Prim(Op),
+    Partial(Op, Rational)
+}
+
+impl From<Rational> for Exact {
+    fn from(r: Rational) -> Self {
+        Exact::Rat(r)
+    }
+}
+
+impl SigmaRules for Exact {
+    type Error = NumError;
+
+    fn apply(f: Self, x: Self) -> Result<Self, NumError> {
+        match (f, x) {
+            (Exact::Prim(op), Exact::Rat(a))       => Ok(Exact::Partial(op, a)),
+            (Exact::Partial(op, a), Exact::Rat(b)) => Rational::eval(op, a, b),
+            _                                      => Err(NumError::NotApplicable)
+        }
+    }
+}
+
+impl fmt::Display for Exact {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Exact::Rat(r)         => write!(f, "{}", r),
+            Exact::Bool(b)        => write!(f, "{}", b),
+            Exact::Prim(op)       => write!(f, "({})", op),
+            Exact::Partial(op, a) => write!(f, "({} {})", op, a)
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn r(num: i64, den: i64) -> Rational {
+        Rational::new(num, den).unwrap()
+    }
+
+    fn apply2(op: Op, a: Rational, b: Rational) -> Result<Exact, NumError> {
+        Exact::apply(Exact::apply(Exact::Prim(op), a.into())?, b.into())
+    }
+
+    #[test]
+    fn test_lowest_terms() {
+        assert_eq!(r(2, -4), r(-1, 2));
+        assert_eq!((r(6, 3).numerator(), r(6, 3).denominator()), (2, 1));
+        assert_eq!(Rational::new(1, 0), None);
+        assert_eq!(Rational::new(i64::MIN, -1), None);
+        assert_eq!(r(0, -5).to_string(), "0");
+        assert_eq!(r(3, 6).to_string(), "1/2");
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        // 1/10 + 2/10 is exactly 3/10.
+        assert_eq!(apply2(Op::Add, r(1, 10), r(2, 10)), Ok(Exact::Rat(r(3, 10))));
+        assert_eq!(apply2(Op::Sub, r(1, 2), r(1, 3)), Ok(Exact::Rat(r(1, 6))));
+        assert_eq!(apply2(Op::Div, r(1, 2), r(-1, 4)), Ok(Exact::Rat(r(-2, 1))));
+        assert_eq!(apply2(Op::Lt, r(1, 3), r(1, 2)), Ok(Exact::Bool(true)));
+        assert_eq!(apply2(Op::Div, r(1, 2), r(0, 1)), Err(NumError::DivideByZero));
+        assert_eq!(apply2(Op::Rem, r(1, 2), r(1, 3)), Err(NumError::NotApplicable));
+    }
+
+    #[test]
+    fn test_overflow() {
+        let big = Rational::integer(i64::MAX);
+        assert_eq!(apply2(Op::Mul, big, big), Err(NumError::Overflow));
+        // Large intermediates are fine if the result fits.
+        assert_eq!(apply2(Op::Mul, big, r(1, i64::MAX)), Ok(Exact::Rat(r(1, 1))));
+    }
+}
@@ -0,0 +1,269 @@
+// The MIT License (MIT)
+//
+// Copyright © 2022 <Brandon Lewis>
+//
+// Permission is hereby granted, free of charge, to any person
+// obtaining a copy of this software and associated documentation
+// files (the “Software”), to deal in the Software without
+// restriction, including without limitation the rights to use, copy,
+// modify, merge, publish, distribute, sublicense, and/or sell copies
+// of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS
+// BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN
+// ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+// Fork this project to create your own MIT license that you can
+// always link to.
+use core::fmt::Write;
+use crate::trs::{SigmaRules, Types};
+use crate::expr::Expr;
+use crate::names::free_vars;
+
+
+/*
+ * Rust code generation.
+ *
+ * `module` turns a closed term into standalone Rust source defining a
+ * function which computes it, for embedding a computed function in an
+ * application without an interpreter. Terms are untyped, so the code
+ * works over a universal `Value`: a reference-counted closure, or one
+ * of the term's values. A value applied to a value goes through the
+ * value type's `SigmaRules`, so the generated module depends on this
+ * crate and on the value type, named by a path the caller supplies.
+ * A value which selects (see `SigmaRules::select`) picks between its
+ * next two arguments.
+ *
+ * Evaluation is strict, like Rust itself, except for selection: where
+ * the head of `c t e` could be a selector, `t` and `e` are passed to
+ * `select` as thunks, and only the one chosen runs, so recursion
+ * guarded by an `if` stops. Recursion itself needs a fixpoint which
+ * is safe under strict evaluation, like the one `scheme` uses.
+ * Variables are renamed to `v0`, `v1`, ..., so any symbol type works,
+ * and binders their body doesn't use get a leading `_`.
+ */
+
+
+/**
+ * Rust source for a module defining `pub fn name() -> Value`, which
+ * evaluates `expr`.
+ *
+ * `val_type` is the path to the value type, as seen from the
+ * generated module, and `val` writes a value as a Rust expression of
+ * that type. Fails with the first free variable if `expr` isn't closed.
+ */
+pub fn module<T, F>(name: &str, expr: &Expr<T>, val_type: &str, val: F) -> Result<String, T::Sym>
+where T: Types, F: Fn(&T::Val) -> String {
+    if let Some(v) = free_vars(expr).into_iter().next() {
+        return Err(v);
+    }
+
+    let mut ret = String::new();
+    writeln!(ret, "use std::rc::Rc;").unwrap();
+    writeln!(ret, "use fgrs_toolbox::trs::SigmaRules;").unwrap();
+    writeln!(ret).unwrap();
+    writeln!(ret, "#[derive(Clone)]").unwrap();
+    writeln!(ret, "pub enum Value {{").unwrap();
+    writeln!(ret, "    Fun(Rc<dyn Fn(Value) -> Value>),").unwrap();
+    writeln!(ret, "    Val({}),", val_type).unwrap();
+    writeln!(ret, "}}").unwrap();
+    writeln!(ret).unwrap();
+    writeln!(ret, "pub fn app(f: Value, x: Value) -> Value {{").unwrap();
+    writeln!(ret, "    match (f, x) {{").unwrap();
+    writeln!(ret, "        (Value::Fun(f), x) => f(x),").unwrap();
+    writeln!(ret, "        (Value::Val(f), x) => match (f.select(), x) {{").unwrap();
+    writeln!(ret, "            (Some(true), x) => Value::Fun(Rc::new(move |_| x.clone())),").unwrap();
+    writeln!(ret, "            (Some(false), _) => Value::Fun(Rc::new(|e| e)),").unwrap();
+    writeln!(ret, "            (None, Value::Val(x)) => Value::Val(").unwrap();
+    writeln!(ret, "                <{} as SigmaRules>::apply(f, x).expect(\"primitive failed\")", val_type).unwrap();
+    writeln!(ret, "            ),").unwrap();
+    writeln!(ret, "            (None, Value::Fun(_)) => panic!(\"value applied to a function\"),").unwrap();
+    writeln!(ret, "        }},").unwrap();
+    writeln!(ret, "    }}").unwrap();
+    writeln!(ret, "}}").unwrap();
+    writeln!(ret).unwrap();
+    writeln!(ret, "pub fn select(c: Value, t: impl FnOnce() -> Value, e: impl FnOnce() -> Value) -> Value {{").unwrap();
+    writeln!(ret, "    match &c {{").unwrap();
+    writeln!(ret, "        Value::Val(v) if v.select() == Some(true) => t(),").unwrap();
+    writeln!(ret, "        Value::Val(v) if v.select() == Some(false) => e(),").unwrap();
+    writeln!(ret, "        _ => {{").unwrap();
+    writeln!(ret, "            let f = app(c, t());").unwrap();
+    writeln!(ret, "            app(f, e())").unwrap();
+    writeln!(ret, "        }}").unwrap();
+    writeln!(ret, "    }}").unwrap();
+    writeln!(ret, "}}").unwrap();
+    writeln!(ret).unwrap();
+    writeln!(ret, "pub fn {}() -> Value {{", name).unwrap();
+    ret.push_str("    ");
+    Gen {val: &val, scope: Vec::new(), next: 0}.expr(expr, &mut ret);
+    writeln!(ret).unwrap();
+    writeln!(ret, "}}").unwrap();
+    Ok(ret)
+}
+
+
+struct Gen<'a, T: Types, F> {
+    val: &'a F,
+    // Binders in scope, innermost last, with their Rust names.
+    scope: Vec<(T::Sym, usize)>,
+    next: usize
+}
+
+impl<T, F> Gen<'_, T, F> where T: Types, F: Fn(&T::Val) -> String {
+    fn lookup(&self, v: &T::Sym) -> usize {
+        self.scope.iter().rev()
+            .find(|(name, _)| name == v)
+            .map(|(_, n)| *n)
+            .expect("term checked closed")
+    }
+
+    /* Whether `f` is `c t` with a `c` which could be a selector: not
+     * a lambda, nor a value which doesn't select. */
+    fn may_select(f: &Expr<T>) -> bool {
+        match f {
+            Expr::App(c, _) => match &**c {
+                Expr::Lambda(..) => false,
+                Expr::Val(v)     => v.select().is_some(),
+                _                => true
+            },
+            _ => false
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr<T>, out: &mut String) {
+        match expr {
+            Expr::Var(v) => write!(out, "v{}.clone()", self.lookup(v)).unwrap(),
+            Expr::Val(v) => write!(out, "Value::Val({})", (self.val)(v)).unwrap(),
+            Expr::Lambda(a, b) => {
+                // The closure is `move`, so give it its own copies of
+                // the variables it captures.
+                let captured = free_vars(expr);
+                if !captured.is_empty() {
+                    out.push_str("{ ");
+                }
+                for v in &captured {
+                    let n = self.lookup(v);
+                    write!(out, "let v{} = v{}.clone(); ", n, n).unwrap();
+                }
+                let n = self.next;
+                self.next += 1;
+                // An unused binder gets a `_` so the output compiles
+                // without warnings.
+                let unused = if free_vars(b).contains(a) {""} else {"_"};
+                write!(out, "Value::Fun(Rc::new(move |{}v{}: Value| ", unused, n).unwrap();
+                self.scope.push((a.clone(), n));
+                self.expr(b, out);
+                self.scope.pop();
+                out.push_str("))");
+                if !captured.is_empty() {
+                    out.push_str(" }");
+                }
+            },
+            // Anything that might turn out to be a selector gets its
+            // two arguments as thunks, and only the chosen one runs.
+            Expr::App(f, e) if Self::may_select(f) => {
+                let (c, t) = match &**f {
+                    Expr::App(c, t) => (c, t),
+                    _               => unreachable!("checked by may_select")
+                };
+                out.push_str("select(");
+                self.expr(c, out);
+                out.push_str(", || ");
+                self.expr(t, out);
+                out.push_str(", || ");
+                self.expr(e, out);
+                out.push(')');
+            },
+            Expr::App(f, x) => {
+                out.push_str("app(");
+                self.expr(f, out);
+                out.push_str(", ");
+                self.expr(x, out);
+                out.push(')');
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trs::SigmaRules;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct RustTypes;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Int(i64);
+
+    impl SigmaRules for Int {
+        type Error = ();
+    }
+
+    impl Types for RustTypes {
+        type Val = Int;
+        type Sym = &'static str;
+    }
+
+    type E = Expr<RustTypes>;
+
+    fn int(v: &Int) -> String {
+        format!("Int({})", v.0)
+    }
+
+    #[test]
+    fn test_module() {
+        // (\x.\y.x) 1 2
+        let k = E::lambda("x", E::lambda("y", E::var("x")));
+        let term = E::apply(E::apply(k, E::val(Int(1))), E::val(Int(2)));
+        let src = module("k", &term, "crate::Int", int).unwrap();
+
+        assert!(src.contains("    Val(crate::Int),\n"));
+        assert!(src.contains("<crate::Int as SigmaRules>::apply(f, x)"));
+        assert!(src.ends_with(concat!(
+            "pub fn k() -> Value {\n",
+            "    app(app(Value::Fun(Rc::new(move |v0: Value| ",
+            "{ let v0 = v0.clone(); Value::Fun(Rc::new(move |_v1: Value| v0.clone())) })), ",
+            "Value::Val(Int(1))), Value::Val(Int(2)))\n",
+            "}\n"
+        )));
+    }
+
+    #[test]
+    fn test_shadowing() {
+        // \x.\x.x refers to the inner x.
+        let term = E::lambda("x", E::lambda("x", E::var("x")));
+        let src = module("f", &term, "Int", int).unwrap();
+        assert!(src.contains("move |v1: Value| v1.clone()"));
+    }
+
+    #[test]
+    fn test_select_thunks() {
+        // \b.b 1 2 might select, so its arguments are thunks; a
+        // lambda can't.
+        let term = E::lambda("b", E::apply(E::apply(E::var("b"), E::val(Int(1))), E::val(Int(2))));
+        let src = module("f", &term, "Int", int).unwrap();
+        assert!(src.contains("select(v0.clone(), || Value::Val(Int(1)), || Value::Val(Int(2)))"));
+
+        let k = E::lambda("x", E::lambda("y", E::var("x")));
+        let term = E::apply(E::apply(k, E::val(Int(1))), E::val(Int(2)));
+        let src = module("k", &term, "Int", int).unwrap();
+        assert!(!src.split("pub fn k()").nth(1).unwrap().contains("select("));
+    }
+
+    #[test]
+    fn test_open() {
+        let term = E::lambda("x", E::var("y"));
+        assert_eq!(module("f", &term, "Int", int), Err("y"));
+    }
+}
@@ -0,0 +1,384 @@
+// The MIT License (MIT)
+//
+// Copyright © 2022 <Brandon Lewis>
+//
+// Permission is hereby granted, free of charge, to any person
+// obtaining a copy of this software and associated documentation
+// files (the “Software”), to deal in the Software without
+// restriction, including without limitation the rights to use, copy,
+// modify, merge, publish, distribute, sublicense, and/or sell copies
+// of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS
+// BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN
+// ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+// Fork this project to create your own MIT license that you can
+// always link to.
+use crate::trs::Types;
+use crate::expr::Expr;
+use crate::num::Num;
+use crate::names::free_vars;
+use crate::text;
+
+
+/*
+ * A reader for a small Scheme subset.
+ *
+ * A program is any number of `define`s followed by one expression,
+ * which is lowered to a single `Expr`:
+ *
+ *   (define x e) ... body       (λx. ... body) e
+ *   (define (f a b) e)          f = λa.λb.e, through Z if f is recursive
+ *   (lambda (a b) e)            λa.λb.e
+ *   (let ((a e1) (b e2)) e)     (λa.λb.e) e1 e2
+ *   (if c t e)                  c t e
+ *   (f a b)                     f a b
+ *
+ * Integers, `#t`, `#f` and `#\c` characters become `Num` values, and
+ * strings become lists of characters (see `text`). `if` applies its
+ * test to the two branches, and a `Num::Bool` selects one of them, so
+ * `#t`, `#f` and the comparisons `==` and `<` can all be tests. Other
+ * symbols are left as variables; `num::Primitives::resolve` turns `+`
+ * and friends into primitives.
+ *
+ * Names starting with `%` are used for the helpers the lowering
+ * introduces, and shouldn't appear in programs.
+ */
+
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ReadError {
+    // A `)` with no `(`, or the input ends inside a list or a string.
+    Unbalanced,
+    // A special form of the wrong shape, e.g. `(if c t)`.
+    Malformed(&'static str),
+    // `(f)` or `(lambda () e)`: there are no nullary functions.
+    Nullary,
+    // There is no final expression after the definitions.
+    NoExpression,
+    // A `#` literal other than `#t`, `#f`, or a character.
+    BadLiteral(String)
+}
+
+
+/* A definition's name and lowered value. */
+type Definition<T> = (<T as Types>::Sym, Expr<T>);
+
+
+enum Sexp {
+    Atom(String),
+    Str(String),
+    List(Vec<Sexp>)
+}
+
+
+/**
+ * Read and lower a program.
+ */
+pub fn read<T>(src: &str) -> Result<Expr<T>, ReadError>
+where T: Types + Clone, T::Val: From<Num>, T::Sym: for<'s> From<&'s str> {
+    let forms = parse(src)?;
+    let (last, defs) = forms.split_last().ok_or(ReadError::NoExpression)?;
+    if definition::<T>(last).is_some() {
+        return Err(ReadError::NoExpression);
+    }
+
+    let mut body = lower(last)?;
+    for def in defs.iter().rev() {
+        let (name, value) = definition(def).ok_or(ReadError::Malformed("define"))??;
+        body = Expr::App(Box::new(Expr::Lambda(name, Box::new(body))), Box::new(value));
+    }
+    Ok(body)
+}
+
+
+fn parse(src: &str) -> Result<Vec<Sexp>, ReadError> {
+    let mut stack = vec![Vec::new()];
+    let mut chars = src.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let sexp = match c {
+            '(' => {
+                stack.push(Vec::new());
+                continue;
+            },
+            ')' => match stack.pop() {
+                Some(list) if !stack.is_empty() => Sexp::List(list),
+                _ => return Err(ReadError::Unbalanced)
+            },
+            ';' => {
+                while chars.next_if(|&c| c != '\n').is_some() {}
+                continue;
+            },
+            '"' => {
+                let mut s = String::new();
+                loop {
+                    match chars.next().ok_or(ReadError::Unbalanced)? {
+                        '"'  => break,
+                        '\\' => match chars.next().ok_or(ReadError::Unbalanced)? {
+                            'n' => s.push('\n'),
+                            c   => s.push(c)
+                        },
+                        c    => s.push(c)
+                    }
+                }
+                Sexp::Str(s)
+            },
+            c if c.is_whitespace() => continue,
+            c => {
+                let mut atom = String::from(c);
+                // The character after `#\` is taken as is, even if
+                // it is a delimiter.
+                if c == '#' && chars.next_if_eq(&'\\').is_some() {
+                    atom.push('\\');
+                    atom.extend(chars.next());
+                }
+                while let Some(c) = chars.next_if(|&c| !is_delimiter(c)) {
+                    atom.push(c);
+                }
+                Sexp::Atom(atom)
+            }
+        };
+        stack.last_mut().expect("stack is never empty").push(sexp);
+    }
+
+    match stack.pop() {
+        Some(forms) if stack.is_empty() => Ok(forms),
+        _ => Err(ReadError::Unbalanced)
+    }
+}
+
+fn is_delimiter(c: char) -> bool {
+    c.is_whitespace() || matches!(c, '(' | ')' | ';' | '"')
+}
+
+
+/* `Some` if `sexp` is a `define`, with its name and lowered value. */
+fn definition<T>(sexp: &Sexp) -> Option<Result<Definition<T>, ReadError>>
+where T: Types + Clone, T::Val: From<Num>, T::Sym: for<'s> From<&'s str> {
+    let items = match sexp {
+        Sexp::List(items) if matches!(items.first(), Some(Sexp::Atom(a)) if a == "define") => items,
+        _ => return None
+    };
+    let malformed = Err(ReadError::Malformed("define"));
+    let (name, value) = match &items[1..] {
+        [Sexp::Atom(name), value] => (name, lower(value)),
+        [Sexp::List(head), body] => match head.split_first() {
+            Some((Sexp::Atom(name), params)) => (name, lambda(params, body)),
+            _ => return Some(malformed)
+        },
+        _ => return Some(malformed)
+    };
+    Some(value.map(|value| {
+        let name = T::Sym::from(name);
+        if free_vars(&value).contains(&name) {
+            let value = Expr::Lambda(name.clone(), Box::new(value));
+            (name, Expr::App(Box::new(fix()), Box::new(value)))
+        } else {
+            (name, value)
+        }
+    }))
+}
+
+
+fn lower<T>(sexp: &Sexp) -> Result<Expr<T>, ReadError>
+where T: Types + Clone, T::Val: From<Num>, T::Sym: for<'s> From<&'s str> {
+    let items = match sexp {
+        Sexp::Atom(atom) => return atom_expr(atom),
+        Sexp::Str(s)     => return Ok(text::list(s)),
+        Sexp::List(items) => items
+    };
+
+    let keyword = match items.first() {
+        Some(Sexp::Atom(a)) => a.as_str(),
+        Some(_)             => "",
+        None                => return Err(ReadError::Nullary)
+    };
+    match keyword {
+        "lambda" => match &items[1..] {
+            [Sexp::List(params), body] => lambda(params, body),
+            _ => Err(ReadError::Malformed("lambda"))
+        },
+        "if" => match &items[1..] {
+            [c, t, e] => Ok(apply(lower(c)?, &[lower(t)?, lower(e)?])),
+            _ => Err(ReadError::Malformed("if"))
+        },
+        "let" => match &items[1..] {
+            [Sexp::List(bindings), body] => {
+                let mut names = Vec::new();
+                let mut values = Vec::new();
+                for binding in bindings {
+                    match binding {
+                        Sexp::List(pair) => match &pair[..] {
+                            [Sexp::Atom(name), value] => {
+                                names.push(T::Sym::from(name));
+                                values.push(lower(value)?);
+                            },
+                            _ => return Err(ReadError::Malformed("let"))
+                        },
+                        _ => return Err(ReadError::Malformed("let"))
+                    }
+                }
+                let body = names.into_iter().rev().fold(lower(body)?, |body, name| {
+                    Expr::Lambda(name, Box::new(body))
+                });
+                Ok(apply(body, &values))
+            },
+            _ => Err(ReadError::Malformed("let"))
+        },
+        "define" => Err(ReadError::Malformed("define")),
+        _ => match items.split_first() {
+            Some((_, [])) => Err(ReadError::Nullary),
+            Some((f, args)) => {
+                let args = args.iter().map(lower).collect::<Result<Vec<_>, _>>()?;
+                Ok(apply(lower(f)?, &args))
+            },
+            None => Err(ReadError::Nullary)
+        }
+    }
+}
+
+fn atom_expr<T>(atom: &str) -> Result<Expr<T>, ReadError>
+where T: Types, T::Val: From<Num>, T::Sym: for<'s> From<&'s str> {
+    if let Ok(n) = atom.parse::<i64>() {
+        return Ok(Expr::Val(Num::Int(n).into()));
+    }
+    match atom {
+        "#t" => Ok(Expr::Val(Num::Bool(true).into())),
+        "#f" => Ok(Expr::Val(Num::Bool(false).into())),
+        _ => match atom.strip_prefix("#\\") {
+            Some("space")   => Ok(Expr::Val(Num::Char(' ').into())),
+            Some("newline") => Ok(Expr::Val(Num::Char('\n').into())),
+            Some(c) if c.chars().count() == 1 => {
+                Ok(Expr::Val(Num::Char(c.chars().next().unwrap()).into()))
+            },
+            _ if atom.starts_with('#') => Err(ReadError::BadLiteral(atom.to_string())),
+            _ => Ok(Expr::Var(T::Sym::from(atom)))
+        }
+    }
+}
+
+fn lambda<T>(params: &[Sexp], body: &Sexp) -> Result<Expr<T>, ReadError>
+where T: Types + Clone, T::Val: From<Num>, T::Sym: for<'s> From<&'s str> {
+    if params.is_empty() {
+        return Err(ReadError::Nullary);
+    }
+    params.iter().rev().try_fold(lower(body)?, |body, param| match param {
+        Sexp::Atom(name) => Ok(Expr::Lambda(T::Sym::from(name), Box::new(body))),
+        _ => Err(ReadError::Malformed("lambda"))
+    })
+}
+
+fn apply<T: Types + Clone>(f: Expr<T>, args: &[Expr<T>]) -> Expr<T> {
+    args.iter().fold(f, |f, x| Expr::App(Box::new(f), Box::new(x.clone())))
+}
+
+/* λ%g.(λ%x.%g (λ%v.%x %x %v)) (λ%x.%g (λ%v.%x %x %v))
+ *
+ * Z rather than Y: `%x %x` is under a lambda, so the fixpoint also
+ * works where arguments are evaluated first, as in `rust::module`.
+ * Recursive definitions are functions, so the extra `%v` is harmless.
+ */
+fn fix<T: Types>() -> Expr<T>
+where T::Sym: for<'s> From<&'s str> {
+    let var = |name: &str| Box::new(Expr::Var(name.into()));
+    let half = || Box::new(Expr::Lambda("%x".into(), Box::new(Expr::App(
+        var("%g"),
+        Box::new(Expr::Lambda("%v".into(), Box::new(Expr::App(
+            Box::new(Expr::App(var("%x"), var("%x"))),
+            var("%v")
+        ))))
+    ))));
+    Expr::Lambda("%g".into(), Box::new(Expr::App(half(), half())))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::num::{Primitives, Overflow};
+    use crate::stepper::{Stepper, NormalOrder, Stop};
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct SchemeTypes;
+
+    impl Types for SchemeTypes {
+        type Val = Num;
+        type Sym = String;
+    }
+
+    type E = Expr<SchemeTypes>;
+
+    fn run(src: &str) -> E {
+        let term: E = read(src).unwrap();
+        let term = Primitives::new(Overflow::Checked).resolve(&term);
+        let mut stepper = Stepper::new(Box::new(term), NormalOrder);
+        assert_eq!(stepper.run(1000).unwrap(), Stop::NormalForm, "{}", src);
+        *stepper.into_term()
+    }
+
+    #[test]
+    fn test_program() {
+        let src = "
+            ; apply f twice
+            (define (twice f x) (f (f x)))
+            (let ((inc (lambda (n) (+ n 1))))
+              (twice inc 5))
+        ";
+        assert_eq!(run(src), Expr::Val(Num::Int(7)));
+    }
+
+    #[test]
+    fn test_literals() {
+        assert_eq!(run("(if #f 1 #\\a)"), Expr::Val(Num::Char('a')));
+        assert_eq!(run("(if #t #\\( #\\space)"), Expr::Val(Num::Char('(')));
+        assert_eq!(text::read(&run("\"h\\\"i\"")), Some("h\"i".to_string()));
+        assert_eq!(run("-3"), Expr::Val(Num::Int(-3)));
+    }
+
+    #[test]
+    fn test_recursion() {
+        // Only terminates because normal order never unfolds the
+        // recursive call in the branch not taken.
+        let src = "(define (loop x) (if #t x (loop x))) (loop 3)";
+        assert!(read::<SchemeTypes>(src).unwrap().to_string().contains("%g"));
+        assert_eq!(run(src), Expr::Val(Num::Int(3)));
+    }
+
+    #[test]
+    fn test_factorial() {
+        let src = "
+            (define (fact n)
+              (if (== n 0) 1 (* n (fact (- n 1)))))
+            (fact 5)
+        ";
+        assert_eq!(run(src), Expr::Val(Num::Int(120)));
+        assert_eq!(run("(if (< 2 1) #\\a #\\b)"), Expr::Val(Num::Char('b')));
+    }
+
+    #[test]
+    fn test_errors() {
+        let err = |src| read::<SchemeTypes>(src).unwrap_err();
+        assert_eq!(err("(f 1"), ReadError::Unbalanced);
+        assert_eq!(err("f)"), ReadError::Unbalanced);
+        assert_eq!(err("\"abc"), ReadError::Unbalanced);
+        assert_eq!(err("(if a b)"), ReadError::Malformed("if"));
+        assert_eq!(err("(let (x 1) x)"), ReadError::Malformed("let"));
+        assert_eq!(err("(f)"), ReadError::Nullary);
+        assert_eq!(err("(lambda () 1)"), ReadError::Nullary);
+        assert_eq!(err("(define x 1)"), ReadError::NoExpression);
+        assert_eq!(err(""), ReadError::NoExpression);
+        assert_eq!(err("1 2"), ReadError::Malformed("define"));
+        assert_eq!(err("#q"), ReadError::BadLiteral("#q".to_string()));
+    }
+}
@@ -0,0 +1,162 @@
+// The MIT License (MIT)
+//
+// Copyright © 2022 <Brandon Lewis>
+//
+// Permission is hereby granted, free of charge, to any person
+// obtaining a copy of this software and associated documentation
+// files (the “Software”), to deal in the Software without
+// restriction, including without limitation the rights to use, copy,
+// modify, merge, publish, distribute, sublicense, and/or sell copies
+// of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS
+// BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN
+// ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+// Fork this project to create your own MIT license that you can
+// always link to.
+use crate::trs::Types;
+use crate::expr::Expr;
+
+
+/*
+ * Test-case reduction for lambda terms.
+ *
+ * Given a term that triggers some bug, `shrink` searches for a smaller
+ * term that still triggers it. The search is greedy: it repeatedly
+ * replaces a subterm by one of its own children, keeping the first
+ * candidate the predicate still accepts, until no candidate is
+ * accepted. Every accepted candidate is strictly smaller, so the
+ * search always terminates.
+ */
+
+
+/**
+ * Shrink `expr` while `fails` keeps returning true.
+ *
+ * `fails(expr)` is expected to be true. The result is locally
+ * minimal: no single replacement of an application by its function or
+ * argument, or of a lambda by its body, still fails. Such replacements
+ * can free bound variables, so `fails` should treat unbound variables
+ * as a different failure if that matters.
+ *
+ * To shrink a panic, wrap the code under test in
+ * `std::panic::catch_unwind` inside `fails`.
+ */
+pub fn shrink<T, F>(expr: &Expr<T>, mut fails: F) -> Expr<T>
+where T: Types + Clone, F: FnMut(&Expr<T>) -> bool {
+    let mut current = expr.clone();
+    'outer: loop {
+        for candidate in candidates(&current) {
+            if fails(&candidate) {
+                current = candidate;
+                continue 'outer;
+            }
+        }
+        return current;
+    }
+}
+
+/* All terms one replacement away from `expr`, biggest cuts first. */
+fn candidates<T>(expr: &Expr<T>) -> Vec<Expr<T>> where T: Types + Clone {
+    let mut ret = Vec::new();
+    match expr {
+        Expr::Lambda(a, b) => {
+            ret.push((**b).clone());
+            for b in candidates(b) {
+                ret.push(Expr::Lambda(a.clone(), Box::new(b)));
+            }
+        },
+        Expr::App(f, x) => {
+            ret.push((**f).clone());
+            ret.push((**x).clone());
+            for f in candidates(f) {
+                ret.push(Expr::App(Box::new(f), x.clone()));
+            }
+            for x in candidates(x) {
+                ret.push(Expr::App(f.clone(), Box::new(x)));
+            }
+        },
+        Expr::Var(_) | Expr::Val(_) => ()
+    }
+    ret
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expr::ReduceError;
+    use crate::trs::SigmaRules;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct ShrinkTypes;
+
+    #[derive(Clone, Debug, PartialEq)]
+    enum Val {
+        Ok,
+        Bad
+    }
+
+    impl SigmaRules for Val {
+        type Error = ();
+    }
+
+    impl Types for ShrinkTypes {
+        type Val = Val;
+        type Sym = String;
+    }
+
+    type E = Expr<ShrinkTypes>;
+
+    fn contains_bad(expr: &E) -> bool {
+        match expr {
+            Expr::Val(v) => *v == Val::Bad,
+            Expr::Var(_) => false,
+            Expr::Lambda(_, b) => contains_bad(b),
+            Expr::App(f, x) => contains_bad(f) || contains_bad(x)
+        }
+    }
+
+    #[test]
+    fn test_shrink_to_leaf() {
+        let term = E::apply(
+            E::lambda("x", E::apply(E::var("x"), E::val(Val::Ok))),
+            E::apply(E::val(Val::Ok), E::lambda("y", E::val(Val::Bad)))
+        );
+        assert_eq!(shrink(&term, contains_bad), Expr::Val(Val::Bad));
+    }
+
+    #[test]
+    fn test_shrink_failing_redex() {
+        // Ok doesn't apply to anything. Bury such a redex in a bigger
+        // term and shrink it out.
+        let fails = |expr: &E| expr.redexes().iter().any(|p| matches!(
+            expr.contractum_at(p),
+            Err(ReduceError::Delta {..})
+        ));
+
+        let culprit = E::apply(E::val(Val::Ok), E::val(Val::Ok));
+        let term = E::lambda("z", E::apply(
+            E::apply(E::var("z"), E::val(Val::Ok)),
+            E::apply(E::lambda("w", culprit.clone()), E::var("z"))
+        ));
+        assert!(fails(&term));
+        assert_eq!(shrink(&term, fails), *culprit);
+    }
+
+    #[test]
+    fn test_unshrinkable() {
+        let term = E::var("x");
+        assert_eq!(shrink(&term, |_| true), *term);
+    }
+}
@@ -0,0 +1,339 @@
+// The MIT License (MIT)
+//
+// Copyright © 2022 <Brandon Lewis>
+//
+// Permission is hereby granted, free of charge, to any person
+// obtaining a copy of this software and associated documentation
+// files (the “Software”), to deal in the Software without
+// restriction, including without limitation the rights to use, copy,
+// modify, merge, publish, distribute, sublicense, and/or sell copies
+// of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS
+// BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN
+// ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+// Fork this project to create your own MIT license that you can
+// always link to.
+use core::fmt;
+use crate::trs::Types;
+use crate::expr::Expr;
+use crate::stepper::{Stepper, NormalOrder, Stop};
+use crate::names::Fresh;
+
+
+/*
+ * A superoptimizer for combinator terms.
+ *
+ * Given the behavior of a small function, e.g. "applied to x and y,
+ * it gives y x", we search all combinator terms over a fixed basis,
+ * smallest first, for one that behaves the same way. "Behaves the
+ * same" means: applied to as many fresh variables, it normalizes to
+ * the same term. Since every candidate is checked by normalization
+ * under a fuel bound, the answer is the shortest term that provably
+ * has the behavior, among those whose check fits in the fuel.
+ *
+ * The search is exhaustive and therefore exponential in the size
+ * bound. It's a research toy, and a source of compact combinator
+ * encodings.
+ */
+
+
+/**
+ * Combinator terms over the S, K, I, B, C, W combinators, plus free
+ * variables numbered from zero.
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Comb {
+    S,
+    K,
+    I,
+    B,
+    C,
+    W,
+    Var(usize),
+    App(Box<Comb>, Box<Comb>)
+}
+
+pub const SKI:  [Comb; 3] = [Comb::S, Comb::K, Comb::I];
+pub const BCKW: [Comb; 4] = [Comb::B, Comb::C, Comb::K, Comb::W];
+
+
+impl Comb {
+    pub fn app(f: Comb, x: Comb) -> Comb {
+        Comb::App(Box::new(f), Box::new(x))
+    }
+
+    /* The number of combinators and variables in the term. */
+    pub fn size(&self) -> usize {
+        match self {
+            Comb::App(f, x) => f.size() + x.size(),
+            _               => 1
+        }
+    }
+
+    // How many arguments the head needs before it can be contracted.
+    fn arity(&self) -> Option<usize> {
+        match self {
+            Comb::S | Comb::B | Comb::C => Some(3),
+            Comb::K | Comb::W           => Some(2),
+            Comb::I                     => Some(1),
+            _                           => None
+        }
+    }
+
+    // Split `f a b c` into `f` and `[a, b, c]`.
+    fn unwind(self) -> (Comb, Vec<Comb>) {
+        let mut head = self;
+        let mut args = Vec::new();
+        while let Comb::App(f, x) = head {
+            args.push(*x);
+            head = *f;
+        }
+        args.reverse();
+        (head, args)
+    }
+
+    fn rewind(head: Comb, args: Vec<Comb>) -> Comb {
+        args.into_iter().fold(head, Comb::app)
+    }
+
+    /**
+     * Normalize leftmost-outermost, using at most `fuel` contractions.
+     *
+     * Returns `None` if the fuel runs out first.
+     */
+    pub fn normalize(self, fuel: &mut usize) -> Option<Comb> {
+        use Comb::*;
+        let (mut head, mut args) = self.unwind();
+
+        while let Some(n) = head.arity().filter(|n| args.len() >= *n) {
+            if *fuel == 0 {
+                return None;
+            }
+            *fuel -= 1;
+
+            let rest = args.split_off(n);
+            let mut a = args.into_iter();
+            let mut next = || a.next().expect("arity checked above");
+            let contractum = match head {
+                S => { let (x, y, z) = (next(), next(), next());
+                       Comb::app(Comb::app(x, z.clone()), Comb::app(y, z)) },
+                B => { let (x, y, z) = (next(), next(), next());
+                       Comb::app(x, Comb::app(y, z)) },
+                C => { let (x, y, z) = (next(), next(), next());
+                       Comb::app(Comb::app(x, z), y) },
+                W => { let (x, y) = (next(), next());
+                       Comb::app(Comb::app(x, y.clone()), y) },
+                K => { let (x, _) = (next(), next()); x },
+                I => next(),
+                _ => unreachable!()
+            };
+
+            let (h, mut a) = contractum.unwind();
+            a.extend(rest);
+            head = h;
+            args = a;
+        }
+
+        let args = args
+            .into_iter()
+            .map(|arg| arg.normalize(fuel))
+            .collect::<Option<Vec<_>>>()?;
+        Some(Comb::rewind(head, args))
+    }
+
+    /**
+     * Convert a normal form built only from applications of the given
+     * variables, as produced by applying a term to `params`.
+     */
+    pub fn from_expr<T>(expr: &Expr<T>, params: &[T::Sym]) -> Option<Comb>
+    where T: Types {
+        match expr {
+            Expr::Var(v)    => params.iter().position(|p| p == v).map(Comb::Var),
+            Expr::App(f, x) => Some(Comb::app(
+                Comb::from_expr(f, params)?,
+                Comb::from_expr(x, params)?
+            )),
+            _ => None
+        }
+    }
+
+    // Terms of this shape have a strictly smaller equivalent, so can
+    // never be the shortest: `I x` is `x`, and `K x y` is `x`.
+    fn has_trivial_redex(&self) -> bool {
+        match self {
+            Comb::App(f, _) => match &**f {
+                Comb::I                             => true,
+                Comb::App(k, _) if **k == Comb::K   => true,
+                _                                   => false
+            },
+            _ => false
+        }
+    }
+}
+
+
+impl fmt::Display for Comb {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Comb::Var(i)                        => write!(f, "x{}", i),
+            Comb::App(g, x) => match &**x {
+                Comb::App(_, _) => write!(f, "{} ({})", g, x),
+                _               => write!(f, "{} {}", g, x),
+            },
+            c => write!(f, "{:?}", c)
+        }
+    }
+}
+
+
+/**
+ * Work out the behavior of `expr` as a function of `params.len()`
+ * arguments, by applying it to `params` and normalizing.
+ *
+ * The names in `params` must not occur in `expr`. Returns `None` if
+ * the normal form isn't reached within `fuel` steps, or contains
+ * anything other than applications of the parameters.
+ */
+pub fn behavior<T>(expr: &Expr<T>, params: &[T::Sym], fuel: usize) -> Option<Comb>
+where T: Types + Clone, T::Sym: Fresh {
+    let applied = params.iter().fold(Box::new(expr.clone()), |f, p| {
+        Expr::apply(f, Expr::var(p.clone()))
+    });
+
+    let mut stepper = Stepper::new(applied, NormalOrder);
+    match stepper.run(fuel) {
+        Ok(Stop::NormalForm) => Comb::from_expr(stepper.term(), params),
+        _                    => None
+    }
+}
+
+
+/**
+ * Find a smallest term over `basis` which, applied to `arity`
+ * variables `x0 .. x(arity - 1)`, normalizes to `result`.
+ *
+ * Candidates are tried in order of size, up to `max_size`, each with
+ * `fuel` contractions. Among terms of equal size, the first in the
+ * enumeration order wins, so results are deterministic.
+ */
+pub fn superoptimize(
+    basis: &[Comb],
+    arity: usize,
+    result: &Comb,
+    max_size: usize,
+    fuel: usize
+) -> Option<Comb> {
+    let matches = |candidate: &Comb| {
+        let applied = (0..arity).fold(candidate.clone(), |f, i| {
+            Comb::app(f, Comb::Var(i))
+        });
+        applied.normalize(&mut fuel.clone()).as_ref() == Some(result)
+    };
+
+    // terms[n] holds every candidate of size n + 1.
+    let mut terms: Vec<Vec<Comb>> = Vec::new();
+    for size in 1..=max_size {
+        let level: Vec<Comb> = if size == 1 {
+            basis.to_vec()
+        } else {
+            let mut level = Vec::new();
+            for left in 1..size {
+                for f in &terms[left - 1] {
+                    for x in &terms[size - left - 1] {
+                        let candidate = Comb::app(f.clone(), x.clone());
+                        if !candidate.has_trivial_redex() {
+                            level.push(candidate);
+                        }
+                    }
+                }
+            }
+            level
+        };
+
+        if let Some(found) = level.iter().find(|c| matches(c)) {
+            return Some(found.clone());
+        }
+        terms.push(level);
+    }
+
+    None
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trs::SigmaRules;
+    use Comb::*;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct SkiTypes;
+
+    impl SigmaRules for () {
+        type Error = ();
+    }
+
+    impl Types for SkiTypes {
+        type Val = ();
+        type Sym = String;
+    }
+
+    type E = Expr<SkiTypes>;
+
+    fn var(i: usize) -> Comb { Var(i) }
+
+    #[test]
+    fn test_normalize() {
+        // S K K x -> K x (K x) -> x
+        let skk = Comb::app(Comb::app(Comb::app(S, K), K), var(0));
+        assert_eq!(skk.normalize(&mut 10), Some(var(0)));
+
+        // Arguments are normalized too: x (I y) -> x y
+        let nested = Comb::app(var(0), Comb::app(I, var(1)));
+        assert_eq!(nested.normalize(&mut 10), Some(Comb::app(var(0), var(1))));
+
+        // S I I (S I I) diverges.
+        let sii = || Comb::app(Comb::app(S, I), I);
+        assert_eq!(Comb::app(sii(), sii()).normalize(&mut 100), None);
+    }
+
+    #[test]
+    fn test_identity() {
+        let found = superoptimize(&[S, K], 1, &var(0), 5, 20).unwrap();
+        assert_eq!(found.to_string(), "S K S");
+    }
+
+    #[test]
+    fn test_single_combinators() {
+        // flip
+        let flipped = Comb::app(Comb::app(var(0), var(2)), var(1));
+        assert_eq!(superoptimize(&BCKW, 3, &flipped, 3, 20), Some(C));
+        assert_eq!(superoptimize(&SKI, 2, &var(0), 3, 20), Some(K));
+    }
+
+    #[test]
+    fn test_from_expr() {
+        // \x.\y.y x
+        let expr = E::lambda("x", E::lambda("y", E::apply(E::var("y"), E::var("x"))));
+        let spec = behavior(&expr, &["a".into(), "b".into()], 10).unwrap();
+        assert_eq!(spec, Comb::app(var(1), var(0)));
+
+        let found = superoptimize(&SKI, 2, &spec, 5, 20).unwrap();
+        assert_eq!(found.to_string(), "S (K (S I)) K");
+        assert_eq!(found.size(), 5);
+
+        // Not a pure combinator.
+        assert_eq!(behavior(&E::lambda("x", E::var("z")), &["a".into()], 10), None);
+    }
+}
@@ -0,0 +1,574 @@
+// The MIT License (MIT)
+//
+// Copyright © 2022 <Brandon Lewis>
+//
+// Permission is hereby granted, free of charge, to any person
+// obtaining a copy of this software and associated documentation
+// files (the “Software”), to deal in the Software without
+// restriction, including without limitation the rights to use, copy,
+// modify, merge, publish, distribute, sublicense, and/or sell copies
+// of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS
+// BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN
+// ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+// Fork this project to create your own MIT license that you can
+// always link to.
+use core::convert::TryFrom;
+use core::fmt::{self, Debug, Write};
+use std::collections::BTreeMap;
+use crate::grs::{Types, DataGraph, DataGraphBody};
+use crate::json::json_string;
+
+
+/* The index of a node in a `Slab`. */
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(pub u32);
+
+impl NodeId {
+    fn index(self) -> usize { self.0 as usize }
+}
+
+
+#[derive(Clone, Debug)]
+enum Slot<V> {
+    // A free slot, and the next free slot after it.
+    Free(Option<NodeId>),
+    Used(V, Vec<NodeId>)
+}
+
+
+/**
+ * A compact datagraph: nodes live in a slab, addressed by 32-bit ids.
+ *
+ * Ids are indices into a `Vec`, so there are no pointers to chase or
+ * fix up, a node id is half the size of a pointer on 64-bit targets,
+ * and the whole graph is plain data, which keeps serialization and
+ * garbage collection simple.
+ *
+ * Freed slots are threaded onto a free list and reused by `alloc()`
+ * before the slab grows. Nodes are only freed by `gc()`.
+ */
+#[derive(Clone, Debug)]
+pub struct Slab<V> {
+    slots: Vec<Slot<V>>,
+    free: Option<NodeId>,
+    root: NodeId,
+    live: usize
+}
+
+
+/**
+ * A summary of what a slab holds, for watching how the heap evolves
+ * during reduction.
+ *
+ * Take one between reduction steps, every so often, and compare:
+ * growing counts for one tag, or one node retaining more and more of
+ * the heap, point at a space leak. Render a snapshot with `to_json`,
+ * and the heap itself with `grs::to_dot`.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct HeapSnapshot {
+    pub live: usize,
+    pub capacity: usize,
+    // Live nodes reachable from the root. The rest is garbage which
+    // the next `gc()` will free.
+    pub reachable: usize,
+    // Reachable nodes, by tag.
+    pub by_tag: BTreeMap<String, usize>,
+    // The nodes which reach the most other nodes, with how many they
+    // reach (including themselves), largest first.
+    pub largest: Vec<(NodeId, usize)>,
+    // Reachable nodes referred to by more than one argument.
+    pub shared: usize
+}
+
+impl HeapSnapshot {
+    pub fn to_json(&self) -> String {
+        let by_tag: Vec<String> = self.by_tag.iter()
+            .map(|(tag, count)| format!("{}:{}", json_string(tag), count))
+            .collect();
+        let largest: Vec<String> = self.largest.iter()
+            .map(|(id, size)| format!("{{\"node\":{},\"size\":{}}}", id.0, size))
+            .collect();
+        format!(
+            "{{\"live\":{},\"capacity\":{},\"reachable\":{},\"shared\":{},\"by_tag\":{{{}}},\"largest\":[{}]}}",
+            self.live,
+            self.capacity,
+            self.reachable,
+            self.shared,
+            by_tag.join(","),
+            largest.join(",")
+        )
+    }
+}
+
+
+/**
+ * A broken slab invariant, found by `Slab::check_invariants`.
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Violation {
+    // A used node has an argument which is out of range or free.
+    Dangling {node: NodeId, arg: NodeId},
+    // The slab isn't empty, but the root is out of range or free.
+    DanglingRoot(NodeId),
+    // The free list reaches a used or out of range slot, or loops.
+    FreeList(NodeId),
+    // A free slot that isn't on the free list, and so is never reused.
+    Unlisted(NodeId),
+    // The live count disagrees with the number of used slots.
+    LiveCount {recorded: usize, counted: usize}
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::Dangling {node, arg} =>
+                write!(f, "#{} has dangling argument #{}", node.0, arg.0),
+            Violation::DanglingRoot(id) => write!(f, "dangling root #{}", id.0),
+            Violation::FreeList(id) => write!(f, "free list broken at #{}", id.0),
+            Violation::Unlisted(id) => write!(f, "free slot #{} not on the free list", id.0),
+            Violation::LiveCount {recorded, counted} =>
+                write!(f, "live count is {} but {} slots are used", recorded, counted)
+        }
+    }
+}
+
+
+impl<V> Slab<V> {
+    /* The number of allocated nodes. */
+    pub fn live(&self) -> usize {
+        self.live
+    }
+
+    /* The number of slots, allocated or free. */
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    /**
+     * Summarize the heap, naming each node's kind with `tag` and
+     * listing the `top` nodes which reach the most others.
+     *
+     * Finding those walks the graph once per reachable node, so this
+     * is quadratic in the worst case.
+     */
+    pub fn snapshot(&self, tag: impl Fn(&V) -> String, top: usize) -> HeapSnapshot {
+        let marks = self.mark();
+        let reachable: Vec<NodeId> = (0..self.slots.len())
+            .filter(|i| marks[*i])
+            .map(|i| NodeId(i as u32))
+            .collect();
+
+        let mut by_tag = BTreeMap::new();
+        let mut referrers = vec![0usize; self.slots.len()];
+        for id in &reachable {
+            let (value, args) = self.node(*id);
+            *by_tag.entry(tag(value)).or_insert(0) += 1;
+            for arg in args {
+                referrers[arg.index()] += 1;
+            }
+        }
+
+        let mut largest: Vec<(NodeId, usize)> = reachable.iter()
+            .map(|id| (*id, self.reach(*id)))
+            .collect();
+        largest.sort_by_key(|(id, size)| (core::cmp::Reverse(*size), *id));
+        largest.truncate(top);
+
+        HeapSnapshot {
+            live: self.live,
+            capacity: self.slots.len(),
+            reachable: reachable.len(),
+            by_tag,
+            largest,
+            shared: referrers.iter().filter(|n| **n > 1).count()
+        }
+    }
+
+    /**
+     * Check the slab's internal consistency.
+     *
+     * With the `invariants` feature, this runs after every operation
+     * which modifies the slab, panicking with a `dump()` on failure.
+     */
+    pub fn check_invariants(&self) -> Result<(), Violation> {
+        let used = |id: NodeId| matches!(self.slots.get(id.index()), Some(Slot::Used(_, _)));
+
+        let mut counted = 0;
+        for (i, slot) in self.slots.iter().enumerate() {
+            if let Slot::Used(_, args) = slot {
+                counted += 1;
+                if let Some(arg) = args.iter().find(|arg| !used(**arg)) {
+                    return Err(Violation::Dangling {node: NodeId(i as u32), arg: *arg});
+                }
+            }
+        }
+        if counted != self.live {
+            return Err(Violation::LiveCount {recorded: self.live, counted});
+        }
+        if self.live > 0 && !used(self.root) {
+            return Err(Violation::DanglingRoot(self.root));
+        }
+
+        let mut listed = vec![false; self.slots.len()];
+        let mut next = self.free;
+        while let Some(id) = next {
+            match self.slots.get(id.index()) {
+                Some(Slot::Free(after)) if !listed[id.index()] => {
+                    listed[id.index()] = true;
+                    next = *after;
+                },
+                _ => return Err(Violation::FreeList(id))
+            }
+        }
+        match listed.iter().zip(&self.slots).position(|(l, s)| !l && matches!(s, Slot::Free(_))) {
+            Some(i) => Err(Violation::Unlisted(NodeId(i as u32))),
+            None    => Ok(())
+        }
+    }
+
+    /**
+     * One line per slot, for bug reports.
+     *
+     * ```text
+     * root #0, 2 live of 3, free #1
+     * #0 Add [#2, #2]
+     * #1 free -> none
+     * #2 Int(2) []
+     * ```
+     */
+    pub fn dump(&self) -> String where V: Debug {
+        let id = |id: Option<NodeId>| id.map_or("none".to_string(), |id| format!("#{}", id.0));
+        let mut ret = format!(
+            "root #{}, {} live of {}, free {}\n",
+            self.root.0, self.live, self.slots.len(), id(self.free)
+        );
+        for (i, slot) in self.slots.iter().enumerate() {
+            match slot {
+                Slot::Free(next) => writeln!(ret, "#{} free -> {}", i, id(*next)),
+                Slot::Used(value, args) => {
+                    let args: Vec<String> = args.iter().map(|a| format!("#{}", a.0)).collect();
+                    writeln!(ret, "#{} {:?} [{}]", i, value, args.join(", "))
+                }
+            }.unwrap();
+        }
+        ret
+    }
+
+    #[cfg(feature = "invariants")]
+    fn debug_check(&self) where V: Debug {
+        if let Err(violation) = self.check_invariants() {
+            panic!("slab invariant violated: {}\n{}", violation, self.dump());
+        }
+    }
+
+    #[cfg(not(feature = "invariants"))]
+    fn debug_check(&self) {}
+
+    // How many nodes are reachable from `id`, including itself.
+    fn reach(&self, id: NodeId) -> usize {
+        let mut seen = vec![false; self.slots.len()];
+        let mut todo = vec![id];
+        let mut count = 0;
+        while let Some(id) = todo.pop() {
+            if !seen[id.index()] {
+                seen[id.index()] = true;
+                count += 1;
+                todo.extend(self.node(id).1.iter().copied());
+            }
+        }
+        count
+    }
+
+    fn node(&self, id: NodeId) -> (&V, &Vec<NodeId>) {
+        match &self.slots[id.index()] {
+            Slot::Used(value, args) => (value, args),
+            Slot::Free(_)           => panic!("dangling node id {:?}", id)
+        }
+    }
+
+    fn node_mut(&mut self, id: NodeId) -> (&mut V, &mut Vec<NodeId>) {
+        match &mut self.slots[id.index()] {
+            Slot::Used(value, args) => (value, args),
+            Slot::Free(_)           => panic!("dangling node id {:?}", id)
+        }
+    }
+
+    // Mark every node reachable from the root.
+    fn mark(&self) -> Vec<bool> {
+        let mut marks = vec![false; self.slots.len()];
+        if self.live == 0 {
+            return marks;
+        }
+
+        let mut todo = vec![self.root];
+        while let Some(id) = todo.pop() {
+            if !marks[id.index()] {
+                marks[id.index()] = true;
+                todo.extend(self.node(id).1.iter().copied());
+            }
+        }
+        marks
+    }
+}
+
+
+impl<'a, T> DataGraphBody<'a, T> for Slab<T::Val> where T: Types<Id = NodeId> {
+    type It = core::iter::Copied<core::slice::Iter<'a, NodeId>>;
+
+    fn new() -> Self {
+        Slab {slots: Vec::new(), free: None, root: NodeId(0), live: 0}
+    }
+
+    fn args(&'a self, id: NodeId) -> Self::It {
+        self.node(id).1.iter().copied()
+    }
+
+    fn value(&'a self, id: NodeId) -> T::Val {
+        *self.node(id).0
+    }
+
+    fn alloc(&'a mut self, func: T::Val) -> NodeId {
+        self.live += 1;
+        match self.free {
+            Some(id) => {
+                if let Slot::Free(next) = self.slots[id.index()] {
+                    self.free = next;
+                }
+                self.slots[id.index()] = Slot::Used(func, Vec::new());
+                self.debug_check();
+                id
+            },
+            None => {
+                let id = u32::try_from(self.slots.len()).expect("heap exhausted");
+                self.slots.push(Slot::Used(func, Vec::new()));
+                self.debug_check();
+                NodeId(id)
+            }
+        }
+    }
+
+    fn append_arg(&'a mut self, id: NodeId, arg: NodeId) {
+        self.node_mut(id).1.push(arg);
+        self.debug_check();
+    }
+
+    // Every reference to `src`, including the root, now points to
+    // `dst`. `src` itself is left for the collector.
+    fn redirect(&'a mut self, src: NodeId, dst: NodeId) {
+        for slot in self.slots.iter_mut() {
+            if let Slot::Used(_, args) = slot {
+                for arg in args.iter_mut().filter(|arg| **arg == src) {
+                    *arg = dst;
+                }
+            }
+        }
+
+        if self.root == src {
+            self.root = dst;
+        }
+        self.debug_check();
+    }
+
+    fn root(&'a self) -> NodeId {
+        self.root
+    }
+
+    fn gc(&'a mut self) {
+        let marks = self.mark();
+        for (i, marked) in marks.into_iter().enumerate().rev() {
+            if !marked && matches!(self.slots[i], Slot::Used(_, _)) {
+                self.slots[i] = Slot::Free(self.free);
+                self.free = Some(NodeId(i as u32));
+                self.live -= 1;
+            }
+        }
+        self.debug_check();
+    }
+}
+
+impl<T> DataGraph<T> for Slab<T::Val> where T: Types<Id = NodeId> {}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grs::SigmaRules;
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    enum Value {Add, Int(i8)}
+
+    impl SigmaRules for Value {
+        type Error = ();
+    }
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    struct SlabTypes;
+
+    impl Types for SlabTypes {
+        type Val = Value;
+        type Id  = NodeId;
+        type Var = u8;
+    }
+
+    type Heap = Slab<Value>;
+
+    fn body<'a>(heap: &'a mut Heap) -> &'a mut impl DataGraphBody<'a, SlabTypes> {
+        heap
+    }
+
+    // Add(Int(1), Int(2)), plus an unreachable Int(3).
+    fn example() -> Heap {
+        let mut heap = <Heap as DataGraphBody<SlabTypes>>::new();
+        let add = body(&mut heap).alloc(Value::Add);
+        let one = body(&mut heap).alloc(Value::Int(1));
+        let two = body(&mut heap).alloc(Value::Int(2));
+        body(&mut heap).alloc(Value::Int(3));
+        body(&mut heap).append_arg(add, one);
+        body(&mut heap).append_arg(add, two);
+        heap
+    }
+
+    #[test]
+    fn test_alloc() {
+        let heap = example();
+        assert_eq!(heap.live(), 4);
+        assert_eq!(DataGraphBody::<SlabTypes>::root(&heap), NodeId(0));
+        assert_eq!(DataGraphBody::<SlabTypes>::value(&heap, NodeId(1)), Value::Int(1));
+        assert_eq!(
+            DataGraphBody::<SlabTypes>::args(&heap, NodeId(0)).collect::<Vec<_>>(),
+            vec![NodeId(1), NodeId(2)]
+        );
+    }
+
+    #[test]
+    fn test_gc_reuses_slots() {
+        let mut heap = example();
+        DataGraphBody::<SlabTypes>::gc(&mut heap);
+        assert_eq!(heap.live(), 3);
+
+        // Int(2) is now shared, so Int(1) becomes garbage too.
+        DataGraphBody::<SlabTypes>::redirect(&mut heap, NodeId(1), NodeId(2));
+        DataGraphBody::<SlabTypes>::gc(&mut heap);
+        assert_eq!(heap.live(), 2);
+        assert_eq!(heap.capacity(), 4);
+
+        // Freed slots are reused, lowest first.
+        assert_eq!(body(&mut heap).alloc(Value::Int(4)), NodeId(1));
+        assert_eq!(body(&mut heap).alloc(Value::Int(5)), NodeId(3));
+        assert_eq!(body(&mut heap).alloc(Value::Int(6)), NodeId(4));
+        assert_eq!(heap.live(), 5);
+    }
+
+    #[test]
+    fn test_redirect_root() {
+        let mut heap = example();
+        DataGraphBody::<SlabTypes>::redirect(&mut heap, NodeId(0), NodeId(3));
+        assert_eq!(DataGraphBody::<SlabTypes>::root(&heap), NodeId(3));
+        DataGraphBody::<SlabTypes>::gc(&mut heap);
+        assert_eq!(heap.live(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "dangling node id")]
+    fn test_dangling() {
+        let mut heap = example();
+        DataGraphBody::<SlabTypes>::gc(&mut heap);
+        DataGraphBody::<SlabTypes>::value(&heap, NodeId(3));
+    }
+
+    fn tag(value: &Value) -> String {
+        match value {
+            Value::Add    => "Add".into(),
+            Value::Int(_) => "Int".into()
+        }
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let mut heap = example();
+        DataGraphBody::<SlabTypes>::redirect(&mut heap, NodeId(1), NodeId(2));
+        let snapshot = heap.snapshot(tag, 2);
+
+        // Add(Int(2), Int(2)), with Int(1) and Int(3) unreachable.
+        assert_eq!(snapshot.live, 4);
+        assert_eq!(snapshot.reachable, 2);
+        assert_eq!(snapshot.shared, 1);
+        assert_eq!(snapshot.largest, vec![(NodeId(0), 2), (NodeId(2), 1)]);
+        assert_eq!(
+            snapshot.to_json(),
+            concat!(
+                "{\"live\":4,\"capacity\":4,\"reachable\":2,\"shared\":1,",
+                "\"by_tag\":{\"Add\":1,\"Int\":1},",
+                "\"largest\":[{\"node\":0,\"size\":2},{\"node\":2,\"size\":1}]}"
+            )
+        );
+    }
+
+    #[test]
+    fn test_dump_snapshot() {
+        // Add(Int(2), Int(2), Int(5)), with Int(5) in a reused slot.
+        let mut heap = example();
+        DataGraphBody::<SlabTypes>::redirect(&mut heap, NodeId(1), NodeId(2));
+        DataGraphBody::<SlabTypes>::gc(&mut heap);
+        let five = body(&mut heap).alloc(Value::Int(5));
+        body(&mut heap).append_arg(NodeId(0), five);
+        crate::snapshot::Snapshots::in_package().assert("slab_dump", &heap.dump());
+    }
+
+    #[test]
+    fn test_invariants() {
+        let mut heap = example();
+        assert_eq!(heap.check_invariants(), Ok(()));
+        DataGraphBody::<SlabTypes>::redirect(&mut heap, NodeId(1), NodeId(2));
+        DataGraphBody::<SlabTypes>::gc(&mut heap);
+        assert_eq!(heap.check_invariants(), Ok(()));
+        assert_eq!(heap.dump(), concat!(
+            "root #0, 2 live of 4, free #1\n",
+            "#0 Add [#2, #2]\n",
+            "#1 free -> #3\n",
+            "#2 Int(2) []\n",
+            "#3 free -> none\n"
+        ));
+
+        let mut bad = heap.clone();
+        bad.slots[0] = Slot::Used(Value::Add, vec![NodeId(3)]);
+        assert_eq!(bad.check_invariants(), Err(Violation::Dangling {node: NodeId(0), arg: NodeId(3)}));
+
+        let mut bad = heap.clone();
+        bad.live = 3;
+        assert_eq!(bad.check_invariants(), Err(Violation::LiveCount {recorded: 3, counted: 2}));
+
+        let mut bad = heap.clone();
+        bad.slots[3] = Slot::Free(Some(NodeId(1)));
+        assert_eq!(bad.check_invariants(), Err(Violation::FreeList(NodeId(1))));
+
+        let mut bad = heap.clone();
+        bad.free = Some(NodeId(3));
+        assert_eq!(bad.check_invariants(), Err(Violation::Unlisted(NodeId(1))));
+
+        let mut bad = heap;
+        bad.root = NodeId(1);
+        assert_eq!(bad.check_invariants(), Err(Violation::DanglingRoot(NodeId(1))));
+    }
+
+    #[test]
+    #[cfg(feature = "invariants")]
+    #[should_panic(expected = "slab invariant violated: #0 has dangling argument #7")]
+    fn test_invariants_checked() {
+        let mut heap = example();
+        body(&mut heap).append_arg(NodeId(0), NodeId(7));
+    }
+}
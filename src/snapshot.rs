@@ -0,0 +1,291 @@
+// The MIT License (MIT)
+//
+// Copyright © 2022 <Brandon Lewis>
+//
+// Permission is hereby granted, free of charge, to any person
+// obtaining a copy of this software and associated documentation
+// files (the “Software”), to deal in the Software without
+// restriction, including without limitation the rights to use, copy,
+// modify, merge, publish, distribute, sublicense, and/or sell copies
+// of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS
+// BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN
+// ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+// Fork this project to create your own MIT license that you can
+// always link to.
+use core::fmt;
+use std::path::PathBuf;
+
+
+/*
+ * Snapshot testing.
+ *
+ * A snapshot is the expected output of a printer, kept in a file
+ * next to the tests rather than in a string literal, so that a change
+ * to a format shows up as a diff and can be accepted wholesale. Run
+ * the tests with `UPDATE_SNAPSHOTS=1` to write the current output as
+ * the new snapshots, then review them like any other change.
+ *
+ * Output is compared after normalizing line endings to `\n` and
+ * ending it with exactly one, so that editors and checkouts on other
+ * platforms don't cause spurious failures. The printers meant to be
+ * snapshotted (`Display`, `pretty`, `to_dot`, `Slab::dump`) are
+ * deterministic, with no addresses or hash-map ordering in them. This
+ * crate's own tests keep theirs in `snapshots/`.
+ */
+
+
+pub const UPDATE_VAR: &str = "UPDATE_SNAPSHOTS";
+
+
+/**
+ * A directory of snapshots, one `.snap` file each.
+ */
+#[derive(Clone, Debug)]
+pub struct Snapshots {
+    dir: PathBuf,
+    update: bool
+}
+
+
+/**
+ * Why a snapshot didn't match.
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Mismatch {
+    // There is no snapshot of this name yet.
+    Missing(String),
+    // The output changed. `diff` has one line per line of either,
+    // prefixed with ` `, `-` (snapshot only), or `+` (output only).
+    Changed {name: String, diff: String},
+    Io(String)
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Mismatch::Missing(name) => write!(
+                f, "no snapshot `{}`; run with {}=1 to create it", name, UPDATE_VAR
+            ),
+            Mismatch::Changed {name, diff} => write!(
+                f, "snapshot `{}` changed; run with {}=1 to accept:\n{}", name, UPDATE_VAR, diff
+            ),
+            Mismatch::Io(err) => write!(f, "{}", err)
+        }
+    }
+}
+
+
+impl Snapshots {
+    /* Snapshots in `dir`, updated if `UPDATE_SNAPSHOTS` is set. */
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        let update = std::env::var_os(UPDATE_VAR).is_some_and(|v| !v.is_empty() && v != "0");
+        Snapshots {dir: dir.into(), update}
+    }
+
+    /**
+     * Snapshots in `snapshots/` in the package under test, as found by
+     * `CARGO_MANIFEST_DIR`, which Cargo sets when running tests.
+     */
+    pub fn in_package() -> Self {
+        let root = std::env::var_os("CARGO_MANIFEST_DIR").unwrap_or_default();
+        Self::new(PathBuf::from(root).join("snapshots"))
+    }
+
+    /* Override the environment: write snapshots instead of checking. */
+    pub fn with_update(self, update: bool) -> Self {
+        Snapshots {update, ..self}
+    }
+
+    /**
+     * Compare `output` to the snapshot `name`, or write it when
+     * updating. `name` becomes a file name, so should be plain.
+     */
+    pub fn check(&self, name: &str, output: &str) -> Result<(), Mismatch> {
+        let path = self.dir.join(format!("{}.snap", name));
+        let output = canonical(output);
+        let io = |e: std::io::Error| Mismatch::Io(format!("{}: {}", path.display(), e));
+
+        if self.update {
+            std::fs::create_dir_all(&self.dir).map_err(io)?;
+            return std::fs::write(&path, output).map_err(io);
+        }
+
+        let expected = match std::fs::read_to_string(&path) {
+            Ok(s) => canonical(&s),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Err(Mismatch::Missing(name.to_string()))
+            },
+            Err(e) => return Err(io(e))
+        };
+        if expected == output {
+            Ok(())
+        } else {
+            Err(Mismatch::Changed {name: name.to_string(), diff: diff(&expected, &output)})
+        }
+    }
+
+    /* `check`, panicking with the diff on a mismatch. */
+    pub fn assert(&self, name: &str, output: &str) {
+        if let Err(mismatch) = self.check(name, output) {
+            panic!("{}", mismatch);
+        }
+    }
+}
+
+
+/* `\n` line endings, and exactly one at the end. */
+pub fn canonical(s: &str) -> String {
+    let mut ret = s.replace("\r\n", "\n");
+    ret.truncate(ret.trim_end_matches('\n').len());
+    ret.push('\n');
+    ret
+}
+
+
+/* The most LCS table cells `diff` will allocate. */
+const DIFF_LIMIT: usize = 1 << 20;
+
+
+/**
+ * A line diff, by longest common subsequence.
+ *
+ * The common prefix and suffix are trimmed first, so the quadratic
+ * table only covers the lines around the changes, which for a
+ * snapshot is usually a handful. If that is still more than
+ * `DIFF_LIMIT` cells, the changed lines are listed as all removed,
+ * then all added.
+ */
+fn diff(old: &str, new: &str) -> String {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..].iter().rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (a, b) = (&old[prefix..old.len() - suffix], &new[prefix..new.len() - suffix]);
+
+    let mut ret = String::new();
+    for line in &old[..prefix] {
+        ret.push_str(&format!(" {}\n", line));
+    }
+    if (a.len() + 1).saturating_mul(b.len() + 1) > DIFF_LIMIT {
+        for line in a {
+            ret.push_str(&format!("-{}\n", line));
+        }
+        for line in b {
+            ret.push_str(&format!("+{}\n", line));
+        }
+    } else {
+        diff_lcs(a, b, &mut ret);
+    }
+    for line in &old[old.len() - suffix..] {
+        ret.push_str(&format!(" {}\n", line));
+    }
+    ret
+}
+
+fn diff_lcs(old: &[&str], new: &[&str], ret: &mut String) {
+    // lcs[i][j] is the LCS length of old[i..] and new[j..].
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            ret.push_str(&format!(" {}\n", old[i]));
+            i += 1;
+            j += 1;
+        } else if j < new.len() && (i == old.len() || lcs[i][j + 1] > lcs[i + 1][j]) {
+            ret.push_str(&format!("+{}\n", new[j]));
+            j += 1;
+        } else {
+            ret.push_str(&format!("-{}\n", old[i]));
+            i += 1;
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir()
+            .join(format!("fgrs-snapshots-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let dir = scratch("round-trip");
+        let snaps = Snapshots::new(&dir).with_update(false);
+        assert_eq!(snaps.check("a", "x\ny"), Err(Mismatch::Missing("a".to_string())));
+
+        snaps.clone().with_update(true).check("a", "x\ny").unwrap();
+        assert_eq!(std::fs::read_to_string(dir.join("a.snap")).unwrap(), "x\ny\n");
+        assert_eq!(snaps.check("a", "x\r\ny\n\n"), Ok(()));
+
+        match snaps.check("a", "x\nz") {
+            Err(Mismatch::Changed {name, diff}) => {
+                assert_eq!(name, "a");
+                assert_eq!(diff, " x\n-y\n+z\n");
+            },
+            other => panic!("expected a change, got {:?}", other)
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "snapshot `b` changed")]
+    fn test_assert() {
+        let dir = scratch("assert");
+        Snapshots::new(&dir).with_update(true).assert("b", "1");
+        Snapshots::new(&dir).with_update(false).assert("b", "2");
+    }
+
+    #[test]
+    fn test_diff() {
+        assert_eq!(diff("a\nb\nc", "a\nc\nd"), " a\n-b\n c\n+d\n");
+        assert_eq!(diff("", "a"), "+a\n");
+        assert_eq!(canonical("a\r\n\n"), "a\n");
+
+        // Only the changed middle goes through the table.
+        let old: String = (0..100_000).map(|n| format!("{}\n", n)).collect();
+        let new = old.replacen("\n5000\n", "\nfive thousand\n", 1);
+        let lines = diff(&old, &new);
+        assert_eq!(lines.lines().filter(|l| !l.starts_with(' ')).collect::<Vec<_>>(),
+                   ["-5000", "+five thousand"]);
+
+        // Too big a middle is listed whole.
+        let a: String = (0..2000).map(|n| format!("a{}\n", n)).collect();
+        let b: String = (0..2000).map(|n| format!("b{}\n", n)).collect();
+        let lines: Vec<String> = diff(&a, &b).lines().map(String::from).collect();
+        assert_eq!(lines.len(), 4000);
+        assert_eq!((lines[0].as_str(), lines[2000].as_str()), ("-a0", "+b0"));
+        assert_eq!(canonical(""), "\n");
+    }
+}
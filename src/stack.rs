@@ -0,0 +1,136 @@
+// The MIT License (MIT)
+//
+// Copyright © 2022 <Brandon Lewis>
+//
+// Permission is hereby granted, free of charge, to any person
+// obtaining a copy of this software and associated documentation
+// files (the “Software”), to deal in the Software without
+// restriction, including without limitation the rights to use, copy,
+// modify, merge, publish, distribute, sublicense, and/or sell copies
+// of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS
+// BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN
+// ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+// Fork this project to create your own MIT license that you can
+// always link to.
+
+
+/**
+ * A LIFO stack which keeps its first `N` elements inline.
+ *
+ * This is a poor man's `SmallVec`, restricted to push and pop, and
+ * written without `unsafe`: the inline slots are `Option`s. Only once
+ * more than `N` elements are live does anything go to the heap, in a
+ * "spill" `Vec` which holds the top of the stack.
+ *
+ * Parsing short token streams is the motivating case: most terms
+ * never need more than a handful of stack slots.
+ */
+pub struct SmallStack<T, const N: usize> {
+    inline: [Option<T>; N],
+    len: usize,
+    spill: Vec<T>
+}
+
+impl<T, const N: usize> SmallStack<T, N> {
+    pub fn new() -> Self {
+        SmallStack {
+            inline: core::array::from_fn(|_| None),
+            len: 0,
+            spill: Vec::new()
+        }
+    }
+
+    /* Reserve room for `capacity` elements in total, so that the
+     * spill is allocated at most once. */
+    pub fn with_capacity(capacity: usize) -> Self {
+        SmallStack {
+            spill: Vec::with_capacity(capacity.saturating_sub(N)),
+            ..Self::new()
+        }
+    }
+
+    pub fn push(&mut self, value: T) {
+        if self.len < N {
+            self.inline[self.len] = Some(value);
+            self.len += 1;
+        } else {
+            self.spill.push(value);
+        }
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        if let Some(value) = self.spill.pop() {
+            Some(value)
+        } else if self.len > 0 {
+            self.len -= 1;
+            self.inline[self.len].take()
+        } else {
+            None
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len + self.spill.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /* True if the contents have outgrown the inline storage. */
+    pub fn spilled(&self) -> bool {
+        !self.spill.is_empty()
+    }
+}
+
+impl<T, const N: usize> Default for SmallStack<T, N> {
+    fn default() -> Self { Self::new() }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_push_pop() {
+        let mut stack: SmallStack<u8, 2> = SmallStack::new();
+        assert!(stack.is_empty());
+
+        stack.push(1);
+        stack.push(2);
+        assert!(!stack.spilled());
+        stack.push(3);
+        assert!(stack.spilled());
+        assert_eq!(stack.len(), 3);
+
+        assert_eq!(stack.pop(), Some(3));
+        assert!(!stack.spilled());
+        stack.push(4);
+        assert_eq!(stack.pop(), Some(4));
+        assert_eq!(stack.pop(), Some(2));
+        assert_eq!(stack.pop(), Some(1));
+        assert_eq!(stack.pop(), None);
+    }
+
+    #[test]
+    fn test_with_capacity() {
+        let stack: SmallStack<u8, 4> = SmallStack::with_capacity(3);
+        assert_eq!(stack.spill.capacity(), 0);
+
+        let stack: SmallStack<u8, 4> = SmallStack::with_capacity(10);
+        assert!(stack.spill.capacity() >= 6);
+    }
+}
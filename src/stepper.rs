@@ -0,0 +1,1143 @@
+// The MIT License (MIT)
+//
+// Copyright © 2022 <Brandon Lewis>
+//
+// Permission is hereby granted, free of charge, to any person
+// obtaining a copy of this software and associated documentation
+// files (the “Software”), to deal in the Software without
+// restriction, including without limitation the rights to use, copy,
+// modify, merge, publish, distribute, sublicense, and/or sell copies
+// of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS
+// BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN
+// ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+// Fork this project to create your own MIT license that you can
+// always link to.
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use core::fmt::Display;
+use crate::trs::{SigmaRules, Types};
+use crate::expr::{Expr, Dir, Path, Reduction, ReduceError};
+use crate::latex;
+use crate::names::{free_vars, Fresh, NameSupply};
+
+
+/*
+ * Single-step reduction of lambda expressions.
+ *
+ * `Expr::reduce()` only contracts the redex at the root. A `Stepper`
+ * owns a term and a strategy, and drives reduction one redex at a
+ * time anywhere in the tree, reporting where each step happened. It
+ * is meant as the engine under interactive front-ends (a REPL
+ * `:step`, a GUI), which is why it exposes the individual redexes
+ * rather than just a `normalize()`.
+ *
+ * Binders renamed to avoid capture get their names from one
+ * `NameSupply` for the whole run, so they are numbered in the order
+ * the steps happened.
+ */
+
+
+/**
+ * A strategy picks the next redex to contract in an expression.
+ *
+ * This is the `Expr` counterpart to `grs::Strategy`. Returning `None`
+ * means the strategy considers the expression to be in normal form.
+ */
+pub trait Strategy<T: Types> {
+    fn next_redex(&mut self, expr: &Expr<T>) -> Option<Path>;
+}
+
+
+/**
+ * Leftmost-outermost reduction.
+ *
+ * Finds the normal form whenever one exists, at the cost of possibly
+ * duplicating unevaluated arguments.
+ */
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NormalOrder;
+
+impl<T: Types + Clone> Strategy<T> for NormalOrder {
+    fn next_redex(&mut self, expr: &Expr<T>) -> Option<Path> {
+        expr.redexes().into_iter().next()
+    }
+}
+
+
+/**
+ * Leftmost-innermost reduction.
+ *
+ * Arguments are reduced before they are substituted, so this can
+ * diverge on terms that have a normal form.
+ */
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ApplicativeOrder;
+
+impl<T: Types + Clone> Strategy<T> for ApplicativeOrder {
+    fn next_redex(&mut self, expr: &Expr<T>) -> Option<Path> {
+        // `redexes()` lists a redex immediately before any redexes
+        // nested within it, so the first one not followed by one of
+        // its own descendants is the leftmost innermost.
+        let redexes = expr.redexes();
+        let mut iter = redexes.iter().peekable();
+        while let Some(path) = iter.next() {
+            match iter.peek() {
+                Some(next) if next.starts_with(path) => continue,
+                _ => return Some(path.clone())
+            }
+        }
+        None
+    }
+}
+
+
+/**
+ * A record of one reduction step.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Step {
+    // Steps are numbered from 1.
+    pub number: usize,
+    pub path: Path,
+    pub reduction: Reduction,
+    // Only filled in when explanations are enabled.
+    pub explanation: Option<Explanation>
+}
+
+
+/**
+ * A description of a reduction step, for teaching front-ends.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Explanation {
+    pub english: String,
+    // LaTeX text, with terms set in inline math.
+    pub latex: String
+}
+
+
+/**
+ * Explain the contraction of `redex` to `contractum`.
+ *
+ * E.g. "beta-reduce: substitute `0` for `x` in `λy.x`". Returns
+ * `None` if `redex` isn't a redex.
+ */
+pub fn explain<T>(redex: &Expr<T>, contractum: &Expr<T>) -> Option<Explanation>
+where T: Types + Clone, T::Sym: Display, T::Val: Display {
+    let (f, x) = match redex {
+        Expr::App(f, x) => (f, x),
+        _               => return None
+    };
+
+    match redex.redex()? {
+        Reduction::Beta => match &**f {
+            Expr::Lambda(a, body) => Some(Explanation {
+                english: format!(
+                    "beta-reduce: substitute `{}` for `{}` in `{}`",
+                    x, a, body
+                ),
+                latex: format!(
+                    "$\\beta$-reduce: substitute ${}$ for ${}$ in ${}$",
+                    x.to_latex(), latex::ident(&a.to_string()), body.to_latex()
+                )
+            }),
+            _ => None
+        },
+        Reduction::Sigma => Some(Explanation {
+            english: format!(
+                "sigma-reduce: apply `{}` to `{}`, giving `{}`",
+                f, x, contractum
+            ),
+            latex: format!(
+                "$\\sigma$-reduce: apply ${}$ to ${}$, giving ${}$",
+                f.to_latex(), x.to_latex(), contractum.to_latex()
+            )
+        }),
+        Reduction::Select => match &**f {
+            Expr::App(b, t) => {
+                let (taken, dropped) = match &**b {
+                    Expr::Val(v) if v.select()? => (t, x),
+                    _                           => (x, t)
+                };
+                Some(Explanation {
+                    english: format!(
+                        "select: `{}`, so take `{}` over `{}`",
+                        b, taken, dropped
+                    ),
+                    latex: format!(
+                        "select: ${}$, so take ${}$ over ${}$",
+                        b.to_latex(), taken.to_latex(), dropped.to_latex()
+                    )
+                })
+            },
+            _ => None
+        }
+    }
+}
+
+
+/**
+ * A condition under which `Stepper::run()` pauses.
+ *
+ * Breakpoints are checked against the redex the strategy is about to
+ * contract, before it is contracted.
+ *
+ * Names don't survive substitution, so there is no breakpoint on each
+ * use of a named definition. `Binder("fib")` pauses where `fib` gets
+ * its definition: contracting `(λfib.body) def`, which is how `let`,
+ * scheme's `define` and `CodeStore` dependencies bind names. It fires
+ * once per such binding, not each time the copies of `def` in `body`
+ * are later applied.
+ */
+pub enum Breakpoint<T: Types> {
+    // Any redex of the given kind
+    Kind(Reduction),
+    // A beta redex whose lambda binds this symbol
+    Binder(T::Sym),
+    // Any redex satisfying the predicate
+    Matches(Box<Predicate<T>>)
+}
+
+/**
+ * The test of `Breakpoint::Matches`. It may capture a pattern.
+ *
+ * This is a trait object rather than a type parameter because a
+ * stepper holds a list of breakpoints, and each `Matches` has its own
+ * closure type. Predicates are only called by `run()`, once per step,
+ * so the indirect call costs little next to the step itself.
+ */
+pub type Predicate<T> = dyn Fn(&Expr<T>) -> bool + Send + Sync;
+
+impl<T: Types> Breakpoint<T> {
+    /* A `Matches` breakpoint testing `predicate`. */
+    pub fn matches<F>(predicate: F) -> Self
+    where F: Fn(&Expr<T>) -> bool + Send + Sync + 'static {
+        Breakpoint::Matches(Box::new(predicate))
+    }
+
+    fn hit(&self, redex: &Expr<T>) -> bool where T: Clone {
+        match self {
+            Self::Kind(kind)  => redex.redex() == Some(*kind),
+            Self::Binder(sym) => match redex {
+                Expr::App(f, _) => matches!(&**f, Expr::Lambda(a, _) if a == sym),
+                _               => false
+            },
+            Self::Matches(predicate) => predicate(redex)
+        }
+    }
+}
+
+
+/**
+ * Why `Stepper::run()` returned.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub enum Stop {
+    NormalForm,
+    OutOfFuel,
+    // The redex at `path` hit the breakpoint with this index.
+    Breakpoint {index: usize, path: Path},
+    // `Progress::cancel()` was called.
+    Cancelled
+}
+
+
+/**
+ * A handle for watching and cancelling a `Stepper` from elsewhere.
+ *
+ * A `Stepper` is `Send` whenever its term, strategy and symbols are,
+ * so it can be moved to a worker thread and driven there. Give it an
+ * `Arc<Progress>` with `Stepper::with_progress()`, and keep a clone:
+ * the stepper publishes its step count there, and `run()` returns
+ * `Stop::Cancelled` soon after `cancel()` is called. Cancellation is
+ * sticky; use a fresh `Progress` to run again.
+ */
+#[derive(Debug, Default)]
+pub struct Progress {
+    steps: AtomicUsize,
+    cancelled: AtomicBool
+}
+
+impl Progress {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+
+    /* The step count of the stepper, as of its last step. */
+    pub fn steps(&self) -> usize {
+        self.steps.load(Ordering::Relaxed)
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+
+/* Signature of `explain()`, so a stepper can hold it. */
+type Explainer<T> = fn(&Expr<T>, &Expr<T>) -> Option<Explanation>;
+
+
+/**
+ * What a stepper does with variables that are free in its term.
+ *
+ * The policy is applied once, before the first step is taken, so it
+ * sees the free variables of the term the stepper was created with.
+ */
+pub enum FreeVars<T: Types> {
+    // Leave them be. An application headed by one is stuck, and is
+    // part of the normal form.
+    Neutral,
+    // Fail with `ReduceError::FreeVariable` on the first one.
+    Error,
+    // Replace each free variable by what the hook returns for its
+    // name, or leave it neutral if the hook returns `None`. The hook
+    // is called once per name, and what it returns is inserted as is,
+    // so it should be closed.
+    Resolve(Box<Resolver<T>>)
+}
+
+/**
+ * The hook of `FreeVars::Resolve`. It may capture an environment.
+ *
+ * This is a trait object rather than a type parameter because it is
+ * only called while settling the term, once per free variable, before
+ * the first step; it is never on the stepping path. A parameter would
+ * add a third type parameter to every `Stepper` to save nothing.
+ */
+pub type Resolver<T> = dyn Fn(&<T as Types>::Sym) -> Option<Expr<T>> + Send + Sync;
+
+impl<T: Types> FreeVars<T> {
+    /* A `Resolve` policy calling `hook`. */
+    pub fn resolve<F>(hook: F) -> Self
+    where F: Fn(&T::Sym) -> Option<Expr<T>> + Send + Sync + 'static {
+        FreeVars::Resolve(Box::new(hook))
+    }
+}
+
+
+/*
+ * What we need to undo one step: the redex that was contracted, and
+ * where it was. Restoring it is a matter of putting it back at the
+ * same path, so we never have to keep a copy of the whole term.
+ */
+struct Undo<T: Types> {
+    step: Step,
+    redex: Expr<T>
+}
+
+
+pub struct Stepper<T, S> where T: Types, S: Strategy<T> {
+    term: Box<Expr<T>>,
+    strategy: S,
+    steps: usize,
+    // The number of nodes in `term`, kept up to date as we go.
+    size: usize,
+    size_limit: Option<usize>,
+    // Oldest first, at most `history_limit` long.
+    history: VecDeque<Undo<T>>,
+    history_limit: usize,
+    // Steps undone by `step_back()`, most recently undone last.
+    redo: Vec<Step>,
+    breakpoints: Vec<Breakpoint<T>>,
+    // Set when `run()` stops at a breakpoint, so that the next
+    // `run()` steps over it rather than stopping again immediately.
+    paused: bool,
+    progress: Option<Arc<Progress>>,
+    // Set by `with_explanations()`, where `Display` is available.
+    explain: Option<Explainer<T>>,
+    free_vars: FreeVars<T>,
+    // Whether `free_vars` has been applied to the term yet.
+    settled: bool,
+    // Knows every name in `term`, so renamed binders never collide.
+    names: NameSupply<T::Sym>
+}
+
+
+impl<T, S> Stepper<T, S> where T: Types + Clone, T::Sym: Fresh, S: Strategy<T> {
+    pub fn new(term: Box<Expr<T>>, strategy: S) -> Self {
+        let mut names = NameSupply::new();
+        names.avoid_in(&term);
+        Stepper {
+            size: term.size(),
+            size_limit: None,
+            term,
+            strategy,
+            steps: 0,
+            history: VecDeque::new(),
+            history_limit: 0,
+            redo: Vec::new(),
+            breakpoints: Vec::new(),
+            paused: false,
+            progress: None,
+            explain: None,
+            free_vars: FreeVars::Neutral,
+            settled: false,
+            names
+        }
+    }
+
+    /**
+     * Set the policy for free variables. The default is `Neutral`.
+     *
+     * Panics if the stepper has already stepped, since the policy has
+     * been applied by then.
+     */
+    pub fn with_free_vars(self, policy: FreeVars<T>) -> Self {
+        assert!(!self.settled, "free variable policy set after stepping started");
+        Stepper {free_vars: policy, ..self}
+    }
+
+    /**
+     * Retain enough history to undo the last `limit` steps.
+     *
+     * Each entry holds only the contracted redex, not a copy of the
+     * term. History is off (zero) by default.
+     */
+    pub fn with_history(self, limit: usize) -> Self {
+        Stepper {history_limit: limit, ..self}
+    }
+
+    pub fn term(&self) -> &Expr<T> {
+        &self.term
+    }
+
+    pub fn into_term(self) -> Box<Expr<T>> {
+        self.term
+    }
+
+    /* The number of steps taken so far. */
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    /**
+     * Refuse any step that would grow the term past `limit` nodes,
+     * failing with `ReduceError::HeapLimit` instead.
+     *
+     * This guards against terms whose normal form is exponentially
+     * larger than the term itself. The offending step is not taken.
+     */
+    pub fn with_size_limit(self, limit: usize) -> Self {
+        Stepper {size_limit: Some(limit), ..self}
+    }
+
+    /* The number of nodes in the current term. */
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /* Publish progress to, and accept cancellation from, `progress`. */
+    pub fn with_progress(self, progress: Arc<Progress>) -> Self {
+        progress.steps.store(self.steps, Ordering::Relaxed);
+        Stepper {progress: Some(progress), ..self}
+    }
+
+    /* Add a breakpoint, returning the index `Stop` will report. */
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint<T>) -> usize {
+        self.breakpoints.push(breakpoint);
+        self.breakpoints.len() - 1
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+        self.paused = false;
+    }
+
+    /* Paths to every redex in the current term, leftmost-outermost
+     * first, regardless of which one the strategy would pick. */
+    pub fn remaining_redexes(&self) -> Vec<Path> {
+        self.term.redexes()
+    }
+
+    /**
+     * Contract the redex chosen by the strategy.
+     *
+     * Returns `Ok(None)` once the strategy finds no more redexes.
+     */
+    pub fn step_forward(&mut self) -> Result<Option<Step>, ReduceError<T>> {
+        self.settle()?;
+        match self.strategy.next_redex(&self.term) {
+            Some(path) => self.step_into(&path).map(Some),
+            None       => Ok(None)
+        }
+    }
+
+    /**
+     * Contract the redex at `path`, overriding the strategy.
+     *
+     * On error the term is left unchanged.
+     */
+    pub fn step_into(&mut self, path: &[Dir]) -> Result<Step, ReduceError<T>> {
+        self.settle()?;
+        let step = self.contract(path)?;
+        self.redo.clear();
+        self.paused = false;
+        Ok(step)
+    }
+
+    fn contract(&mut self, path: &[Dir]) -> Result<Step, ReduceError<T>> {
+        let (reduction, contractum) = self.term.contractum_at_with(path, &mut self.names)?;
+        let node = self.term.at_mut(path).expect("path checked above");
+        let size = self.size - node.size() + contractum.size();
+
+        if self.size_limit.is_some_and(|limit| size > limit) {
+            return Err(ReduceError::HeapLimit);
+        }
+
+        let explanation = self.explain.and_then(|explain| explain(node, &contractum));
+        let redex = core::mem::replace(node, *contractum);
+        self.size = size;
+        self.steps += 1;
+        self.publish();
+        let step = Step {number: self.steps, path: path.to_vec(), reduction, explanation};
+
+        if self.history_limit > 0 {
+            if self.history.len() == self.history_limit {
+                self.history.pop_front();
+            }
+            self.history.push_back(Undo {step: step.clone(), redex});
+        }
+
+        Ok(step)
+    }
+
+    /**
+     * Undo the most recent step, returning it.
+     *
+     * Returns `None` when there is no history left to undo.
+     */
+    pub fn step_back(&mut self) -> Option<Step> {
+        let undo = self.history.pop_back()?;
+        let node = self.term.at_mut(&undo.step.path).expect("history out of sync");
+        self.size = self.size - node.size() + undo.redex.size();
+        *node = undo.redex;
+        self.steps -= 1;
+        self.publish();
+        self.redo.push(undo.step.clone());
+        Some(undo.step)
+    }
+
+    /**
+     * Move to the state after step `n`.
+     *
+     * Going backwards is limited by the retained history. Going
+     * forwards first replays any undone steps, then follows the
+     * strategy, stopping early at a normal form. Returns the number of
+     * the step actually reached.
+     */
+    pub fn jump_to(&mut self, n: usize) -> Result<usize, ReduceError<T>> {
+        while self.steps > n && self.step_back().is_some() {}
+
+        while self.steps < n {
+            if let Some(step) = self.redo.pop() {
+                self.contract(&step.path)?;
+            } else if self.step_forward()?.is_none() {
+                break;
+            }
+        }
+
+        Ok(self.steps)
+    }
+
+    /**
+     * Step until the strategy finds no more redexes, `fuel` steps have
+     * been taken, or the next redex hits a breakpoint.
+     *
+     * When resuming after a breakpoint, the redex that triggered it is
+     * contracted without checking breakpoints again.
+     */
+    pub fn run(&mut self, fuel: usize) -> Result<Stop, ReduceError<T>> {
+        self.settle()?;
+        let start = self.steps;
+        let mut resuming = self.paused;
+
+        loop {
+            let path = match self.strategy.next_redex(&self.term) {
+                Some(path) => path,
+                None       => return Ok(Stop::NormalForm)
+            };
+
+            if self.steps - start >= fuel {
+                return Ok(Stop::OutOfFuel);
+            }
+
+            if self.progress.as_ref().is_some_and(|p| p.is_cancelled()) {
+                return Ok(Stop::Cancelled);
+            }
+
+            if !resuming {
+                if let Some(index) = self.breakpoint_at(&path) {
+                    self.paused = true;
+                    return Ok(Stop::Breakpoint {index, path});
+                }
+            }
+
+            resuming = false;
+            self.step_into(&path)?;
+        }
+    }
+
+    /**
+     * Like `run()`, but yields to the async executor after every
+     * `yield_every` steps, so that a long reduction doesn't hog the
+     * worker it is polled on.
+     *
+     * Requires the `async` feature. No particular runtime is assumed:
+     * yielding just wakes the task and returns `Pending` once.
+     */
+    #[cfg(feature = "async")]
+    pub async fn normalize_async(
+        &mut self,
+        fuel: usize,
+        yield_every: usize
+    ) -> Result<Stop, ReduceError<T>> {
+        let mut remaining = fuel;
+        loop {
+            let chunk = remaining.min(yield_every.max(1));
+            let start = self.steps;
+            match self.run(chunk)? {
+                Stop::OutOfFuel if remaining > chunk => {
+                    remaining -= self.steps - start;
+                    YieldNow(false).await;
+                },
+                stop => return Ok(stop)
+            }
+        }
+    }
+
+    /* Apply the free variable policy, if we haven't yet. */
+    fn settle(&mut self) -> Result<(), ReduceError<T>> {
+        if self.settled {
+            return Ok(());
+        }
+        match &self.free_vars {
+            FreeVars::Neutral => (),
+            FreeVars::Error => if let Some(v) = free_vars(&self.term).into_iter().next() {
+                return Err(ReduceError::FreeVariable(v));
+            },
+            FreeVars::Resolve(hook) => {
+                let defs: Vec<_> = free_vars(&self.term)
+                    .into_iter()
+                    .filter_map(|v| hook(&v).map(|e| (v, e)))
+                    .collect();
+                if !defs.is_empty() {
+                    *self.term = self.term.replace_free(|v| {
+                        defs.iter().find(|(name, _)| name == v).map(|(_, e)| e.clone())
+                    });
+                    self.size = self.term.size();
+                    self.names.avoid_in(&self.term);
+                }
+            }
+        }
+        self.settled = true;
+        Ok(())
+    }
+
+    fn publish(&self) {
+        if let Some(progress) = &self.progress {
+            progress.steps.store(self.steps, Ordering::Relaxed);
+        }
+    }
+
+    fn breakpoint_at(&self, path: &[Dir]) -> Option<usize> {
+        let redex = self.term.at(path)?;
+        self.breakpoints.iter().position(|b| b.hit(redex))
+    }
+}
+
+
+impl<T, S> Stepper<T, S>
+where T: Types + Clone,
+      T::Sym: Display,
+      T::Val: Display,
+      S: Strategy<T>
+{
+    /* Attach an `Explanation` to every step from now on. */
+    pub fn with_explanations(self) -> Self {
+        Stepper {explain: Some(explain::<T>), ..self}
+    }
+}
+
+
+/* A future which is pending exactly once. */
+#[cfg(feature = "async")]
+struct YieldNow(bool);
+
+#[cfg(feature = "async")]
+impl core::future::Future for YieldNow {
+    type Output = ();
+
+    fn poll(
+        mut self: core::pin::Pin<&mut Self>,
+        cx: &mut core::task::Context<'_>
+    ) -> core::task::Poll<()> {
+        if self.0 {
+            core::task::Poll::Ready(())
+        } else {
+            self.0 = true;
+            cx.waker().wake_by_ref();
+            core::task::Poll::Pending
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trs::SigmaRules;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct StepTypes;
+
+    #[derive(Clone, Debug, PartialEq)]
+    enum Val {
+        Int(i32),
+        Succ
+    }
+
+    impl SigmaRules for Val {
+        type Error = ();
+
+        fn apply(f: Self, x: Self) -> Result<Self, ()> {
+            match (f, x) {
+                (Val::Succ, Val::Int(i)) => Ok(Val::Int(i + 1)),
+                _                        => Err(())
+            }
+        }
+    }
+
+    impl Display for Val {
+        fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            match self {
+                Val::Int(i) => write!(f, "{}", i),
+                Val::Succ   => write!(f, "Succ")
+            }
+        }
+    }
+
+    impl Types for StepTypes {
+        type Val = Val;
+        type Sym = String;
+    }
+
+    type E = Expr<StepTypes>;
+    use Dir::*;
+
+    // (\x.Succ x) (Succ 0)
+    fn example() -> Box<E> {
+        E::apply(
+            E::lambda("x", E::apply(E::val(Val::Succ), E::var("x"))),
+            E::apply(E::val(Val::Succ), E::val(Val::Int(0)))
+        )
+    }
+
+    #[test]
+    fn test_redexes() {
+        assert_eq!(example().redexes(), vec![vec![], vec![Arg]]);
+        assert_eq!(example().redex(), Some(Reduction::Beta));
+        assert_eq!(example().at(&[Arg]).unwrap().redex(), Some(Reduction::Sigma));
+        assert_eq!(example().at(&[Arg, Body]), None);
+    }
+
+    #[test]
+    fn test_normal_order() {
+        let mut s = Stepper::new(example(), NormalOrder);
+        assert_eq!(
+            s.step_forward().unwrap(),
+            Some(Step {
+                number: 1,
+                path: vec![],
+                reduction: Reduction::Beta,
+                explanation: None
+            })
+        );
+        assert_eq!(
+            s.remaining_redexes(),
+            vec![vec![Arg]]
+        );
+        assert_eq!(s.run(10).unwrap(), Stop::NormalForm);
+        assert_eq!(s.step_forward().unwrap(), None);
+        assert_eq!(s.steps(), 3);
+        assert_eq!(s.into_term(), E::val(Val::Int(2)));
+    }
+
+    #[test]
+    fn test_applicative_order() {
+        let mut s = Stepper::new(example(), ApplicativeOrder);
+        assert_eq!(
+            s.step_forward().unwrap(),
+            Some(Step {
+                number: 1,
+                path: vec![Arg],
+                reduction: Reduction::Sigma,
+                explanation: None
+            })
+        );
+        assert_eq!(s.run(10).unwrap(), Stop::NormalForm);
+        assert_eq!(s.steps(), 3);
+        assert_eq!(s.into_term(), E::val(Val::Int(2)));
+    }
+
+    fn church(n: usize) -> Box<E> {
+        E::lambda("f", E::lambda("x", (0..n).fold(
+            E::var("x"),
+            |body, _| E::apply(E::var("f"), body)
+        )))
+    }
+
+    #[test]
+    fn test_duplicated_lambda() {
+        // 2 2 copies \f.\x.f (f x) under its own binders, so a copy of
+        // `x` has to be renamed on the way to 4.
+        let mut s = Stepper::new(E::apply(church(2), church(2)), NormalOrder);
+        assert_eq!(s.run(100).unwrap(), Stop::NormalForm);
+        assert!(s.term().alpha_eq(&church(4)));
+
+        let four = || E::apply(
+            E::apply(E::apply(church(2), church(2)), E::val(Val::Succ)),
+            E::val(Val::Int(0))
+        );
+        let mut s = Stepper::new(four(), NormalOrder);
+        assert_eq!(s.run(100).unwrap(), Stop::NormalForm);
+        assert_eq!(s.into_term(), E::val(Val::Int(4)));
+        let mut s = Stepper::new(four(), ApplicativeOrder);
+        assert_eq!(s.run(100).unwrap(), Stop::NormalForm);
+        assert_eq!(s.into_term(), E::val(Val::Int(4)));
+    }
+
+    #[test]
+    fn test_step_into() {
+        let mut s = Stepper::new(example(), NormalOrder);
+        assert_eq!(s.step_into(&[Arg]).unwrap().reduction, Reduction::Sigma);
+        assert!(matches!(s.step_into(&[Arg]), Err(ReduceError::NotApplicable)));
+        assert!(matches!(s.step_into(&[Body]), Err(ReduceError::InvalidPath)));
+        assert_eq!(s.steps(), 1);
+    }
+
+    #[test]
+    fn test_step_back() {
+        let mut s = Stepper::new(example(), NormalOrder).with_history(10);
+        s.run(10).unwrap();
+        assert_eq!(s.term(), &*E::val(Val::Int(2)));
+
+        assert_eq!(s.step_back().map(|step| step.number), Some(3));
+        assert_eq!(s.step_back().map(|step| step.number), Some(2));
+        assert_eq!(s.steps(), 1);
+        assert_eq!(s.term(), &*E::apply(
+            E::val(Val::Succ),
+            E::apply(E::val(Val::Succ), E::val(Val::Int(0)))
+        ));
+
+        assert_eq!(s.step_back().map(|step| step.number), Some(1));
+        assert_eq!(s.step_back(), None);
+        assert_eq!(s.term(), &*example());
+    }
+
+    #[test]
+    fn test_jump_to() {
+        let mut s = Stepper::new(example(), ApplicativeOrder).with_history(2);
+        assert_eq!(s.jump_to(10).unwrap(), 3);
+        assert_eq!(s.jump_to(0).unwrap(), 1);
+
+        // Replaying the undone steps gets us back where we were.
+        assert_eq!(s.jump_to(2).unwrap(), 2);
+        assert_eq!(s.remaining_redexes(), vec![vec![]]);
+        assert_eq!(s.jump_to(3).unwrap(), 3);
+        assert_eq!(s.term(), &*E::val(Val::Int(2)));
+    }
+
+    #[test]
+    fn test_new_step_discards_redo() {
+        // (\x.x) ((\y.y) 0)
+        let term = E::apply(
+            E::lambda("x", E::var("x")),
+            E::apply(E::lambda("y", E::var("y")), E::val(Val::Int(0)))
+        );
+        let mut s = Stepper::new(term, NormalOrder).with_history(5);
+        s.step_forward().unwrap();
+        s.step_back();
+        s.step_into(&[Arg]).unwrap();
+        assert_eq!(s.jump_to(2).unwrap(), 2);
+        assert_eq!(s.term(), &*E::val(Val::Int(0)));
+        assert_eq!(s.step_back().map(|step| step.path), Some(vec![]));
+        assert_eq!(s.step_back().map(|step| step.path), Some(vec![Arg]));
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        let mut s = Stepper::new(example(), NormalOrder);
+        assert_eq!(s.add_breakpoint(Breakpoint::Binder("y".into())), 0);
+        assert_eq!(s.add_breakpoint(Breakpoint::Kind(Reduction::Sigma)), 1);
+
+        assert_eq!(s.run(10).unwrap(), Stop::Breakpoint {index: 1, path: vec![Arg]});
+        assert_eq!(s.steps(), 1);
+
+        // Resuming steps over the breakpoint we stopped at, but not the
+        // next one.
+        assert_eq!(s.run(10).unwrap(), Stop::Breakpoint {index: 1, path: vec![]});
+        assert_eq!(s.steps(), 2);
+
+        s.clear_breakpoints();
+        assert_eq!(s.run(10).unwrap(), Stop::NormalForm);
+    }
+
+    #[test]
+    fn test_binder_fires_once_per_binding() {
+        // let f = \n.Succ n in f (f 0)
+        let term = E::apply(
+            E::lambda("f", E::apply(E::var("f"), E::apply(E::var("f"), E::val(Val::Int(0))))),
+            E::lambda("n", E::apply(E::val(Val::Succ), E::var("n")))
+        );
+        let mut s = Stepper::new(term, NormalOrder);
+        s.add_breakpoint(Breakpoint::Binder("f".into()));
+        assert_eq!(s.run(10).unwrap(), Stop::Breakpoint {index: 0, path: vec![]});
+        assert_eq!(s.run(10).unwrap(), Stop::NormalForm);
+        assert_eq!(s.into_term(), E::val(Val::Int(2)));
+    }
+
+    #[test]
+    fn test_breakpoint_predicate() {
+        // Succ applied to the captured pattern.
+        let arg = Expr::Val(Val::Int(0));
+        let succ_of = move |redex: &E| matches!(redex, Expr::App(_, x) if **x == arg);
+
+        let mut s = Stepper::new(example(), NormalOrder);
+        s.add_breakpoint(Breakpoint::Binder("x".into()));
+        s.add_breakpoint(Breakpoint::matches(succ_of));
+        assert_eq!(s.run(10).unwrap(), Stop::Breakpoint {index: 0, path: vec![]});
+        assert_eq!(s.run(10).unwrap(), Stop::Breakpoint {index: 1, path: vec![Arg]});
+        assert_eq!(s.run(10).unwrap(), Stop::NormalForm);
+    }
+
+    #[test]
+    fn test_size_limit() {
+        // (\x.x x x) applied to itself triples the term every step.
+        let triple = || E::lambda("x", E::apply(
+            E::apply(E::var("x"), E::var("x")),
+            E::var("x")
+        ));
+        let term = E::apply(triple(), triple());
+        assert_eq!(term.size(), 13);
+
+        let mut s = Stepper::new(term, NormalOrder)
+            .with_size_limit(100)
+            .with_history(10);
+        assert!(matches!(s.run(1000), Err(ReduceError::HeapLimit)));
+        assert!(s.size() <= 100);
+        assert_eq!(s.size(), s.term().size());
+
+        let steps = s.steps();
+        s.step_back();
+        assert_eq!(s.steps(), steps - 1);
+        assert_eq!(s.size(), s.term().size());
+    }
+
+    #[test]
+    fn test_explanations() {
+        let mut s = Stepper::new(example(), NormalOrder).with_explanations();
+
+        let beta = s.step_forward().unwrap().unwrap().explanation.unwrap();
+        assert_eq!(
+            beta.english,
+            "beta-reduce: substitute `Succ 0` for `x` in `Succ x`"
+        );
+        assert_eq!(
+            beta.latex,
+            "$\\beta$-reduce: substitute $\\mathit{Succ}\\;0$ for $x$ in $\\mathit{Succ}\\;x$"
+        );
+
+        let sigma = s.step_forward().unwrap().unwrap().explanation.unwrap();
+        assert_eq!(sigma.english, "sigma-reduce: apply `Succ` to `0`, giving `1`");
+        assert_eq!(
+            sigma.latex,
+            "$\\sigma$-reduce: apply $\\mathit{Succ}$ to $0$, giving $1$"
+        );
+    }
+
+    #[test]
+    fn test_trace_snapshot() {
+        // (\f.\x.f (f x)) Succ 0, one step per paragraph.
+        let twice = E::lambda("f", E::lambda("x", E::apply(
+            E::var("f"),
+            E::apply(E::var("f"), E::var("x"))
+        )));
+        let term = E::apply(E::apply(twice, E::val(Val::Succ)), E::val(Val::Int(0)));
+        let mut s = Stepper::new(term, NormalOrder).with_explanations();
+
+        let mut trace = format!("{}\n", s.term());
+        while let Some(step) = s.step_forward().unwrap() {
+            let explanation = step.explanation.unwrap();
+            trace.push_str(&format!(
+                "\n{} {:?} at {:?}\n{}\n{}\n{}\n",
+                step.number, step.reduction, step.path,
+                explanation.english, explanation.latex, s.term()
+            ));
+        }
+        crate::snapshot::Snapshots::in_package().assert("stepper_trace", &trace);
+    }
+
+    #[test]
+    fn test_run_out_of_fuel() {
+        // (\x.x x) (\x.x x) reduces to itself forever.
+        let omega = || E::lambda("x", E::apply(E::var("x"), E::var("x")));
+        let mut s = Stepper::new(E::apply(omega(), omega()), NormalOrder);
+        assert_eq!(s.run(5).unwrap(), Stop::OutOfFuel);
+        assert_eq!(s.steps(), 5);
+        assert_eq!(s.term(), &*E::apply(omega(), omega()));
+    }
+
+    #[test]
+    fn test_neutral_terms() {
+        // \y.(\x.f x (Succ x)) (g y) normalizes to a neutral term.
+        let term = || E::lambda("y", E::apply(
+            E::lambda("x", E::apply(
+                E::apply(E::var("f"), E::var("x")),
+                E::apply(E::val(Val::Succ), E::var("x"))
+            )),
+            E::apply(E::var("g"), E::var("y"))
+        ));
+        let normal = E::lambda("y", E::apply(
+            E::apply(E::var("f"), E::apply(E::var("g"), E::var("y"))),
+            E::apply(E::val(Val::Succ), E::apply(E::var("g"), E::var("y")))
+        ));
+
+        let mut s = Stepper::new(term(), NormalOrder);
+        assert_eq!(s.run(10).unwrap(), Stop::NormalForm);
+        assert_eq!(s.term(), &*normal);
+
+        let mut s = Stepper::new(term(), ApplicativeOrder);
+        assert_eq!(s.run(10).unwrap(), Stop::NormalForm);
+        assert_eq!(s.term(), &*normal);
+    }
+
+    #[test]
+    fn test_free_vars() {
+        // (\x.f x) (Succ one), with f and one free.
+        let term = || E::apply(
+            E::lambda("x", E::apply(E::var("f"), E::var("x"))),
+            E::apply(E::val(Val::Succ), E::var("one"))
+        );
+
+        let mut s = Stepper::new(term(), NormalOrder);
+        assert_eq!(s.run(10).unwrap(), Stop::NormalForm);
+        assert_eq!(s.term(), &*E::apply(
+            E::var("f"),
+            E::apply(E::val(Val::Succ), E::var("one"))
+        ));
+
+        let mut s = Stepper::new(term(), NormalOrder).with_free_vars(FreeVars::Error);
+        assert!(matches!(s.run(10), Err(ReduceError::FreeVariable(v)) if v == "f"));
+        assert_eq!(s.steps(), 0);
+
+        // The resolver can close over an environment.
+        let env = [("one".to_string(), Expr::Val(Val::Int(1)))];
+        let resolve = move |name: &String| {
+            env.iter().find(|(n, _)| n == name).map(|(_, e)| e.clone())
+        };
+        let mut s = Stepper::new(term(), NormalOrder)
+            .with_free_vars(FreeVars::resolve(resolve));
+        assert_eq!(s.run(10).unwrap(), Stop::NormalForm);
+        assert_eq!(s.term(), &*E::apply(E::var("f"), E::val(Val::Int(2))));
+        assert_eq!(s.size(), 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "free variable policy set after stepping started")]
+    fn test_free_vars_after_stepping() {
+        let mut s = Stepper::new(example(), NormalOrder);
+        s.step_forward().unwrap();
+        s.with_free_vars(FreeVars::Error);
+    }
+
+    fn assert_send_sync<X: Send + Sync>() {}
+
+    #[test]
+    fn test_send_sync() {
+        assert_send_sync::<E>();
+        assert_send_sync::<Stepper<StepTypes, NormalOrder>>();
+        assert_send_sync::<Progress>();
+    }
+
+    #[test]
+    fn test_cancel_from_another_thread() {
+        let omega = || E::lambda("x", E::apply(E::var("x"), E::var("x")));
+        let progress = Progress::new();
+        let stepper = Stepper::new(E::apply(omega(), omega()), NormalOrder)
+            .with_progress(progress.clone());
+
+        let worker = std::thread::spawn(move || {
+            let mut stepper = stepper;
+            let stop = stepper.run(usize::MAX).unwrap();
+            (stop, stepper.steps())
+        });
+
+        while progress.steps() < 100 {
+            std::thread::yield_now();
+        }
+        progress.cancel();
+
+        let (stop, steps) = worker.join().unwrap();
+        assert_eq!(stop, Stop::Cancelled);
+        assert!(steps >= 100);
+        assert_eq!(progress.steps(), steps);
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_normalize_async_yields() {
+        use core::future::Future;
+        use core::task::{Context, Poll, Waker};
+        use std::task::Wake;
+
+        struct Noop;
+        impl Wake for Noop {
+            fn wake(self: Arc<Self>) {}
+        }
+
+        // Poll to completion on this thread, counting the yields.
+        fn block_on<F: Future>(future: F) -> (F::Output, usize) {
+            let waker = Waker::from(Arc::new(Noop));
+            let mut cx = Context::from_waker(&waker);
+            let mut future = Box::pin(future);
+            let mut pending = 0;
+            loop {
+                match future.as_mut().poll(&mut cx) {
+                    Poll::Ready(out) => return (out, pending),
+                    Poll::Pending    => pending += 1
+                }
+            }
+        }
+
+        let omega = || E::lambda("x", E::apply(E::var("x"), E::var("x")));
+        let mut s = Stepper::new(E::apply(omega(), omega()), NormalOrder);
+        let (stop, yields) = block_on(s.normalize_async(25, 10));
+        assert_eq!(stop.unwrap(), Stop::OutOfFuel);
+        assert_eq!(yields, 2);
+        assert_eq!(s.steps(), 25);
+
+        let mut s = Stepper::new(example(), NormalOrder);
+        let (stop, yields) = block_on(s.normalize_async(100, 1));
+        assert_eq!(stop.unwrap(), Stop::NormalForm);
+        assert_eq!(yields, 2);
+        assert_eq!(s.steps(), 3);
+    }
+}
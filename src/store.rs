@@ -0,0 +1,740 @@
+// The MIT License (MIT)
+//
+// Copyright © 2022 <Brandon Lewis>
+//
+// Permission is hereby granted, free of charge, to any person
+// obtaining a copy of this software and associated documentation
+// files (the “Software”), to deal in the Software without
+// restriction, including without limitation the rights to use, copy,
+// modify, merge, publish, distribute, sublicense, and/or sell copies
+// of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS
+// BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN
+// ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+// Fork this project to create your own MIT license that you can
+// always link to.
+use core::fmt;
+use core::hash::{Hash as StdHash, Hasher};
+use std::collections::BTreeMap;
+use crate::trs::Types;
+use crate::expr::{Expr, ReduceError};
+use crate::stepper::{Stepper, NormalOrder};
+use crate::names::Fresh;
+
+
+/*
+ * Content-addressed definitions.
+ *
+ * A definition is identified by a hash of its structure rather than
+ * its name. Bound variables are hashed as de Bruijn indices, so
+ * alpha-variants get the same hash, and references to other
+ * definitions are hashed as the hash of the definition referred to.
+ * Names are just a table pointing at hashes: renaming a definition
+ * only touches that table, and two modules defining the same term
+ * share one stored copy.
+ *
+ * Definitions can't refer to themselves. Write recursion with a
+ * fixpoint combinator instead.
+ *
+ * Normal forms are cached by hash. Since a hash pins down everything
+ * a definition depends on, a cached entry never goes stale: changing
+ * a definition with `update` gives it and its dependents new hashes,
+ * and only their entries are dropped and recomputed.
+ *
+ * Since each definition is normalized on its own, the reduction steps
+ * spent on it, and the nodes they allocate, can be attributed to it
+ * exactly, which gives a profile of where the work in a program goes.
+ */
+
+
+/**
+ * The address of a definition in a `CodeStore`.
+ *
+ * This is 64-bit FNV-1a over the term's structure. It is stable
+ * across runs and platforms, as long as the `Hash` impls of the
+ * symbol and value types are.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Hash(pub u64);
+
+impl fmt::Display for Hash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{:016x}", self.0)
+    }
+}
+
+
+/* The integer writes are fixed-width little-endian, rather than the
+ * native-endian defaults, and `usize` is always hashed as 64 bits, so
+ * hashes don't depend on the platform. The signed writes default to
+ * these. */
+struct Fnv(u64);
+
+impl Hasher for Fnv {
+    fn write(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 ^= u64::from(*b);
+            self.0 = self.0.wrapping_mul(0x100_0000_01b3);
+        }
+    }
+
+    fn write_u8(&mut self, n: u8) {
+        self.write(&[n]);
+    }
+
+    fn write_u16(&mut self, n: u16) {
+        self.write(&n.to_le_bytes());
+    }
+
+    fn write_u32(&mut self, n: u32) {
+        self.write(&n.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        self.write(&n.to_le_bytes());
+    }
+
+    fn write_u128(&mut self, n: u128) {
+        self.write(&n.to_le_bytes());
+    }
+
+    fn write_usize(&mut self, n: usize) {
+        self.write_u64(n as u64);
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+
+/**
+ * A stored term, together with the definitions it refers to.
+ *
+ * `deps` maps each free variable of `expr` that named a definition
+ * when this one was added to that definition's hash. Later renames and
+ * redefinitions don't affect it.
+ */
+#[derive(Clone, Debug)]
+pub struct Definition<T: Types> {
+    pub expr: Expr<T>,
+    pub deps: BTreeMap<T::Sym, Hash>
+}
+
+
+/**
+ * Hash `expr`, resolving its free variables through `deps`.
+ */
+pub fn hash<T>(expr: &Expr<T>, deps: &BTreeMap<T::Sym, Hash>) -> Hash
+where T: Types, T::Sym: Ord + StdHash, T::Val: StdHash {
+    let mut h = Fnv(0xcbf2_9ce4_8422_2325);
+    hash_rec(expr, deps, &mut Vec::new(), &mut h);
+    Hash(h.finish())
+}
+
+fn hash_rec<T>(
+    expr: &Expr<T>,
+    deps: &BTreeMap<T::Sym, Hash>,
+    bound: &mut Vec<T::Sym>,
+    h: &mut Fnv
+) where T: Types, T::Sym: Ord + StdHash, T::Val: StdHash {
+    match expr {
+        Expr::Var(v) => match bound.iter().rev().position(|b| b == v) {
+            Some(index) => {
+                h.write_u8(0);
+                h.write_u64(index as u64);
+            },
+            None => match deps.get(v) {
+                Some(dep) => {
+                    h.write_u8(1);
+                    h.write_u64(dep.0);
+                },
+                None => {
+                    h.write_u8(2);
+                    v.hash(h);
+                }
+            }
+        },
+        Expr::Val(v) => {
+            h.write_u8(3);
+            v.hash(h);
+        },
+        Expr::Lambda(a, b) => {
+            h.write_u8(4);
+            bound.push(a.clone());
+            hash_rec(b, deps, bound, h);
+            bound.pop();
+        },
+        Expr::App(f, x) => {
+            h.write_u8(5);
+            hash_rec(f, deps, bound, h);
+            hash_rec(x, deps, bound, h);
+        }
+    }
+}
+
+
+#[derive(Debug)]
+pub enum NormalizeError<T: Types> {
+    // No definition with this hash is stored.
+    Unknown(Hash),
+    OutOfFuel,
+    Reduce(ReduceError<T>)
+}
+
+
+/**
+ * Reduction work attributed to one named definition.
+ *
+ * Work is counted in steps, and in nodes allocated: a contraction
+ * builds its contractum afresh, so it allocates as many nodes as the
+ * contractum has.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProfileEntry<S> {
+    pub name: S,
+    pub hash: Hash,
+    // Steps spent normalizing this definition's own body.
+    pub flat: usize,
+    // `flat`, plus the flat steps of everything it depends on,
+    // directly or not, each counted once.
+    pub cumulative: usize,
+    // Nodes allocated by those steps, likewise.
+    pub flat_nodes: usize,
+    pub cumulative_nodes: usize
+}
+
+
+/**
+ * Definitions stored by hash, plus a table of names for them.
+ */
+#[derive(Clone, Debug)]
+pub struct CodeStore<T: Types> where T::Sym: Ord {
+    defs: BTreeMap<Hash, Definition<T>>,
+    names: BTreeMap<T::Sym, Hash>,
+    normal_forms: BTreeMap<Hash, Expr<T>>,
+    // Steps spent, and nodes allocated, by `normal_form` on each hash.
+    steps: BTreeMap<Hash, usize>,
+    nodes: BTreeMap<Hash, usize>
+}
+
+impl<T> CodeStore<T> where T: Types + Clone, T::Sym: Ord + StdHash, T::Val: StdHash {
+    pub fn new() -> Self {
+        CodeStore {
+            defs: BTreeMap::new(),
+            names: BTreeMap::new(),
+            normal_forms: BTreeMap::new(),
+            steps: BTreeMap::new(),
+            nodes: BTreeMap::new()
+        }
+    }
+
+    /**
+     * Store `expr` and bind `name` to it, returning its hash.
+     *
+     * Free variables of `expr` which currently name definitions are
+     * resolved to those definitions. If an alpha-equivalent term with
+     * the same dependencies is already stored, it is reused.
+     */
+    pub fn add(&mut self, name: T::Sym, expr: Expr<T>) -> Hash {
+        let mut deps = BTreeMap::new();
+        self.resolve(&expr, &mut Vec::new(), &mut deps);
+        let hash = hash(&expr, &deps);
+        self.defs.entry(hash).or_insert(Definition {expr, deps});
+        self.names.insert(name, hash);
+        hash
+    }
+
+    fn resolve(
+        &self,
+        expr: &Expr<T>,
+        bound: &mut Vec<T::Sym>,
+        deps: &mut BTreeMap<T::Sym, Hash>
+    ) {
+        match expr {
+            Expr::Var(v) if !bound.contains(v) => {
+                if let Some(hash) = self.names.get(v) {
+                    deps.insert(v.clone(), *hash);
+                }
+            },
+            Expr::Var(_) | Expr::Val(_) => (),
+            Expr::Lambda(a, b) => {
+                bound.push(a.clone());
+                self.resolve(b, bound, deps);
+                bound.pop();
+            },
+            Expr::App(f, x) => {
+                self.resolve(f, bound, deps);
+                self.resolve(x, bound, deps);
+            }
+        }
+    }
+
+    /**
+     * Rebind `name` to `expr`, and propagate the change to everything
+     * that depends on it.
+     *
+     * Unlike `add`, which leaves existing definitions pointing at the
+     * old hash, this re-hashes every named definition that refers to
+     * the old one, transitively, and moves their names to the new
+     * hashes. Cached normal forms of the replaced hashes are dropped;
+     * the rest of the cache is kept. Returns the names of the
+     * dependents that changed, each once.
+     *
+     * `expr` may refer to the previous version of `name`, as in
+     * `f = succ f`; that reference keeps pointing at the old hash.
+     */
+    pub fn update(&mut self, name: T::Sym, expr: Expr<T>) -> Vec<T::Sym> {
+        let old = self.lookup(&name);
+        let new = self.add(name.clone(), expr);
+
+        let mut changed = Vec::new();
+        let mut work = Vec::new();
+        if let Some(old) = old.filter(|o| *o != new) {
+            work.push((name.clone(), old, new));
+        }
+        // A dependency is rewritten only where it goes by the name that
+        // moved: another name may happen to share the old hash.
+        while let Some((moved, old, new)) = work.pop() {
+            self.evict(old);
+
+            let dependents: Vec<(T::Sym, Hash)> = self.names.iter()
+                .filter(|(n, _)| **n != name)
+                .filter(|(_, h)| self.defs[h].deps.get(&moved) == Some(&old))
+                .map(|(n, h)| (n.clone(), *h))
+                .collect();
+
+            for (dependent, prev) in dependents {
+                let mut def = self.defs[&prev].clone();
+                def.deps.insert(moved.clone(), new);
+                let next = hash(&def.expr, &def.deps);
+                self.defs.entry(next).or_insert(def);
+                self.names.insert(dependent.clone(), next);
+                if !changed.contains(&dependent) {
+                    changed.push(dependent.clone());
+                }
+                work.push((dependent, prev, next));
+            }
+        }
+        changed
+    }
+
+    // Drop the cached normal form of `hash`, unless a name still
+    // refers to it.
+    fn evict(&mut self, hash: Hash) {
+        if !self.names.values().any(|h| *h == hash) {
+            self.normal_forms.remove(&hash);
+            self.steps.remove(&hash);
+            self.nodes.remove(&hash);
+        }
+    }
+
+    /**
+     * The normal form of the definition `hash`, computed with normal
+     * order reduction and cached.
+     *
+     * Each dependency is normalized first (hitting the cache where
+     * possible) and passed in as an argument: `g = f 0` is evaluated
+     * as `(\f.f 0) f'`, where `f'` is the normal form of `f`. `fuel`
+     * bounds the steps for each definition separately.
+     */
+    pub fn normal_form(&mut self, hash: Hash, fuel: usize) -> Result<Expr<T>, NormalizeError<T>>
+    where T::Sym: Fresh {
+        if let Some(cached) = self.normal_forms.get(&hash) {
+            return Ok(cached.clone());
+        }
+
+        let def = self.defs.get(&hash).ok_or(NormalizeError::Unknown(hash))?.clone();
+        let mut args = Vec::new();
+        for dep in def.deps.values() {
+            args.push(self.normal_form(*dep, fuel)?);
+        }
+
+        let mut term = def.expr;
+        for name in def.deps.keys().rev() {
+            term = Expr::Lambda(name.clone(), Box::new(term));
+        }
+        for arg in args {
+            term = Expr::App(Box::new(term), Box::new(arg));
+        }
+
+        // Step by hand rather than `run()`, to see each contractum.
+        let mut stepper = Stepper::new(Box::new(term), NormalOrder);
+        let mut nodes = 0;
+        let stop = loop {
+            if stepper.steps() == fuel {
+                break Ok(stepper.remaining_redexes().is_empty());
+            }
+            match stepper.step_forward() {
+                Ok(Some(step)) => nodes += stepper.term().at(&step.path).map_or(0, Expr::size),
+                Ok(None)       => break Ok(true),
+                Err(error)     => break Err(error)
+            }
+        };
+        *self.steps.entry(hash).or_insert(0) += stepper.steps();
+        *self.nodes.entry(hash).or_insert(0) += nodes;
+        if stop.map_err(NormalizeError::Reduce)? {
+            let normal = *stepper.into_term();
+            self.normal_forms.insert(hash, normal.clone());
+            Ok(normal)
+        } else {
+            Err(NormalizeError::OutOfFuel)
+        }
+    }
+
+    /**
+     * A flat and cumulative profile of the steps taken by
+     * `normal_form`, one entry per name, most expensive first.
+     *
+     * Cached results cost nothing, so a definition's steps are counted
+     * when it is first normalized, whichever definition asked for it.
+     */
+    pub fn profile(&self) -> Vec<ProfileEntry<T::Sym>> {
+        let mut entries: Vec<ProfileEntry<T::Sym>> = self.names.iter()
+            .map(|(name, hash)| {
+                let mut seen = Vec::new();
+                self.closure(*hash, &mut seen);
+                ProfileEntry {
+                    name: name.clone(),
+                    hash: *hash,
+                    flat: self.flat_steps(*hash),
+                    cumulative: seen.iter().map(|h| self.flat_steps(*h)).sum(),
+                    flat_nodes: self.flat_nodes(*hash),
+                    cumulative_nodes: seen.iter().map(|h| self.flat_nodes(*h)).sum()
+                }
+            })
+            .collect();
+        entries.sort_by_key(|e| core::cmp::Reverse(e.cumulative));
+        entries
+    }
+
+    fn flat_steps(&self, hash: Hash) -> usize {
+        self.steps.get(&hash).copied().unwrap_or(0)
+    }
+
+    fn flat_nodes(&self, hash: Hash) -> usize {
+        self.nodes.get(&hash).copied().unwrap_or(0)
+    }
+
+    // Push `hash` and everything it depends on onto `seen`, once each.
+    fn closure(&self, hash: Hash, seen: &mut Vec<Hash>) {
+        if seen.contains(&hash) {
+            return;
+        }
+        seen.push(hash);
+        if let Some(def) = self.defs.get(&hash) {
+            for dep in def.deps.values() {
+                self.closure(*dep, seen);
+            }
+        }
+    }
+
+    /**
+     * The profile below `root` in collapsed-stack format, for
+     * flamegraph tools: one `root;dep;dep2 steps` line per definition
+     * that took any steps.
+     *
+     * Frames are named as the depending definition refers to them. A
+     * definition needed along several paths is charged to the first
+     * one, in depth-first order, since that's where it was evaluated.
+     */
+    pub fn collapsed_stacks(&self, root: &T::Sym) -> Option<String>
+    where T::Sym: fmt::Display {
+        let hash = self.lookup(root)?;
+        let mut out = String::new();
+        self.collapse(hash, &mut vec![root.to_string()], &mut Vec::new(), &mut out);
+        Some(out)
+    }
+
+    fn collapse(&self, hash: Hash, stack: &mut Vec<String>, seen: &mut Vec<Hash>, out: &mut String)
+    where T::Sym: fmt::Display {
+        if seen.contains(&hash) {
+            return;
+        }
+        seen.push(hash);
+
+        let steps = self.flat_steps(hash);
+        if steps > 0 {
+            out.push_str(&format!("{} {}\n", stack.join(";"), steps));
+        }
+        if let Some(def) = self.defs.get(&hash) {
+            for (name, dep) in &def.deps {
+                stack.push(name.to_string());
+                self.collapse(*dep, stack, seen, out);
+                stack.pop();
+            }
+        }
+    }
+
+    /* The cached normal form of `hash`, if there is one. */
+    pub fn cached(&self, hash: Hash) -> Option<&Expr<T>> {
+        self.normal_forms.get(&hash)
+    }
+
+    pub fn get(&self, hash: Hash) -> Option<&Definition<T>> {
+        self.defs.get(&hash)
+    }
+
+    pub fn lookup(&self, name: &T::Sym) -> Option<Hash> {
+        self.names.get(name).copied()
+    }
+
+    /* All names currently bound to `hash`. */
+    pub fn names_of(&self, hash: Hash) -> impl Iterator<Item = &T::Sym> {
+        self.names.iter().filter(move |(_, h)| **h == hash).map(|(n, _)| n)
+    }
+
+    /**
+     * Move the name `old` to `new`, returning the hash it points to.
+     *
+     * Nothing stored changes: definitions refer to each other by hash.
+     */
+    pub fn rename(&mut self, old: &T::Sym, new: T::Sym) -> Option<Hash> {
+        let hash = self.names.remove(old)?;
+        self.names.insert(new, hash);
+        Some(hash)
+    }
+
+    /* The number of distinct definitions stored. */
+    pub fn len(&self) -> usize {
+        self.defs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.defs.is_empty()
+    }
+}
+
+impl<T> Default for CodeStore<T> where T: Types + Clone, T::Sym: Ord + StdHash, T::Val: StdHash {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trs::SigmaRules;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct StoreTypes;
+
+    #[derive(Clone, Debug, PartialEq, Hash)]
+    enum Int {
+        Lit(i32),
+        Succ
+    }
+    use Int::*;
+
+    impl SigmaRules for Int {
+        type Error = ();
+
+        fn apply(f: Self, x: Self) -> Result<Self, ()> {
+            match (f, x) {
+                (Succ, Lit(n)) => Ok(Lit(n + 1)),
+                _              => Err(())
+            }
+        }
+    }
+
+    impl Types for StoreTypes {
+        type Val = Int;
+        type Sym = String;
+    }
+
+    type E = Expr<StoreTypes>;
+
+    fn konst(x: &'static str, y: &'static str) -> E {
+        *E::lambda(x, E::lambda(y, E::var(x)))
+    }
+
+    #[test]
+    fn test_fixed_width_writes() {
+        let fnv = |f: &dyn Fn(&mut Fnv)| {
+            let mut h = Fnv(0xcbf2_9ce4_8422_2325);
+            f(&mut h);
+            h.finish()
+        };
+        let bytes = fnv(&|h| h.write(&[1, 0, 0, 0, 0, 0, 0, 0]));
+        assert_eq!(fnv(&|h| h.write_usize(1)), bytes);
+        assert_eq!(fnv(&|h| h.write_isize(1)), bytes);
+        assert_eq!(fnv(&|h| h.write_u64(1)), bytes);
+        assert_eq!(fnv(&|h| h.write_u32(0x0403_0201)), fnv(&|h| h.write(&[1, 2, 3, 4])));
+    }
+
+    #[test]
+    fn test_alpha_variants_share_a_hash() {
+        let mut store = CodeStore::<StoreTypes>::new();
+        let k = store.add("k".into(), konst("x", "y"));
+        let const_ = store.add("const".into(), konst("a", "b"));
+        assert_eq!(k, const_);
+        assert_eq!(store.len(), 1);
+        assert_eq!(store.names_of(k).collect::<Vec<_>>(), vec!["const", "k"]);
+
+        // \x.\y.y is a different term.
+        let other = store.add("other".into(), *E::lambda("x", E::lambda("y", E::var("y"))));
+        assert_ne!(k, other);
+        assert_eq!(store.len(), 2);
+    }
+
+    #[test]
+    fn test_rename_keeps_dependents() {
+        let mut store = CodeStore::<StoreTypes>::new();
+        let k = store.add("k".into(), konst("x", "y"));
+        let zero = store.add("zero".into(), *E::apply(E::var("k"), E::val(Lit(0))));
+        assert_eq!(store.get(zero).unwrap().deps.get("k"), Some(&k));
+
+        assert_eq!(store.rename(&"k".into(), "const".into()), Some(k));
+        assert_eq!(store.lookup(&"k".into()), None);
+        assert_eq!(store.lookup(&"const".into()), Some(k));
+
+        // Re-adding the dependent under the new name of its dependency
+        // finds the same definition.
+        let again = store.add("zero".into(), *E::apply(E::var("const"), E::val(Lit(0))));
+        assert_eq!(again, zero);
+        assert_eq!(store.len(), 2);
+    }
+
+    #[test]
+    fn test_dependencies_are_hashed_by_content() {
+        let mut store = CodeStore::<StoreTypes>::new();
+        let call = || *E::apply(E::var("f"), E::val(Lit(0)));
+
+        // With f unbound, the reference is hashed by name.
+        let unresolved = store.add("g".into(), call());
+        assert!(store.get(unresolved).unwrap().deps.is_empty());
+
+        store.add("f".into(), konst("x", "y"));
+        let first = store.add("g".into(), call());
+        store.add("f".into(), *E::lambda("x", E::var("x")));
+        let second = store.add("g".into(), call());
+
+        assert_ne!(unresolved, first);
+        assert_ne!(first, second);
+        assert_eq!(store.len(), 5);
+    }
+
+    #[test]
+    fn test_normal_form() {
+        let mut store = CodeStore::<StoreTypes>::new();
+        store.add("one".into(), *E::apply(E::val(Succ), E::val(Lit(0))));
+        let two = store.add("two".into(), *E::apply(E::val(Succ), E::var("one")));
+
+        assert_eq!(store.normal_form(two, 100).unwrap(), Expr::Val(Lit(2)));
+        assert_eq!(store.cached(store.lookup(&"one".into()).unwrap()), Some(&Expr::Val(Lit(1))));
+        assert!(matches!(
+            store.normal_form(Hash(0), 100),
+            Err(NormalizeError::Unknown(Hash(0)))
+        ));
+    }
+
+    #[test]
+    fn test_update_invalidates_dependents_only() {
+        let mut store = CodeStore::<StoreTypes>::new();
+        let one = store.add("one".into(), *E::apply(E::val(Succ), E::val(Lit(0))));
+        let two = store.add("two".into(), *E::apply(E::val(Succ), E::var("one")));
+        let three = store.add("three".into(), *E::apply(E::val(Succ), E::var("two")));
+        let other = store.add("other".into(), *E::apply(E::val(Succ), E::val(Lit(9))));
+        store.normal_form(three, 100).unwrap();
+        store.normal_form(other, 100).unwrap();
+
+        // one = 10
+        let changed = store.update("one".into(), Expr::Val(Lit(10)));
+        assert_eq!(changed, vec!["two", "three"]);
+
+        assert_eq!(store.cached(one), None);
+        assert_eq!(store.cached(two), None);
+        assert_eq!(store.cached(three), None);
+        assert_eq!(store.cached(other), Some(&Expr::Val(Lit(10))));
+
+        let three = store.lookup(&"three".into()).unwrap();
+        assert_eq!(store.normal_form(three, 100).unwrap(), Expr::Val(Lit(12)));
+    }
+
+    #[test]
+    fn test_update_refers_to_previous_version() {
+        let mut store = CodeStore::<StoreTypes>::new();
+        store.add("f".into(), Expr::Val(Lit(1)));
+        let g = || *E::apply(E::val(Succ), E::var("f"));
+        store.add("g".into(), g());
+
+        // f = Succ f, where f is the old f.
+        let changed = store.update("f".into(), g());
+        assert_eq!(changed, vec!["g"]);
+        let f = store.lookup(&"f".into()).unwrap();
+        assert_eq!(store.normal_form(f, 100).unwrap(), Expr::Val(Lit(2)));
+        let g = store.lookup(&"g".into()).unwrap();
+        assert_eq!(store.normal_form(g, 100).unwrap(), Expr::Val(Lit(3)));
+    }
+
+    #[test]
+    fn test_update_diamond() {
+        let mut store = CodeStore::<StoreTypes>::new();
+        let succ = |name| *E::apply(E::val(Succ), E::var(name));
+        store.add("a".into(), Expr::Val(Lit(0)));
+        store.add("b".into(), succ("a"));
+        store.add("c".into(), succ("a"));
+        store.add("d".into(), *E::apply(
+            E::apply(E::lambda("x", E::lambda("y", E::var("x"))), E::var("b")),
+            E::var("c")
+        ));
+
+        let mut changed = store.update("a".into(), Expr::Val(Lit(5)));
+        changed.sort();
+        assert_eq!(changed, vec!["b", "c", "d"]);
+        let d = store.lookup(&"d".into()).unwrap();
+        assert_eq!(store.get(d).unwrap().deps.get("c"), store.lookup(&"c".into()).as_ref());
+        assert_eq!(store.normal_form(d, 100).unwrap(), Expr::Val(Lit(6)));
+    }
+
+    #[test]
+    fn test_profile() {
+        let mut store = CodeStore::<StoreTypes>::new();
+        let succ = |e| E::apply(E::val(Succ), e);
+
+        // one: 1 step. two: 1 step to bind `one`, 1 for Succ.
+        // four: 1 step to bind `two`, 2 for the Succs. A sigma step
+        // allocates the one node of its result, and binding `two`
+        // builds Succ (Succ 2), five nodes.
+        store.add("one".into(), *succ(E::val(Lit(0))));
+        store.add("two".into(), *succ(E::var("one")));
+        let four = store.add("four".into(), *succ(succ(E::var("two"))));
+        store.add("unused".into(), *succ(E::val(Lit(5))));
+        store.normal_form(four, 100).unwrap();
+
+        let entry = |name: &str, (flat, cumulative), (flat_nodes, cumulative_nodes)| {
+            ProfileEntry {
+                name: name.into(),
+                hash: store.lookup(&name.into()).unwrap(),
+                flat,
+                cumulative,
+                flat_nodes,
+                cumulative_nodes
+            }
+        };
+        assert_eq!(store.profile(), vec![
+            entry("four", (3, 6), (7, 12)),
+            entry("two", (2, 3), (4, 5)),
+            entry("one", (1, 1), (1, 1)),
+            entry("unused", (0, 0), (0, 0)),
+        ]);
+
+        assert_eq!(
+            store.collapsed_stacks(&"four".into()).unwrap(),
+            "four 3\nfour;two 2\nfour;two;one 1\n"
+        );
+        assert_eq!(store.collapsed_stacks(&"missing".into()), None);
+    }
+}
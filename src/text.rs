@@ -0,0 +1,160 @@
+// The MIT License (MIT)
+//
+// Copyright © 2022 <Brandon Lewis>
+//
+// Permission is hereby granted, free of charge, to any person
+// obtaining a copy of this software and associated documentation
+// files (the “Software”), to deal in the Software without
+// restriction, including without limitation the rights to use, copy,
+// modify, merge, publish, distribute, sublicense, and/or sell copies
+// of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS
+// BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN
+// ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+// Fork this project to create your own MIT license that you can
+// always link to.
+use crate::trs::Types;
+use crate::expr::Expr;
+use crate::num::Num;
+
+
+/*
+ * Strings as lists of characters.
+ *
+ * There is no string value: a string is a Scott-encoded list of
+ * `Num::Char`, so programs take it apart with ordinary application.
+ *
+ *   nil       = λc.λn.n
+ *   cons h t  = λc.λn.c h t
+ *
+ * `list` builds such a term from a Rust string, and `read` recovers
+ * the string from a normal form, whatever its binders are called.
+ */
+
+
+/* The Scott-encoded list of the characters of `s`. Every cell binds
+ * the same `c` and `n`. */
+pub fn list<T: Types + Clone>(s: &str) -> Expr<T>
+where T::Val: From<Num>, T::Sym: From<&'static str> {
+    let nil = Expr::lambda("c", Expr::lambda("n", Expr::var("n")));
+    let list = s.chars().rev().fold(nil, |tail, ch| {
+        let cons = Expr::apply(
+            Expr::apply(Expr::var("c"), Expr::val(Num::Char(ch))),
+            tail
+        );
+        Expr::lambda("c", Expr::lambda("n", cons))
+    });
+    *list
+}
+
+
+/**
+ * The string a term spells out, if it is a fully evaluated list of
+ * characters.
+ */
+pub fn read<T: Types<Val = Num>>(expr: &Expr<T>) -> Option<String> {
+    let mut out = String::new();
+    let mut cur = expr;
+    loop {
+        let (c, n, body) = match cur {
+            Expr::Lambda(c, body) => match &**body {
+                Expr::Lambda(n, body) => (c, n, &**body),
+                _ => return None
+            },
+            _ => return None
+        };
+        match body {
+            Expr::Var(v) if v == n && v != c => return Some(out),
+            Expr::App(f, tail) => match &**f {
+                Expr::App(g, h) => match (&**g, &**h) {
+                    (Expr::Var(v), Expr::Val(Num::Char(ch)))
+                        if v == c && v != n =>
+                    {
+                        out.push(*ch);
+                        cur = tail;
+                    },
+                    _ => return None
+                },
+                _ => return None
+            },
+            _ => return None
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::num::{Primitives, Overflow};
+    use crate::stepper::{Stepper, NormalOrder, Stop};
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct TextTypes;
+
+    impl Types for TextTypes {
+        type Val = Num;
+        type Sym = String;
+    }
+
+    type E = Expr<TextTypes>;
+
+    fn normalize(term: E) -> E {
+        let mut stepper = Stepper::new(Box::new(term), NormalOrder);
+        assert_eq!(stepper.run(1000).unwrap(), Stop::NormalForm);
+        stepper.term().clone()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let hi: E = list("hi");
+        assert_eq!(hi.to_string(), "λc.λn.c 'h' (λc.λn.c 'i' (λc.λn.n))");
+        assert_eq!(read(&hi), Some("hi".to_string()));
+        assert_eq!(read(&list::<TextTypes>("")), Some(String::new()));
+        assert_eq!(read(&list::<TextTypes>("λ→x")), Some("λ→x".to_string()));
+
+        // Not lists: a bare value, and a cons cell of a non-char.
+        assert_eq!(read(&*E::val(1)), None);
+        let bad = E::lambda("c", E::lambda("n", E::apply(
+            E::apply(E::var("c"), E::val(1)),
+            Box::new(list("")))
+        ));
+        assert_eq!(read(&*bad), None);
+    }
+
+    #[test]
+    fn test_programs() {
+        let prims = Primitives::new(Overflow::Checked);
+        let ord = E::val(prims.lookup("ord").unwrap());
+
+        // The codepoint of the head: (λl.l (λh.λt.ord h) 0) "hi"
+        let head = E::lambda("l", E::apply(
+            E::apply(
+                E::var("l"),
+                E::lambda("h", E::lambda("t", E::apply(ord, E::var("h"))))
+            ),
+            E::val(0)
+        ));
+        let term = *E::apply(head, Box::new(list("hi")));
+        assert_eq!(normalize(term), Expr::Val(Num::Int(104)));
+
+        // The tail: (λl.l (λh.λt.t) nil) "hi"
+        let tail = E::lambda("l", E::apply(
+            E::apply(E::var("l"), E::lambda("h", E::lambda("t", E::var("t")))),
+            Box::new(list(""))
+        ));
+        let term = *E::apply(tail, Box::new(list("hi")));
+        assert_eq!(read(&normalize(term)), Some("i".to_string()));
+    }
+}
@@ -53,6 +53,16 @@ pub trait SigmaRules: Sized {
     fn apply(_f: Self, _x: Self) -> Result<Self, Self::Error> {
         Err(Self::Error::default())
     }
+
+    /* If this value is a boolean, which one.
+     *
+     * A boolean selects between its next two arguments, like a Church
+     * boolean: `true t e` reduces to `t`, and `false t e` to `e`. This
+     * lets a primitive comparison be the test of an `if`.
+     */
+    fn select(&self) -> Option<bool> {
+        None
+    }
 }
 
 
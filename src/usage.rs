@@ -0,0 +1,194 @@
+// The MIT License (MIT)
+//
+// Copyright © 2022 <Brandon Lewis>
+//
+// Permission is hereby granted, free of charge, to any person
+// obtaining a copy of this software and associated documentation
+// files (the “Software”), to deal in the Software without
+// restriction, including without limitation the rights to use, copy,
+// modify, merge, publish, distribute, sublicense, and/or sell copies
+// of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS
+// BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN
+// ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+// Fork this project to create your own MIT license that you can
+// always link to.
+use crate::trs::Types;
+use crate::expr::{Expr, Dir, Path};
+
+
+/*
+ * Usage checking for lambda-bound variables.
+ *
+ * In a linear calculus every bound variable is used exactly once; in
+ * an affine one, at most once. The count is syntactic, so passing the
+ * affine check doesn't make a binder safe to inline: a single use
+ * under a lambda can still be evaluated many times, once per call of
+ * that lambda. `occurrence::Occurrence::inline_is_safe` accounts for
+ * that.
+ */
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Discipline {
+    // Exactly once
+    Linear,
+    // At most once
+    Affine
+}
+
+impl Discipline {
+    fn allows(self, uses: usize) -> bool {
+        match self {
+            Discipline::Linear => uses == 1,
+            Discipline::Affine => uses <= 1
+        }
+    }
+}
+
+
+/**
+ * A lambda whose variable is used the wrong number of times.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Violation<T: Types> {
+    // Path to the offending lambda
+    pub path: Path,
+    pub binder: T::Sym,
+    pub uses: usize
+}
+
+
+/**
+ * Check every lambda in `expr` against `discipline`.
+ *
+ * Occurrences are counted syntactically, with inner binders of the
+ * same name shadowing outer ones. Violations are reported in
+ * pre-order, outermost lambda first.
+ */
+pub fn check<T: Types>(expr: &Expr<T>, discipline: Discipline) -> Vec<Violation<T>> {
+    let mut violations = Vec::new();
+    check_rec(expr, discipline, &mut Vec::new(), &mut Vec::new(), &mut violations);
+    violations.sort_by(|a, b| a.path.cmp(&b.path));
+    violations
+}
+
+fn check_rec<T: Types>(
+    expr: &Expr<T>,
+    discipline: Discipline,
+    here: &mut Path,
+    scope: &mut Vec<(T::Sym, usize)>,
+    violations: &mut Vec<Violation<T>>
+) {
+    match expr {
+        Expr::Var(v) => {
+            if let Some(entry) = scope.iter_mut().rev().find(|(s, _)| s == v) {
+                entry.1 += 1;
+            }
+        },
+        Expr::Val(_) => (),
+        Expr::Lambda(a, b) => {
+            scope.push((a.clone(), 0));
+            here.push(Dir::Body);
+            check_rec(b, discipline, here, scope, violations);
+            here.pop();
+            let (binder, uses) = scope.pop().expect("scope pushed above");
+            if !discipline.allows(uses) {
+                violations.push(Violation {path: here.clone(), binder, uses});
+            }
+        },
+        Expr::App(f, x) => {
+            here.push(Dir::Func);
+            check_rec(f, discipline, here, scope, violations);
+            here.pop();
+            here.push(Dir::Arg);
+            check_rec(x, discipline, here, scope, violations);
+            here.pop();
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trs::SigmaRules;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct UsageTypes;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Unit;
+
+    impl SigmaRules for Unit {
+        type Error = ();
+    }
+
+    impl Types for UsageTypes {
+        type Val = Unit;
+        type Sym = &'static str;
+    }
+
+    type E = Expr<UsageTypes>;
+    use Dir::*;
+
+    #[test]
+    fn test_linear() {
+        // \f.\x.f x
+        let apply = E::lambda("f", E::lambda("x", E::apply(E::var("f"), E::var("x"))));
+        assert_eq!(check(&apply, Discipline::Linear), vec![]);
+
+        // \x.\y.x
+        let konst = E::lambda("x", E::lambda("y", E::var("x")));
+        assert_eq!(
+            check(&konst, Discipline::Linear),
+            vec![Violation {path: vec![Body], binder: "y", uses: 0}]
+        );
+        assert_eq!(check(&konst, Discipline::Affine), vec![]);
+    }
+
+    #[test]
+    fn test_affine() {
+        // \x.x x
+        let dup = E::lambda("x", E::apply(E::var("x"), E::var("x")));
+        assert_eq!(
+            check(&dup, Discipline::Affine),
+            vec![Violation {path: vec![], binder: "x", uses: 2}]
+        );
+    }
+
+    #[test]
+    fn test_shadowing() {
+        // \x.(\x.x) x: each x is used once, by its own binder.
+        let term = E::lambda("x", E::apply(
+            E::lambda("x", E::var("x")),
+            E::var("x")
+        ));
+        assert_eq!(check(&term, Discipline::Linear), vec![]);
+
+        // \x.(\x.x x) (\y.y): the inner x is used twice, the outer not
+        // at all.
+        let term = E::lambda("x", E::apply(
+            E::lambda("x", E::apply(E::var("x"), E::var("x"))),
+            E::lambda("y", E::var("y"))
+        ));
+        assert_eq!(
+            check(&term, Discipline::Linear),
+            vec![
+                Violation {path: vec![], binder: "x", uses: 0},
+                Violation {path: vec![Body, Func], binder: "x", uses: 2},
+            ]
+        );
+    }
+}
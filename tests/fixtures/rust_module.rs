@@ -0,0 +1,37 @@
+use std::rc::Rc;
+use fgrs_toolbox::trs::SigmaRules;
+
+#[derive(Clone)]
+pub enum Value {
+    Fun(Rc<dyn Fn(Value) -> Value>),
+    Val(Num),
+}
+
+pub fn app(f: Value, x: Value) -> Value {
+    match (f, x) {
+        (Value::Fun(f), x) => f(x),
+        (Value::Val(f), x) => match (f.select(), x) {
+            (Some(true), x) => Value::Fun(Rc::new(move |_| x.clone())),
+            (Some(false), _) => Value::Fun(Rc::new(|e| e)),
+            (None, Value::Val(x)) => Value::Val(
+                <Num as SigmaRules>::apply(f, x).expect("primitive failed")
+            ),
+            (None, Value::Fun(_)) => panic!("value applied to a function"),
+        },
+    }
+}
+
+pub fn select(c: Value, t: impl FnOnce() -> Value, e: impl FnOnce() -> Value) -> Value {
+    match &c {
+        Value::Val(v) if v.select() == Some(true) => t(),
+        Value::Val(v) if v.select() == Some(false) => e(),
+        _ => {
+            let f = app(c, t());
+            app(f, e())
+        }
+    }
+}
+
+pub fn compute() -> Value {
+    app(Value::Fun(Rc::new(move |v0: Value| app({ let v0 = v0.clone(); Value::Fun(Rc::new(move |v1: Value| app(app(Value::Val(Num::prim(Op::Add, Overflow::Checked)), app(v0.clone(), Value::Val(Num::Int(5)))), select(v1.clone(), || Value::Val(Num::Int(0)), || Value::Val(Num::Int(1)))))) }, Value::Fun(Rc::new(move |v2: Value| { let v2 = v2.clone(); Value::Fun(Rc::new(move |_v3: Value| v2.clone())) }))))), app(Value::Fun(Rc::new(move |v4: Value| app({ let v4 = v4.clone(); Value::Fun(Rc::new(move |v5: Value| app(v4.clone(), { let v5 = v5.clone(); Value::Fun(Rc::new(move |v6: Value| select(v5.clone(), || v5.clone(), || v6.clone()))) }))) }, { let v4 = v4.clone(); Value::Fun(Rc::new(move |v7: Value| app(v4.clone(), { let v7 = v7.clone(); Value::Fun(Rc::new(move |v8: Value| select(v7.clone(), || v7.clone(), || v8.clone()))) }))) }))), Value::Fun(Rc::new(move |v9: Value| { let v9 = v9.clone(); Value::Fun(Rc::new(move |v10: Value| select(app(app(Value::Val(Num::prim(Op::Eq, Overflow::Checked)), v10.clone()), Value::Val(Num::Int(0))), || Value::Val(Num::Int(1)), || app(app(Value::Val(Num::prim(Op::Mul, Overflow::Checked)), v10.clone()), app(v9.clone(), app(app(Value::Val(Num::prim(Op::Sub, Overflow::Checked)), v10.clone()), Value::Val(Num::Int(1)))))))) }))))
+}
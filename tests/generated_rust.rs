@@ -0,0 +1,106 @@
+// The MIT License (MIT)
+//
+// Copyright © 2022 <Brandon Lewis>
+//
+// Permission is hereby granted, free of charge, to any person
+// obtaining a copy of this software and associated documentation
+// files (the “Software”), to deal in the Software without
+// restriction, including without limitation the rights to use, copy,
+// modify, merge, publish, distribute, sublicense, and/or sell copies
+// of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS
+// BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN
+// ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+// Fork this project to create your own MIT license that you can
+// always link to.
+
+use fgrs_toolbox::trs::Types;
+use fgrs_toolbox::expr::Expr;
+use fgrs_toolbox::num::{Num, Overflow, Primitives};
+use fgrs_toolbox::rust::module;
+use fgrs_toolbox::scheme;
+use fgrs_toolbox::stepper::{Stepper, NormalOrder, Stop};
+
+
+/*
+ * Building and running the output of `rust::module`.
+ *
+ * `fixtures/rust_module.rs` is generated code, compiled into this test
+ * as is. It is kept by hand rather than as a snapshot, so that a
+ * change to the generator can't leave the build broken: when
+ * `test_fixture_is_current` fails, check the new output compiles and
+ * paste it into the fixture.
+ */
+
+
+mod generated {
+    use fgrs_toolbox::num::{Num, Op, Overflow};
+    include!("fixtures/rust_module.rs");
+}
+
+
+#[derive(Clone, Debug, PartialEq)]
+struct NumTypes;
+
+impl Types for NumTypes {
+    type Val = Num;
+    type Sym = String;
+}
+
+type E = Expr<NumTypes>;
+
+// Recursion through an `if`, which only stops if the branch not taken
+// is never evaluated, and a binder that is never used.
+const PROGRAM: &str = "
+    (define (fact n) (if (== n 0) 1 (* n (fact (- n 1)))))
+    (define (first a b) a)
+    (+ (fact 5) (first 0 1))
+";
+
+fn program() -> E {
+    let term: E = scheme::read(PROGRAM).unwrap();
+    Primitives::new(Overflow::Checked).resolve(&term)
+}
+
+fn literal(v: &Num) -> String {
+    match v {
+        Num::Int(n)  => format!("Num::Int({})", n),
+        Num::Bool(b) => format!("Num::Bool({})", b),
+        Num::Prim(p) => format!("Num::prim(Op::{:?}, Overflow::{:?})", p.op, p.overflow),
+        other        => panic!("no literal for {:?}", other)
+    }
+}
+
+#[test]
+fn test_fixture_is_current() {
+    let src = module("compute", &program(), "Num", literal).unwrap();
+    assert_eq!(
+        src,
+        include_str!("fixtures/rust_module.rs"),
+        "the generator's output changed; update tests/fixtures/rust_module.rs"
+    );
+}
+
+#[test]
+fn test_generated_runs() {
+    // The same answer as the stepper.
+    let mut stepper = Stepper::new(Box::new(program()), NormalOrder);
+    assert_eq!(stepper.run(10_000).unwrap(), Stop::NormalForm);
+    assert_eq!(*stepper.term(), Expr::Val(Num::Int(120)));
+
+    match generated::compute() {
+        generated::Value::Val(n) => assert_eq!(n, Num::Int(120)),
+        generated::Value::Fun(_) => panic!("expected a value")
+    }
+}